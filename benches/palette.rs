@@ -0,0 +1,73 @@
+//! Ranking speed of the command palette, with as many candidates as a long history gives.
+//!
+//! Needs nightly, run with `cargo bench --bench palette`.
+
+#![feature(test)]
+
+extern crate test;
+
+#[path = "../src/fuzzy.rs"]
+mod fuzzy;
+#[allow(dead_code)]
+#[path = "../src/palette.rs"]
+mod palette;
+
+use palette::{rank_candidates, Candidate, CandidateSource};
+use test::{black_box, Bencher};
+
+const FOLDERS: [&str; 8] = [
+    "Documents",
+    "Downloads",
+    "Projects",
+    "Pictures",
+    "Music",
+    "Videos",
+    "Desktop",
+    "Archive",
+];
+
+fn candidates(count: usize) -> Vec<Candidate> {
+    (0..count)
+        .map(|i| {
+            let title = format!("{} {}", FOLDERS[i % FOLDERS.len()], i);
+            let source = match i % 10 {
+                0 => CandidateSource::Tab,
+                1 => CandidateSource::Bookmark,
+                _ => CandidateSource::History,
+            };
+            Candidate {
+                detail: format!("C:\\Users\\me\\{}\\{}", FOLDERS[(i / 8) % 8], title),
+                title,
+                source,
+            }
+        })
+        .collect()
+}
+
+#[bench]
+fn rank_short_query(b: &mut Bencher) {
+    let candidates = candidates(1000);
+    b.iter(|| rank_candidates(black_box("doc"), &candidates, 20));
+}
+
+#[bench]
+fn rank_long_query(b: &mut Bencher) {
+    let candidates = candidates(1000);
+    b.iter(|| rank_candidates(black_box("users projects 42"), &candidates, 20));
+}
+
+#[bench]
+fn rank_empty_query(b: &mut Bencher) {
+    let candidates = candidates(1000);
+    b.iter(|| rank_candidates(black_box(""), &candidates, 20));
+}
+
+#[bench]
+fn score_path(b: &mut Bencher) {
+    b.iter(|| {
+        fuzzy::fuzzy_score(
+            black_box("prjdoc"),
+            black_box("C:\\Users\\me\\Projects\\extabbar\\Documentation"),
+        )
+    });
+}
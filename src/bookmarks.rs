@@ -0,0 +1,370 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::get_dll_path;
+use crate::idl::Idl;
+
+// Indices from the root down to a node
+pub type NodePath = [usize];
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum BookmarkNode {
+    Bookmark {
+        name: String,
+        path: String,
+        // Serialized id list, see `Idl::to_hex`
+        idl: String,
+    },
+    Folder {
+        name: String,
+        children: Vec<BookmarkNode>,
+    },
+}
+
+impl BookmarkNode {
+    pub fn name(&self) -> &str {
+        match self {
+            BookmarkNode::Bookmark { name, .. } | BookmarkNode::Folder { name, .. } => name,
+        }
+    }
+
+    pub fn get_idl(&self) -> Option<Idl> {
+        match self {
+            BookmarkNode::Bookmark { idl, .. } => Idl::from_hex(idl),
+            BookmarkNode::Folder { .. } => None,
+        }
+    }
+
+    // Same kind and name, and same folder for bookmarks. The content of folders may differ.
+    fn is_same(&self, other: &BookmarkNode) -> bool {
+        match (self, other) {
+            (
+                BookmarkNode::Bookmark { name, idl, .. },
+                BookmarkNode::Bookmark {
+                    name: other_name,
+                    idl: other_idl,
+                    ..
+                },
+            ) => name == other_name && idl == other_idl,
+            (
+                BookmarkNode::Folder { name, .. },
+                BookmarkNode::Folder {
+                    name: other_name, ..
+                },
+            ) => name == other_name,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct Bookmarks {
+    items: Vec<BookmarkNode>,
+}
+
+impl Bookmarks {
+    fn load(path: &Path) -> Self {
+        || -> Result<Bookmarks, Box<dyn Error>> {
+            let file = std::fs::File::open(path)?;
+            Ok(serde_json::from_reader(file)?)
+        }()
+        .unwrap_or_default()
+    }
+
+    fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let file = std::fs::File::create(path)?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    /// Content of a folder, the empty path being the root.
+    pub fn children(&self, folder: &NodePath) -> Option<&[BookmarkNode]> {
+        let mut children = &self.items;
+        for index in folder {
+            children = match children.get(*index)? {
+                BookmarkNode::Folder { children, .. } => children,
+                BookmarkNode::Bookmark { .. } => return None,
+            };
+        }
+        Some(children)
+    }
+
+    fn children_mut(&mut self, folder: &NodePath) -> Option<&mut Vec<BookmarkNode>> {
+        let mut children = &mut self.items;
+        for index in folder {
+            children = match children.get_mut(*index)? {
+                BookmarkNode::Folder { children, .. } => children,
+                BookmarkNode::Bookmark { .. } => return None,
+            };
+        }
+        Some(children)
+    }
+
+    /// Every bookmark, folders first to last.
+    pub fn all_bookmarks(&self) -> Vec<&BookmarkNode> {
+        fn collect<'a>(nodes: &'a [BookmarkNode], output: &mut Vec<&'a BookmarkNode>) {
+            for node in nodes {
+                match node {
+                    BookmarkNode::Bookmark { .. } => output.push(node),
+                    BookmarkNode::Folder { children, .. } => collect(children, output),
+                }
+            }
+        }
+        let mut output = Vec::new();
+        collect(&self.items, &mut output);
+        output
+    }
+
+    /// Paths of every folder, the root first and each folder before its subfolders.
+    pub fn all_folders(&self) -> Vec<Vec<usize>> {
+        fn collect(nodes: &[BookmarkNode], path: &mut Vec<usize>, output: &mut Vec<Vec<usize>>) {
+            output.push(path.clone());
+            for (index, node) in nodes.iter().enumerate() {
+                if let BookmarkNode::Folder { children, .. } = node {
+                    path.push(index);
+                    collect(children, path, output);
+                    path.pop();
+                }
+            }
+        }
+        let mut output = Vec::new();
+        collect(&self.items, &mut Vec::new(), &mut output);
+        output
+    }
+
+    pub fn get(&self, path: &NodePath) -> Option<&BookmarkNode> {
+        let (index, folder) = path.split_last()?;
+        self.children(folder)?.get(*index)
+    }
+
+    // Paths are read from a copy of the bookmarks, other windows may have changed them since
+    fn is_at(&self, path: &NodePath, expected: &BookmarkNode) -> bool {
+        matches!(self.get(path), Some(node) if node.is_same(expected))
+    }
+
+    pub fn insert(&mut self, folder: &NodePath, node: BookmarkNode) -> bool {
+        match self.children_mut(folder) {
+            Some(children) => {
+                children.push(node);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the node at `path`, when it still is `expected`.
+    pub fn remove(&mut self, path: &NodePath, expected: &BookmarkNode) -> Option<BookmarkNode> {
+        if !self.is_at(path, expected) {
+            return None;
+        }
+        let (index, folder) = path.split_last()?;
+        Some(self.children_mut(folder)?.remove(*index))
+    }
+
+    /// Moves the node at `path` to the end of `folder`, when it still is `expected`.
+    pub fn move_node(
+        &mut self,
+        path: &NodePath,
+        expected: &BookmarkNode,
+        folder: &NodePath,
+    ) -> bool {
+        // Folders can't go inside themselves
+        if folder.starts_with(path) || self.children(folder).is_none() {
+            return false;
+        }
+        let (index, parent) = match path.split_last() {
+            Some(split) => split,
+            None => return false,
+        };
+        // Taking the node out shifts the siblings after it, the folder may be one of them
+        let mut folder = folder.to_vec();
+        if folder.len() > parent.len()
+            && folder.starts_with(parent)
+            && folder[parent.len()] > *index
+        {
+            folder[parent.len()] -= 1;
+        }
+        match self.remove(path, expected) {
+            Some(node) => self.insert(&folder, node),
+            None => false,
+        }
+    }
+
+    /// Renames the node at `path`, when it still is `expected`.
+    pub fn rename(&mut self, path: &NodePath, expected: &BookmarkNode, new_name: String) -> bool {
+        if !self.is_at(path, expected) {
+            return false;
+        }
+        let (index, folder) = match path.split_last() {
+            Some(split) => split,
+            None => return false,
+        };
+        match self.children_mut(folder).and_then(|c| c.get_mut(*index)) {
+            Some(BookmarkNode::Bookmark { name, .. }) | Some(BookmarkNode::Folder { name, .. }) => {
+                *name = new_name;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+static BOOKMARKS: Lazy<Mutex<Bookmarks>> =
+    Lazy::new(|| Mutex::new(Bookmarks::load(&bookmarks_file_path())));
+
+fn bookmarks_file_path() -> PathBuf {
+    get_dll_path().with_file_name("bookmarks.json")
+}
+
+pub fn current_bookmarks() -> Bookmarks {
+    BOOKMARKS.lock().unwrap().clone()
+}
+
+/// Changes the bookmarks shared by all windows and saves them.
+pub fn update_bookmarks<T>(update: impl FnOnce(&mut Bookmarks) -> T) -> T {
+    let mut bookmarks = BOOKMARKS.lock().unwrap();
+    let result = update(&mut bookmarks);
+    if let Err(e) = bookmarks.save(&bookmarks_file_path()) {
+        log::error!("Could not save bookmarks:{:?}", e);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bookmark(name: &str) -> BookmarkNode {
+        BookmarkNode::Bookmark {
+            name: name.to_owned(),
+            path: format!("C:\\{}", name),
+            idl: name.to_owned(),
+        }
+    }
+
+    fn folder(name: &str, children: Vec<BookmarkNode>) -> BookmarkNode {
+        BookmarkNode::Folder {
+            name: name.to_owned(),
+            children,
+        }
+    }
+
+    // Root: Users, Work [Projects [Rust], Docs], Temp
+    fn sample() -> Bookmarks {
+        Bookmarks {
+            items: vec![
+                bookmark("Users"),
+                folder(
+                    "Work",
+                    vec![folder("Projects", vec![bookmark("Rust")]), bookmark("Docs")],
+                ),
+                bookmark("Temp"),
+            ],
+        }
+    }
+
+    fn names(nodes: &[BookmarkNode]) -> Vec<&str> {
+        nodes.iter().map(BookmarkNode::name).collect()
+    }
+
+    #[test]
+    fn children_follow_the_path() {
+        let bookmarks = sample();
+        assert_eq!(
+            names(bookmarks.children(&[]).unwrap()),
+            ["Users", "Work", "Temp"]
+        );
+        assert_eq!(names(bookmarks.children(&[1, 0]).unwrap()), ["Rust"]);
+        // Bookmarks have no children, and paths past the end lead nowhere
+        assert!(bookmarks.children(&[0]).is_none());
+        assert!(bookmarks.children(&[5]).is_none());
+    }
+
+    #[test]
+    fn insert_appends_to_a_folder() {
+        let mut bookmarks = sample();
+        assert!(bookmarks.insert(&[1], bookmark("Music")));
+        assert_eq!(
+            names(bookmarks.children(&[1]).unwrap()),
+            ["Projects", "Docs", "Music"]
+        );
+        assert!(!bookmarks.insert(&[0], bookmark("Music")));
+    }
+
+    #[test]
+    fn remove_checks_the_node_is_still_there() {
+        let mut bookmarks = sample();
+        // Read before another window removed Users, Work has taken its place
+        assert!(bookmarks.remove(&[0], &bookmark("Work")).is_none());
+        let removed = bookmarks.remove(&[1, 1], &bookmark("Docs"));
+        assert_eq!(
+            removed.map(|node| node.name().to_owned()),
+            Some("Docs".to_owned())
+        );
+        assert_eq!(names(bookmarks.children(&[1]).unwrap()), ["Projects"]);
+        // A folder of the same name is not the bookmark
+        assert!(bookmarks.remove(&[1, 0], &bookmark("Projects")).is_none());
+        assert!(bookmarks
+            .remove(&[1, 0], &folder("Projects", vec![]))
+            .is_some());
+    }
+
+    #[test]
+    fn rename_checks_the_node_is_still_there() {
+        let mut bookmarks = sample();
+        assert!(!bookmarks.rename(&[2], &bookmark("Users"), "Home".to_owned()));
+        assert!(bookmarks.rename(&[0], &bookmark("Users"), "Home".to_owned()));
+        assert!(bookmarks.rename(&[1], &folder("Work", vec![]), "Office".to_owned()));
+        assert_eq!(
+            names(bookmarks.children(&[]).unwrap()),
+            ["Home", "Office", "Temp"]
+        );
+    }
+
+    #[test]
+    fn move_node_between_folders() {
+        let mut bookmarks = sample();
+        assert!(bookmarks.move_node(&[0], &bookmark("Users"), &[1, 0]));
+        // Work moved up with Users gone
+        assert_eq!(
+            names(bookmarks.children(&[0, 0]).unwrap()),
+            ["Rust", "Users"]
+        );
+        assert!(bookmarks.move_node(&[0, 0, 0], &bookmark("Rust"), &[]));
+        assert_eq!(
+            names(bookmarks.children(&[]).unwrap()),
+            ["Work", "Temp", "Rust"]
+        );
+    }
+
+    #[test]
+    fn folders_do_not_move_inside_themselves() {
+        let mut bookmarks = sample();
+        let work = folder("Work", vec![]);
+        assert!(!bookmarks.move_node(&[1], &work, &[1]));
+        assert!(!bookmarks.move_node(&[1], &work, &[1, 0]));
+        // Nor inside bookmarks
+        assert!(!bookmarks.move_node(&[2], &bookmark("Temp"), &[0]));
+        assert_eq!(
+            names(bookmarks.children(&[]).unwrap()),
+            ["Users", "Work", "Temp"]
+        );
+    }
+
+    #[test]
+    fn lists_every_bookmark_and_folder() {
+        let bookmarks = sample();
+        let all: Vec<&str> = bookmarks
+            .all_bookmarks()
+            .into_iter()
+            .map(BookmarkNode::name)
+            .collect();
+        assert_eq!(all, ["Users", "Rust", "Docs", "Temp"]);
+        assert_eq!(bookmarks.all_folders(), vec![vec![], vec![1], vec![1, 0]]);
+    }
+}
@@ -0,0 +1,55 @@
+//! Saving of the files shared by every window, off the UI threads and batched.
+
+use std::error::Error;
+use std::sync::{Condvar, Mutex, Once};
+use std::time::Duration;
+
+// Navigating through a few folders in a row writes the file once
+const SAVE_DELAY: Duration = Duration::from_secs(2);
+
+pub type SaveFn = fn() -> Result<(), Box<dyn Error>>;
+
+/// Runs `save` on a thread of its own, a while after it was last requested.
+///
+/// `save` reads the data when it runs, so requests made while waiting are saved together.
+pub struct DelayedSave {
+    name: &'static str,
+    save: SaveFn,
+    pending: Mutex<bool>,
+    requested: Condvar,
+    started: Once,
+}
+
+impl DelayedSave {
+    pub fn new(name: &'static str, save: SaveFn) -> Self {
+        Self {
+            name,
+            save,
+            pending: Mutex::new(false),
+            requested: Condvar::new(),
+            started: Once::new(),
+        }
+    }
+
+    pub fn request(&'static self) {
+        self.started
+            .call_once(|| drop(std::thread::spawn(move || self.run())));
+        *self.pending.lock().unwrap() = true;
+        self.requested.notify_one();
+    }
+
+    fn run(&self) {
+        loop {
+            let mut pending = self.pending.lock().unwrap();
+            while !*pending {
+                pending = self.requested.wait(pending).unwrap();
+            }
+            drop(pending);
+            std::thread::sleep(SAVE_DELAY);
+            *self.pending.lock().unwrap() = false;
+            if let Err(e) = (self.save)() {
+                log::error!("Could not save {}:{:?}", self.name, e);
+            }
+        }
+    }
+}
@@ -1,16 +1,82 @@
 use std::ffi::c_void;
+use std::sync::Mutex;
 
+use once_cell::sync::Lazy;
 use windows::core::{IUnknown, Interface, GUID, HRESULT};
-use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, POINT, S_FALSE, WPARAM};
+use windows::Win32::Foundation::{E_FAIL, LPARAM, LRESULT, POINT, S_FALSE, WPARAM};
 use windows::Win32::System::Com::{CoCreateInstance, CLSCTX_INPROC_SERVER};
 use windows::Win32::UI::Shell::Common::ITEMIDLIST;
 use windows::Win32::UI::Shell::IShellBrowser;
 use windows::Win32::UI::WindowsAndMessaging::{RegisterWindowMessageW, SendMessageW};
 
+use crate::hooks::{interface_pointer, HookError, VtableHook};
+use crate::vtable::{COMMON_EXPLORER_HOST_SHOW_WINDOW, SHELL_BROWSER_BROWSE_OBJECT};
+use crate::window_registry::main_window;
 use crate::{BROWSE_OBJECT_MESSAGE, SHOW_WINDOW_MESSAGE};
 
-static mut MESSAGE_ID_BROWSE_OBJECT: u32 = 0;
-static mut DETOUR_BROWSE_OBJECT: Option<detour::RawDetour> = None;
+/// Functions of explorer the tab bar hooks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    BrowseObject,
+    ShowWindow,
+}
+
+impl Hook {
+    // What stops working without the hook
+    pub fn feature(&self) -> &'static str {
+        match self {
+            Hook::BrowseObject => "Back and forward in tabs",
+            Hook::ShowWindow => "New windows as tabs",
+        }
+    }
+}
+
+// Outcome of the last install attempt of each hook
+static HOOK_STATUS: Lazy<Mutex<Vec<(Hook, Result<(), HookError>)>>> = Lazy::new(Default::default);
+
+fn set_hook_status(hook: Hook, result: &Result<(), HookError>) {
+    if let Err(e) = result {
+        log::error!("Hook {:?} failed:{}", hook, e);
+    }
+    store_hook_status(hook, result);
+}
+
+fn store_hook_status(hook: Hook, result: &Result<(), HookError>) {
+    let mut status = HOOK_STATUS.lock().unwrap();
+    status.retain(|(other, _)| *other != hook);
+    status.push((hook, result.clone()));
+}
+
+/// Hooks tried so far, with why they failed.
+pub fn hook_status() -> Vec<(Hook, Result<(), HookError>)> {
+    HOOK_STATUS.lock().unwrap().clone()
+}
+
+// Explorer opens new windows itself while no window has a tab bar, `hook_show_window` resumes
+fn pause_show_window_hook() {
+    if !SHOW_WINDOW_HOOK.is_enabled() {
+        return;
+    }
+    match SHOW_WINDOW_HOOK.set_enabled(false) {
+        Ok(()) => store_hook_status(Hook::ShowWindow, &Err(HookError::Paused)),
+        Err(e) => log::error!("Could not pause show window hook:{}", e),
+    }
+}
+
+/// Restores the original functions once no window has a tab bar, the next tab bar hooks again.
+///
+/// The dll can only unload after that, so nothing is left hooked by then. Unloading itself is
+/// too late: `DllMain` runs under the loader lock, where waiting on the hook locks can deadlock.
+/// Only disables the hooks, see `VtableHook::disable`.
+pub fn disable_hooks() {
+    pause_show_window_hook();
+    BROWSE_OBJECT_HOOK.disable();
+}
+
+static BROWSE_OBJECT_MESSAGE_ID: Lazy<u32> =
+    Lazy::new(|| unsafe { RegisterWindowMessageW(BROWSE_OBJECT_MESSAGE) });
+static BROWSE_OBJECT_HOOK: Lazy<VtableHook<BrowseObjectFn>> =
+    Lazy::new(|| VtableHook::new(SHELL_BROWSER_BROWSE_OBJECT));
 
 type BrowseObjectFn =
     unsafe extern "system" fn(this: *mut c_void, pidl: *const ITEMIDLIST, w_flags: u32) -> HRESULT;
@@ -24,7 +90,7 @@ unsafe extern "system" fn browse_object_detour(
     if let Ok(window) = (*shell_browser).GetWindow() {
         let res = SendMessageW(
             window,
-            MESSAGE_ID_BROWSE_OBJECT,
+            *BROWSE_OBJECT_MESSAGE_ID,
             WPARAM(&mut w_flags as *mut _ as _),
             LPARAM(pidl as _),
         );
@@ -34,40 +100,26 @@ unsafe extern "system" fn browse_object_detour(
         }
     }
 
-    let base: BrowseObjectFn =
-        std::mem::transmute(DETOUR_BROWSE_OBJECT.as_ref().unwrap().trampoline());
-    base(this, pidl, w_flags)
+    match BROWSE_OBJECT_HOOK.trampoline() {
+        Some(base) => base(this, pidl, w_flags),
+        None => E_FAIL,
+    }
 }
 
-pub unsafe fn hook_browse_object(browser: IShellBrowser) {
-    if DETOUR_BROWSE_OBJECT.is_some() {
-        return;
-    }
+pub unsafe fn hook_browse_object(browser: IShellBrowser) -> Result<(), HookError> {
+    log::info!("hook browse object {:?}", *BROWSE_OBJECT_MESSAGE_ID);
 
-    MESSAGE_ID_BROWSE_OBJECT = RegisterWindowMessageW(BROWSE_OBJECT_MESSAGE);
-    log::info!("hook browse object {:?}", MESSAGE_ID_BROWSE_OBJECT);
+    let result = BROWSE_OBJECT_HOOK.install(interface_pointer(&browser), browse_object_detour);
+    set_hook_status(Hook::BrowseObject, &result);
 
-    DETOUR_BROWSE_OBJECT = detour::RawDetour::new(
-        browser.vtable().BrowseObject as _,
-        browse_object_detour as _,
-    )
-    .map_err(|op| {
-        log::error!("error hook:{:?}", &op);
-        op
-    })
-    .ok();
-    DETOUR_BROWSE_OBJECT
-        .as_ref()
-        .unwrap()
-        .enable()
-        .expect("failed to enable hook");
-
-    log::info!("hook status: {:?}", DETOUR_BROWSE_OBJECT);
+    log::info!("hook status: {:?}", BROWSE_OBJECT_HOOK.is_enabled());
+    result
 }
 
-static mut DETOUR_SHOW_WINDOW: Option<detour::RawDetour> = None;
-static mut SHOW_WINDOW_EXPLORER_HANDLE: Option<HWND> = None;
-static mut SHOW_WINDOW_MESSAGE_ID: u32 = 0;
+static SHOW_WINDOW_MESSAGE_ID: Lazy<u32> =
+    Lazy::new(|| unsafe { RegisterWindowMessageW(SHOW_WINDOW_MESSAGE) });
+static SHOW_WINDOW_HOOK: Lazy<VtableHook<ShowWindowFn>> =
+    Lazy::new(|| VtableHook::new(COMMON_EXPLORER_HOST_SHOW_WINDOW));
 
 type ShowWindowFn = unsafe extern "system" fn(
     this: *mut c_void,
@@ -90,11 +142,18 @@ unsafe extern "system" fn show_window_detour(
     unkn3: u64,
     unkn4: u64,
 ) -> HRESULT {
-    let base: ShowWindowFn = std::mem::transmute(DETOUR_SHOW_WINDOW.as_ref().unwrap().trampoline());
-    let handle = SHOW_WINDOW_EXPLORER_HANDLE.unwrap();
+    let base = match SHOW_WINDOW_HOOK.trampoline() {
+        Some(base) => base,
+        None => return E_FAIL,
+    };
+    // No window with a tab bar left to take the folder in
+    let handle = match main_window() {
+        Some(handle) => handle,
+        None => return base(this, pidl, flags, pt, unkn, unkn2, unkn3, unkn4),
+    };
     let result = SendMessageW(
         handle,
-        SHOW_WINDOW_MESSAGE_ID,
+        *SHOW_WINDOW_MESSAGE_ID,
         WPARAM(&mut flags as *mut _ as usize),
         LPARAM(pidl as _),
     )
@@ -122,41 +181,32 @@ unsafe extern "system" fn show_window_detour(
     }
 }
 
-// From QTTabBar
-//MIDL_INTERFACE("489E9453-869B-4BCC-A1C7-48B5285FD9D8") ICommonExplorerHost  : public IUnknown {};
-//MIDL_INTERFACE("93A56381-E0CD-485A-B60E-67819E12F81B") CExplorerFactoryServer {};
-pub unsafe fn hook_show_window() {
-    if DETOUR_SHOW_WINDOW.is_some() {
-        DETOUR_SHOW_WINDOW.as_ref().unwrap().enable().unwrap();
-        return;
-    }
-
-    SHOW_WINDOW_MESSAGE_ID = RegisterWindowMessageW(SHOW_WINDOW_MESSAGE);
-
+// The ICommonExplorerHost of a CExplorerFactoryServer, the slot is read from its vtable
+unsafe fn create_common_explorer_host() -> Result<IUnknown, HookError> {
     let explorer_factory_server_clsid = GUID::from("93A56381-E0CD-485A-B60E-67819E12F81B");
+    let common_explorer_host_iid = GUID::from("489E9453-869B-4BCC-A1C7-48B5285FD9D8");
     let instance: IUnknown = CoCreateInstance(
         std::ptr::addr_of!(explorer_factory_server_clsid),
         None,
         CLSCTX_INPROC_SERVER,
     )
-    .expect("failed to get explorer factory server");
-
-    // Need to find a clearer way to do this
-    // We need to hook the function at index 3 in the vtable
-    let out: *const *const *const c_void = std::mem::transmute_copy(&instance);
-    let out = (*out).add(3);
-    DETOUR_SHOW_WINDOW = Some(
-        detour::RawDetour::new(*out as _, show_window_detour as _).expect("failed to create hook"),
-    );
-    DETOUR_SHOW_WINDOW
-        .as_ref()
-        .unwrap()
-        .enable()
-        .expect("failed to enable hook")
+    .map_err(|e| HookError::NoInstance(e.code()))?;
+    // The object may implement other interfaces first, their vtable is not the one hooked
+    let mut host = std::ptr::null_mut();
+    instance
+        .query(&common_explorer_host_iid, &mut host)
+        .ok()
+        .map_err(|e| HookError::NoInstance(e.code()))?;
+    // Owns the reference QueryInterface added
+    Ok(std::mem::transmute(host))
 }
 
-pub fn set_main_explorer(explorer_handle: HWND) {
-    unsafe {
-        SHOW_WINDOW_EXPLORER_HANDLE = Some(explorer_handle);
-    }
+// From QTTabBar
+//MIDL_INTERFACE("489E9453-869B-4BCC-A1C7-48B5285FD9D8") ICommonExplorerHost  : public IUnknown {};
+//MIDL_INTERFACE("93A56381-E0CD-485A-B60E-67819E12F81B") CExplorerFactoryServer {};
+pub unsafe fn hook_show_window() -> Result<(), HookError> {
+    let result = create_common_explorer_host()
+        .and_then(|host| SHOW_WINDOW_HOOK.install(interface_pointer(&host), show_window_detour));
+    set_hook_status(Hook::ShowWindow, &result);
+    result
 }
@@ -0,0 +1,387 @@
+// Shared with the tab bar, which uses more of it
+#[allow(dead_code)]
+#[path = "../workspaces.rs"]
+mod workspaces;
+
+// The server half is used by the tab bar
+#[allow(dead_code)]
+#[path = "../ipc/protocol.rs"]
+mod protocol;
+
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::{E_FAIL, HWND},
+        UI::{Shell::ShellExecuteW, WindowsAndMessaging::SW_SHOWNORMAL},
+    },
+};
+
+use protocol::{decode_response, encode_request, Request, Response, TabInfo, WindowId, PIPE_NAME};
+use workspaces::{Workspaces, WORKSPACES_FILE_NAME};
+
+const USAGE: &str = "\
+Usage: extabctl <command>
+
+Commands:
+    open <path> [--background] [--window <id>]
+                                    Open a folder in a new tab, or in explorer when no tab bar runs
+    list                            List the windows with a tab bar and their tabs
+    activate <tab> [--window <id>]  Switch to a tab, by the index shown by list
+    close [<tab>] [--window <id>]   Close a tab, the selected one by default
+    workspace list                  List saved workspaces
+    workspace show <name>           List the tabs of a workspace
+    workspace save <name> [--window <id>]
+                                    Save the tabs of a window as a workspace
+    workspace open <name> [--append] [--window <id>]
+                                    Open a workspace in place of the tabs of a window,
+                                    or after them with --append
+    workspace delete <name>         Delete a workspace
+    workspace rename <name> <new>   Rename a workspace
+
+Windows are given by the id shown by list, the last active one by default.
+
+Exit codes:
+    0   Success
+    1   Invalid arguments, or the request could not be sent
+    2   No tab bar is running
+    3   The tab bar could not carry out the request";
+
+enum Failure {
+    Usage,
+    NotRunning,
+    Rejected(String),
+    Other(Box<dyn Error>),
+}
+
+impl Failure {
+    fn exit_code(&self) -> i32 {
+        match self {
+            Failure::Usage | Failure::Other(_) => 1,
+            Failure::NotRunning => 2,
+            Failure::Rejected(_) => 3,
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            Failure::Usage => USAGE.to_owned(),
+            Failure::NotRunning => "no tab bar is running".to_owned(),
+            Failure::Rejected(message) => message.clone(),
+            Failure::Other(e) => e.to_string(),
+        }
+    }
+}
+
+impl<E: Into<Box<dyn Error>>> From<E> for Failure {
+    fn from(e: E) -> Self {
+        Failure::Other(e.into())
+    }
+}
+
+// Every client gets its own pipe instance. Busy only covers the short gap between a client
+// taking the last instance and the server creating the next one.
+fn connect() -> Result<File, Failure> {
+    const ERROR_PIPE_BUSY: i32 = 231;
+    for _ in 0..50 {
+        match OpenOptions::new().read(true).write(true).open(PIPE_NAME) {
+            Ok(pipe) => return Ok(pipe),
+            Err(e) if e.kind() == ErrorKind::NotFound => return Err(Failure::NotRunning),
+            Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY) => {
+                std::thread::sleep(Duration::from_millis(100))
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Err("the tab bar is busy".into())
+}
+
+struct Client {
+    pipe: File,
+    reader: BufReader<File>,
+}
+
+impl Client {
+    fn connect() -> Result<Self, Failure> {
+        let pipe = connect()?;
+        let reader = BufReader::new(pipe.try_clone()?);
+        Ok(Client { pipe, reader })
+    }
+
+    fn send(&mut self, request: &Request) -> Result<Response, Failure> {
+        writeln!(self.pipe, "{}", encode_request(request))?;
+        self.pipe.flush()?;
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err("the tab bar closed the connection".into());
+        }
+        match decode_response(&line)? {
+            Response::Error { message } => Err(Failure::Rejected(message)),
+            response => Ok(response),
+        }
+    }
+
+    fn tabs(&mut self, window: Option<WindowId>) -> Result<Vec<TabInfo>, Failure> {
+        match self.send(&Request::ListTabs { window })? {
+            Response::Tabs { tabs } => Ok(tabs),
+            response => Err(format!("unexpected response {:?}", response).into()),
+        }
+    }
+}
+
+// Relative folders are relative to where the tool runs, not to explorer
+fn absolute_path(path: &str) -> Result<String, Failure> {
+    let relative = Path::new(path);
+    if relative.is_absolute() || !relative.exists() {
+        // Shell locations like shell:Downloads are passed as they are
+        return Ok(path.to_owned());
+    }
+    let absolute: PathBuf = std::env::current_dir()?
+        .join(relative)
+        .components()
+        .collect();
+    Ok(absolute.to_string_lossy().into_owned())
+}
+
+struct Options<'a> {
+    positional: Vec<&'a str>,
+    flags: Vec<&'a str>,
+    window: Option<WindowId>,
+}
+
+// Splits `--window <id>` and flags off the positional arguments
+fn parse_options<'a>(args: &'a [String], flags: &[&str]) -> Result<Options<'a>, Failure> {
+    let mut positional = Vec::new();
+    let mut set_flags = Vec::new();
+    let mut window = None;
+    let mut args = args.iter().map(String::as_str);
+    while let Some(arg) = args.next() {
+        match arg {
+            "--window" => {
+                let id = args.next().ok_or(Failure::Usage)?;
+                window = Some(id.parse().map_err(|_| Failure::Usage)?);
+            }
+            flag if flags.contains(&flag) => set_flags.push(flag),
+            flag if flag.starts_with("--") => return Err(Failure::Usage),
+            arg => positional.push(arg),
+        }
+    }
+    Ok(Options {
+        positional,
+        flags: set_flags,
+        window,
+    })
+}
+
+fn open_command(args: &[String]) -> Result<(), Failure> {
+    let options = parse_options(args, &["--background"])?;
+    let window = options.window;
+    let path = match options.positional.as_slice() {
+        [path] => absolute_path(path)?,
+        _ => return Err(Failure::Usage),
+    };
+    let request = Request::Open {
+        path: path.clone(),
+        background: options.flags.contains(&"--background"),
+        window,
+    };
+    match Client::connect() {
+        Ok(mut client) => client.send(&request).map(|_| ()),
+        // Without a tab bar, opening a window is the next best thing
+        Err(Failure::NotRunning) if window.is_none() => open_in_explorer(&path),
+        Err(e) => Err(e),
+    }
+}
+
+// Asks for the `open` verb by name, the default verb may be the one that started us
+fn open_in_explorer(path: &str) -> Result<(), Failure> {
+    let instance = unsafe {
+        ShellExecuteW(
+            HWND(0),
+            "open",
+            path,
+            PCWSTR::default(),
+            PCWSTR::default(),
+            SW_SHOWNORMAL.0 as i32,
+        )
+    };
+    // Values up to 32 are errors
+    if instance.0 <= 32 {
+        return Err(windows::core::Error::from(E_FAIL).into());
+    }
+    Ok(())
+}
+
+fn list_command(args: &[String]) -> Result<(), Failure> {
+    if !args.is_empty() {
+        return Err(Failure::Usage);
+    }
+    let mut client = Client::connect()?;
+    let windows = match client.send(&Request::ListWindows)? {
+        Response::Windows { windows } => windows,
+        response => return Err(format!("unexpected response {:?}", response).into()),
+    };
+    for window in windows {
+        let active = if window.active { "*" } else { " " };
+        println!("{} {}\t{}", active, window.id, window.title);
+        for tab in client.tabs(Some(window.id))? {
+            let selected = if tab.selected { "*" } else { " " };
+            println!(
+                "    {} {}\t{}\t{}",
+                selected, tab.index, tab.title, tab.path
+            );
+        }
+    }
+    Ok(())
+}
+
+fn tab_command(args: &[String], close: bool) -> Result<(), Failure> {
+    let options = parse_options(args, &[])?;
+    let window = options.window;
+    let mut client = Client::connect()?;
+    let tab = match options.positional.as_slice() {
+        [tab] => tab.parse().map_err(|_| Failure::Usage)?,
+        [] if close => client
+            .tabs(window)?
+            .iter()
+            .find(|tab| tab.selected)
+            .map(|tab| tab.index)
+            .ok_or_else(|| Failure::Rejected("no tab is selected".to_owned()))?,
+        _ => return Err(Failure::Usage),
+    };
+    let request = match close {
+        true => Request::Close { tab, window },
+        false => Request::Activate { tab, window },
+    };
+    client.send(&request).map(|_| ())
+}
+
+// The workspaces are saved next to the tab bar dll, installed alongside this tool
+fn workspaces_file_path() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(std::env::current_exe()?.with_file_name(WORKSPACES_FILE_NAME))
+}
+
+fn workspace_command(args: &[String]) -> Result<(), Failure> {
+    // Only saving and opening take a window, they need a running tab bar
+    match args.first().map(String::as_str) {
+        Some("save") => return save_workspace_command(&args[1..]),
+        Some("open") => return open_workspace_command(&args[1..]),
+        _ => (),
+    }
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["list"] => {
+            for workspace in load_workspaces()?.list() {
+                println!("{}\t{} tabs", workspace.name, workspace.tabs.len());
+            }
+        }
+        ["show", name] => {
+            let workspaces = load_workspaces()?;
+            let workspace = workspaces
+                .get(name)
+                .ok_or_else(|| format!("no workspace named {:?}", name))?;
+            for (index, tab) in workspace.tabs.iter().enumerate() {
+                let selected = if workspace.selected == Some(index) {
+                    "*"
+                } else {
+                    " "
+                };
+                let title = tab.custom_title.as_deref().unwrap_or(&tab.name);
+                println!("{} {}\t{}", selected, title, tab.path);
+            }
+        }
+        ["delete", name] => change_workspaces(
+            &Request::DeleteSession {
+                name: name.to_string(),
+            },
+            |workspaces| {
+                workspaces
+                    .remove(name)
+                    .map(|_| ())
+                    .ok_or_else(|| format!("no workspace named {:?}", name).into())
+            },
+        )?,
+        ["rename", name, new_name] => change_workspaces(
+            &Request::RenameSession {
+                name: name.to_string(),
+                new_name: new_name.to_string(),
+            },
+            |workspaces| Ok(workspaces.rename(name, new_name)?),
+        )?,
+        _ => return Err(Failure::Usage),
+    }
+    Ok(())
+}
+
+fn save_workspace_command(args: &[String]) -> Result<(), Failure> {
+    let options = parse_options(args, &[])?;
+    let name = match options.positional.as_slice() {
+        [name] => name.to_string(),
+        _ => return Err(Failure::Usage),
+    };
+    let request = Request::SaveSession {
+        name,
+        window: options.window,
+    };
+    Client::connect()?.send(&request).map(|_| ())
+}
+
+fn open_workspace_command(args: &[String]) -> Result<(), Failure> {
+    let options = parse_options(args, &["--append"])?;
+    let name = match options.positional.as_slice() {
+        [name] => name.to_string(),
+        _ => return Err(Failure::Usage),
+    };
+    let request = Request::LoadSession {
+        name,
+        replace: !options.flags.contains(&"--append"),
+        window: options.window,
+    };
+    Client::connect()?.send(&request).map(|_| ())
+}
+
+fn load_workspaces() -> Result<Workspaces, Box<dyn Error>> {
+    Workspaces::load(&workspaces_file_path()?)
+}
+
+// A running tab bar makes the change, it saves the same file from its windows too.
+// Without one nothing else writes the file and it is changed here.
+fn change_workspaces(
+    request: &Request,
+    change: impl FnOnce(&mut Workspaces) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Failure> {
+    match Client::connect() {
+        Ok(mut client) => client.send(request).map(|_| ()),
+        Err(Failure::NotRunning) => {
+            let path = workspaces_file_path()?;
+            let mut workspaces = Workspaces::load(&path)?;
+            change(&mut workspaces)?;
+            Ok(workspaces.save(&path)?)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.split_first() {
+        Some((command, rest)) => match command.as_str() {
+            "open" => open_command(rest),
+            "list" => list_command(rest),
+            "activate" => tab_command(rest, false),
+            "close" => tab_command(rest, true),
+            "workspace" => workspace_command(rest),
+            _ => Err(Failure::Usage),
+        },
+        None => Err(Failure::Usage),
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e.message());
+        std::process::exit(e.exit_code());
+    }
+}
@@ -0,0 +1,312 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::delayed_save::DelayedSave;
+use crate::fuzzy::fuzzy_score;
+use crate::get_dll_path;
+use crate::idl::Idl;
+
+const HOUR: i64 = 60 * 60;
+const DAY: i64 = 24 * HOUR;
+const WEEK: i64 = 7 * DAY;
+
+// Once the ranks add up to more than this, they all decay so old folders fade out
+const MAX_TOTAL_RANK: f64 = 1000.0;
+const DECAY_TARGET: f64 = 0.9 * MAX_TOTAL_RANK;
+// Folders whose rank decays below this are forgotten
+const MIN_RANK: f64 = 1.0;
+
+/// Source of the current time, in unix seconds.
+pub trait Clock {
+    fn now(&self) -> i64;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> i64 {
+        chrono::Utc::now().timestamp()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FrecencyEntry {
+    pub name: String,
+    pub path: String,
+    // Serialized id list, see `Idl::to_hex`
+    pub idl: String,
+    // Number of visits, lowered as entries decay
+    pub rank: f64,
+    pub last_visit: i64,
+}
+
+impl FrecencyEntry {
+    pub fn get_idl(&self) -> Option<Idl> {
+        Idl::from_hex(&self.idl)
+    }
+
+    /// Rank weighted by how long ago the folder was last visited.
+    pub fn score(&self, now: i64) -> f64 {
+        let age = now - self.last_visit;
+        let weight = match age {
+            _ if age < HOUR => 4.0,
+            _ if age < DAY => 2.0,
+            _ if age < WEEK => 0.5,
+            _ => 0.25,
+        };
+        self.rank * weight
+    }
+}
+
+/// Folders ranked by how often and how recently they were visited.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Frecency {
+    entries: Vec<FrecencyEntry>,
+}
+
+impl Frecency {
+    fn load(path: &Path) -> Self {
+        || -> Result<Frecency, Box<dyn Error>> {
+            let file = std::fs::File::open(path)?;
+            Ok(serde_json::from_reader(file)?)
+        }()
+        .unwrap_or_default()
+    }
+
+    pub fn visit(&mut self, idl: String, name: String, path: String, clock: &impl Clock) {
+        let now = clock.now();
+        match self.entries.iter_mut().find(|entry| entry.idl == idl) {
+            Some(entry) => {
+                entry.rank += 1.0;
+                entry.last_visit = now;
+                entry.name = name;
+                entry.path = path;
+            }
+            None => self.entries.push(FrecencyEntry {
+                name,
+                path,
+                idl,
+                rank: 1.0,
+                last_visit: now,
+            }),
+        }
+        self.decay();
+    }
+
+    fn decay(&mut self) {
+        let total: f64 = self.entries.iter().map(|entry| entry.rank).sum();
+        if total <= MAX_TOTAL_RANK {
+            return;
+        }
+        let factor = DECAY_TARGET / total;
+        for entry in &mut self.entries {
+            entry.rank *= factor;
+        }
+        self.entries.retain(|entry| entry.rank >= MIN_RANK);
+    }
+
+    /// Highest scored folders, ties going to the latest visited.
+    pub fn top(&self, count: usize, clock: &impl Clock) -> Vec<FrecencyEntry> {
+        self.matches("", count, clock)
+    }
+
+    /// Folders fuzzy matching the query by name or path, by score.
+    ///
+    /// The quality of the match only filters, the frecency decides the order.
+    pub fn matches(&self, query: &str, count: usize, clock: &impl Clock) -> Vec<FrecencyEntry> {
+        let now = clock.now();
+        let mut matches: Vec<&FrecencyEntry> = self
+            .entries
+            .iter()
+            .filter(|entry| {
+                fuzzy_score(query, &entry.name).is_some()
+                    || fuzzy_score(query, &entry.path).is_some()
+            })
+            .collect();
+        matches.sort_by(|a, b| {
+            b.score(now)
+                .partial_cmp(&a.score(now))
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(b.last_visit.cmp(&a.last_visit))
+        });
+        matches.into_iter().take(count).cloned().collect()
+    }
+}
+
+static FRECENCY: Lazy<Mutex<Frecency>> =
+    Lazy::new(|| Mutex::new(Frecency::load(&frecency_file_path())));
+static FRECENCY_SAVE: Lazy<DelayedSave> = Lazy::new(|| DelayedSave::new("frecency", save));
+
+fn frecency_file_path() -> PathBuf {
+    get_dll_path().with_file_name("frecency.json")
+}
+
+// Serialized under the lock, written without it
+fn save() -> Result<(), Box<dyn Error>> {
+    let json = serde_json::to_vec(&*FRECENCY.lock().unwrap())?;
+    std::fs::write(frecency_file_path(), json)?;
+    Ok(())
+}
+
+pub fn visit(idl: &Idl, name: String, path: String) {
+    FRECENCY
+        .lock()
+        .unwrap()
+        .visit(idl.to_hex(), name, path, &SystemClock);
+    FRECENCY_SAVE.request();
+}
+
+pub fn top(count: usize) -> Vec<FrecencyEntry> {
+    FRECENCY.lock().unwrap().top(count, &SystemClock)
+}
+
+// Best folder for a jump query, like `z` in a shell
+pub fn best_match(query: &str) -> Option<FrecencyEntry> {
+    FRECENCY
+        .lock()
+        .unwrap()
+        .matches(query, 1, &SystemClock)
+        .pop()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    struct FakeClock(Cell<i64>);
+
+    impl FakeClock {
+        fn advance(&self, seconds: i64) {
+            self.0.set(self.0.get() + seconds);
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> i64 {
+            self.0.get()
+        }
+    }
+
+    fn visit(frecency: &mut Frecency, name: &str, clock: &FakeClock) {
+        let path = format!("C:\\{}", name);
+        frecency.visit(name.to_lowercase(), name.to_owned(), path, clock);
+    }
+
+    fn names(entries: Vec<FrecencyEntry>) -> Vec<String> {
+        entries.into_iter().map(|entry| entry.name).collect()
+    }
+
+    fn entry(rank: f64, last_visit: i64) -> FrecencyEntry {
+        FrecencyEntry {
+            name: String::new(),
+            path: String::new(),
+            idl: String::new(),
+            rank,
+            last_visit,
+        }
+    }
+
+    #[test]
+    fn score_ages_with_the_last_visit() {
+        let now = 100 * WEEK;
+        let entry = |age| entry(8.0, now - age);
+        assert_eq!(entry(0).score(now), 32.0);
+        assert_eq!(entry(HOUR - 1).score(now), 32.0);
+        assert_eq!(entry(HOUR).score(now), 16.0);
+        assert_eq!(entry(DAY).score(now), 4.0);
+        assert_eq!(entry(WEEK).score(now), 2.0);
+        assert_eq!(entry(52 * WEEK).score(now), 2.0);
+    }
+
+    #[test]
+    fn visits_add_up() {
+        let clock = FakeClock(Cell::new(0));
+        let mut frecency = Frecency::default();
+        visit(&mut frecency, "Projects", &clock);
+        clock.advance(10);
+        frecency.visit(
+            "projects".to_owned(),
+            "Projects renamed".to_owned(),
+            "C:\\Projects renamed".to_owned(),
+            &clock,
+        );
+        assert_eq!(frecency.entries.len(), 1);
+        let entry = &frecency.entries[0];
+        assert_eq!(entry.rank, 2.0);
+        assert_eq!(entry.last_visit, 10);
+        assert_eq!(entry.name, "Projects renamed");
+    }
+
+    #[test]
+    fn frequent_beats_rare_and_recent_beats_old() {
+        let clock = FakeClock(Cell::new(0));
+        let mut frecency = Frecency::default();
+        for _ in 0..3 {
+            visit(&mut frecency, "Often", &clock);
+        }
+        visit(&mut frecency, "Once", &clock);
+        assert_eq!(names(frecency.top(10, &clock)), vec!["Often", "Once"]);
+
+        // Three visits two days ago weigh less than one visit now
+        clock.advance(2 * DAY);
+        visit(&mut frecency, "Once", &clock);
+        assert_eq!(names(frecency.top(10, &clock)), vec!["Once", "Often"]);
+    }
+
+    #[test]
+    fn ties_go_to_the_latest_visit() {
+        let clock = FakeClock(Cell::new(0));
+        let mut frecency = Frecency::default();
+        visit(&mut frecency, "First", &clock);
+        clock.advance(1);
+        visit(&mut frecency, "Second", &clock);
+        assert_eq!(names(frecency.top(10, &clock)), vec!["Second", "First"]);
+        assert_eq!(names(frecency.top(1, &clock)), vec!["Second"]);
+    }
+
+    #[test]
+    fn ranks_decay_past_the_total() {
+        let clock = FakeClock(Cell::new(0));
+        let mut rare = entry(1.05, 0);
+        rare.idl = "rare".to_owned();
+        let mut frecency = Frecency {
+            entries: vec![entry(996.0, 0), rare],
+        };
+        visit(&mut frecency, "New", &clock);
+        visit(&mut frecency, "New", &clock);
+
+        // 998.05 then 999.05 stay under the limit, the next visit goes over
+        assert_eq!(frecency.entries.len(), 3);
+        assert_eq!(frecency.entries[0].rank, 996.0);
+        visit(&mut frecency, "New", &clock);
+        let total: f64 = frecency.entries.iter().map(|entry| entry.rank).sum();
+        assert!(total <= DECAY_TARGET && total > DECAY_TARGET - MIN_RANK);
+        // The rare folder fell below the minimum rank and is forgotten
+        assert!(frecency.entries.iter().all(|entry| entry.idl != "rare"));
+    }
+
+    #[test]
+    fn matches_filter_by_name_or_path() {
+        let clock = FakeClock(Cell::new(0));
+        let mut frecency = Frecency::default();
+        visit(&mut frecency, "Downloads", &clock);
+        visit(&mut frecency, "Documents", &clock);
+        visit(&mut frecency, "Documents", &clock);
+        assert_eq!(
+            names(frecency.matches("do", 10, &clock)),
+            vec!["Documents", "Downloads"]
+        );
+        assert_eq!(
+            names(frecency.matches("dwn", 10, &clock)),
+            vec!["Downloads"]
+        );
+        assert_eq!(names(frecency.matches("c:", 1, &clock)), vec!["Documents"]);
+        assert!(frecency.matches("xyz", 10, &clock).is_empty());
+    }
+}
@@ -0,0 +1,127 @@
+// Score bonuses, a match earns more when its characters are close together
+// and start words or path components
+const MATCH_SCORE: i32 = 16;
+const CONSECUTIVE_BONUS: i32 = 16;
+const WORD_START_BONUS: i32 = 24;
+const GAP_PENALTY: i32 = 2;
+const SUBSTRING_BONUS: i32 = 1000;
+
+fn is_word_start(previous: Option<char>, current: char) -> bool {
+    match previous {
+        None => true,
+        Some(previous) => {
+            !previous.is_alphanumeric()
+                || (previous.is_lowercase() && current.is_uppercase())
+                || (!previous.is_numeric() && current.is_numeric())
+        }
+    }
+}
+
+/// Scores how well `query` matches `text`, higher is better.
+///
+/// Every character of the query must appear in the text, in order and ignoring case.
+/// Texts containing the query as a whole rank above scattered matches.
+/// An empty query matches everything with a score of 0.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let query: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+    let text: Vec<char> = text.chars().collect();
+    let lower_text: Vec<char> = text
+        .iter()
+        .map(|c| c.to_lowercase().next().unwrap_or(*c))
+        .collect();
+
+    let mut score = 0;
+    let mut query_index = 0;
+    let mut last_match: Option<usize> = None;
+    for (index, c) in lower_text.iter().enumerate() {
+        if query_index == query.len() {
+            break;
+        }
+        if *c != query[query_index] {
+            continue;
+        }
+
+        score += MATCH_SCORE;
+        let previous = index.checked_sub(1).map(|i| text[i]);
+        if is_word_start(previous, text[index]) {
+            score += WORD_START_BONUS;
+        }
+        match last_match {
+            Some(last) if last + 1 == index => score += CONSECUTIVE_BONUS,
+            Some(last) => score -= GAP_PENALTY * (index - last - 1).min(8) as i32,
+            None => (),
+        }
+        last_match = Some(index);
+        query_index += 1;
+    }
+    if query_index < query.len() {
+        return None;
+    }
+
+    let is_substring = lower_text
+        .windows(query.len())
+        .any(|window| window == query.as_slice());
+    if is_substring {
+        score += SUBSTRING_BONUS;
+    }
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_query_matches_everything() {
+        assert_eq!(fuzzy_score("", "Documents"), Some(0));
+        assert_eq!(fuzzy_score("", ""), Some(0));
+    }
+
+    #[test]
+    fn characters_must_appear_in_order() {
+        assert!(fuzzy_score("dcs", "Documents").is_some());
+        assert_eq!(fuzzy_score("sd", "Documents"), None);
+        assert_eq!(fuzzy_score("documentsx", "Documents"), None);
+    }
+
+    #[test]
+    fn case_is_ignored() {
+        assert_eq!(
+            fuzzy_score("DOC", "documents"),
+            fuzzy_score("doc", "documents")
+        );
+        assert!(fuzzy_score("doc", "DOCUMENTS").is_some());
+    }
+
+    #[test]
+    fn substrings_rank_above_scattered_matches() {
+        let substring = fuzzy_score("down", "Downloads").unwrap();
+        let scattered = fuzzy_score("down", "Do Work Now").unwrap();
+        assert!(substring > scattered + SUBSTRING_BONUS / 2);
+    }
+
+    #[test]
+    fn word_starts_rank_above_inner_matches() {
+        let word_start = fuzzy_score("p", "C:\\Projects").unwrap();
+        let inner = fuzzy_score("p", "C:\\Temp").unwrap();
+        assert!(word_start > inner);
+        assert!(is_word_start(Some('e'), 'F'));
+        assert!(is_word_start(Some('x'), '2'));
+        assert!(!is_word_start(Some('2'), '3'));
+    }
+
+    #[test]
+    fn gaps_cost_less_than_a_match() {
+        let close = fuzzy_score("ab", "a_b").unwrap();
+        let far = fuzzy_score("ab", "a________________b").unwrap();
+        assert!(close > far);
+        // The gap penalty is capped
+        assert_eq!(
+            fuzzy_score("ab", "a________b"),
+            fuzzy_score("ab", "a________________b")
+        );
+    }
+}
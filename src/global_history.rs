@@ -0,0 +1,165 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::delayed_save::DelayedSave;
+use crate::fuzzy::fuzzy_score;
+use crate::get_dll_path;
+use crate::idl::Idl;
+
+/// A folder opened in any tab of any window.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HistoryEntry {
+    // Unix time in seconds
+    pub time: i64,
+    pub name: String,
+    pub path: String,
+    // Serialized id list, see `Idl::to_hex`
+    pub idl: String,
+    pub tab: usize,
+    // See `new_window_id`
+    pub window: i64,
+}
+
+impl HistoryEntry {
+    pub fn get_idl(&self) -> Option<Idl> {
+        Idl::from_hex(&self.idl)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Retention {
+    // 0 means no limit
+    pub max_entries: usize,
+    pub max_age_days: u32,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct GlobalHistory {
+    // Oldest entries first
+    entries: Vec<HistoryEntry>,
+}
+
+static GLOBAL_HISTORY: Lazy<Mutex<GlobalHistory>> =
+    Lazy::new(|| Mutex::new(GlobalHistory::load(&history_file_path())));
+static GLOBAL_HISTORY_SAVE: Lazy<DelayedSave> = Lazy::new(|| DelayedSave::new("history", save));
+
+fn history_file_path() -> PathBuf {
+    get_dll_path().with_file_name("history.json")
+}
+
+// Serialized under the lock, written without it
+fn save() -> Result<(), Box<dyn Error>> {
+    let json = serde_json::to_vec(&*GLOBAL_HISTORY.lock().unwrap())?;
+    std::fs::write(history_file_path(), json)?;
+    Ok(())
+}
+
+/// Identifies a window in the history, for as long as the history file is kept.
+///
+/// Window handles are reused once a window closes, and across explorer restarts.
+pub fn new_window_id() -> i64 {
+    static LAST_ID: AtomicI64 = AtomicI64::new(0);
+    let now = chrono::Utc::now().timestamp_millis();
+    // Still unique for windows opening at the same time
+    let previous = LAST_ID
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |last| {
+            Some(now.max(last + 1))
+        })
+        .unwrap();
+    now.max(previous + 1)
+}
+
+impl GlobalHistory {
+    fn load(path: &Path) -> Self {
+        || -> Result<GlobalHistory, Box<dyn Error>> {
+            let file = std::fs::File::open(path)?;
+            Ok(serde_json::from_reader(file)?)
+        }()
+        .unwrap_or_default()
+    }
+
+    fn record(&mut self, entry: HistoryEntry, retention: Retention) {
+        // Refreshing a folder completes a navigation to the same place
+        let repeated = self.entries.last().map_or(false, |last| {
+            last.idl == entry.idl && last.tab == entry.tab && last.window == entry.window
+        });
+        if repeated {
+            self.entries.pop();
+        }
+        let now = entry.time;
+        self.entries.push(entry);
+        self.prune(now, retention);
+    }
+
+    fn prune(&mut self, now: i64, retention: Retention) {
+        if retention.max_age_days > 0 {
+            let oldest = now - retention.max_age_days as i64 * 24 * 60 * 60;
+            self.entries.retain(|entry| entry.time >= oldest);
+        }
+        if retention.max_entries > 0 && self.entries.len() > retention.max_entries {
+            self.entries
+                .drain(..self.entries.len() - retention.max_entries);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    // Latest visit of every folder, newest first
+    fn latest_visits(&self) -> impl Iterator<Item = &HistoryEntry> {
+        let mut seen = std::collections::HashSet::new();
+        self.entries
+            .iter()
+            .rev()
+            .filter(move |entry| seen.insert(entry.idl.as_str()))
+    }
+
+    fn recent(&self, count: usize) -> Vec<HistoryEntry> {
+        self.latest_visits().take(count).cloned().collect()
+    }
+
+    /// Folders matching the query by name or path, best matches first.
+    fn search(&self, query: &str, count: usize) -> Vec<HistoryEntry> {
+        let mut matches: Vec<(i32, usize, &HistoryEntry)> = self
+            .latest_visits()
+            .enumerate()
+            .filter_map(|(age, entry)| {
+                let score = fuzzy_score(query, &entry.name)
+                    .into_iter()
+                    .chain(fuzzy_score(query, &entry.path))
+                    .max()?;
+                Some((score, age, entry))
+            })
+            .collect();
+        matches.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        matches
+            .into_iter()
+            .take(count)
+            .map(|(_, _, entry)| entry.clone())
+            .collect()
+    }
+}
+
+pub fn record(entry: HistoryEntry, retention: Retention) {
+    GLOBAL_HISTORY.lock().unwrap().record(entry, retention);
+    GLOBAL_HISTORY_SAVE.request();
+}
+
+pub fn recent(count: usize) -> Vec<HistoryEntry> {
+    GLOBAL_HISTORY.lock().unwrap().recent(count)
+}
+
+pub fn search(query: &str, count: usize) -> Vec<HistoryEntry> {
+    GLOBAL_HISTORY.lock().unwrap().search(query, count)
+}
+
+pub fn clear() {
+    GLOBAL_HISTORY.lock().unwrap().clear();
+    GLOBAL_HISTORY_SAVE.request();
+}
@@ -0,0 +1,215 @@
+use std::ffi::c_void;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::Mutex;
+
+use windows::core::{Interface, HRESULT, PCWSTR};
+use windows::Win32::Foundation::HINSTANCE;
+use windows::Win32::System::LibraryLoader::{
+    GetModuleHandleExW, GetModuleHandleW, GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS,
+    GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT,
+};
+use windows::Win32::System::Memory::{
+    VirtualQuery, MEMORY_BASIC_INFORMATION, MEM_COMMIT, PAGE_EXECUTE, PAGE_EXECUTE_READ,
+    PAGE_EXECUTE_READWRITE, PAGE_EXECUTE_WRITECOPY, PAGE_PROTECTION_FLAGS, PAGE_READONLY,
+    PAGE_READWRITE, PAGE_WRITECOPY,
+};
+
+use crate::vtable::VtableSlot;
+
+/// Why a hook is not installed, explorer keeps running without it.
+#[derive(Debug, Clone)]
+pub enum HookError {
+    // The object whose vtable holds the function could not be created
+    NoInstance(HRESULT),
+    // Not code of a loaded module, the vtable layout probably changed with a Windows update
+    InvalidTarget(usize),
+    // Code of another dll than the one implementing the method
+    WrongModule(&'static str, usize),
+    // Disabled on purpose, see `detour::pause_show_window_hook`
+    Paused,
+    Create(String),
+    Enable(String),
+}
+
+impl fmt::Display for HookError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HookError::NoInstance(code) => write!(f, "no instance to hook ({:?})", code),
+            HookError::InvalidTarget(address) => write!(f, "invalid target {:#x}", address),
+            HookError::WrongModule(module, address) => {
+                write!(f, "target {:#x} outside {}", address, module)
+            }
+            HookError::Paused => write!(f, "paused while no window has a tab bar"),
+            HookError::Create(e) => write!(f, "could not create hook: {}", e),
+            HookError::Enable(e) => write!(f, "could not enable hook: {}", e),
+        }
+    }
+}
+
+// Checks `address` is committed memory with one of the `allowed` protections
+unsafe fn has_protection(address: *const c_void, allowed: PAGE_PROTECTION_FLAGS) -> bool {
+    let mut info = MEMORY_BASIC_INFORMATION::default();
+    let size = std::mem::size_of::<MEMORY_BASIC_INFORMATION>();
+    VirtualQuery(address, &mut info, size) == size
+        && info.State == MEM_COMMIT
+        && (info.Protect & allowed).0 != 0
+}
+
+unsafe fn is_readable(address: *const c_void) -> bool {
+    has_protection(
+        address,
+        PAGE_READONLY
+            | PAGE_READWRITE
+            | PAGE_WRITECOPY
+            | PAGE_EXECUTE_READ
+            | PAGE_EXECUTE_READWRITE
+            | PAGE_EXECUTE_WRITECOPY,
+    )
+}
+
+// Run before patching anything, a bad target would crash explorer
+unsafe fn validate_target(target: *const c_void, expected: &'static str) -> Result<(), HookError> {
+    let executable =
+        PAGE_EXECUTE | PAGE_EXECUTE_READ | PAGE_EXECUTE_READWRITE | PAGE_EXECUTE_WRITECOPY;
+    let mut module = HINSTANCE::default();
+    let in_module = GetModuleHandleExW(
+        GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS | GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT,
+        PCWSTR(target as _),
+        &mut module,
+    )
+    .as_bool();
+    if target.is_null() || !has_protection(target, executable) || !in_module {
+        return Err(HookError::InvalidTarget(target as usize));
+    }
+    match GetModuleHandleW(expected) {
+        Ok(expected_module) if expected_module == module => Ok(()),
+        _ => Err(HookError::WrongModule(expected, target as usize)),
+    }
+}
+
+/// Reads the function in a vtable slot, checking the vtable and the function first.
+///
+/// # Safety
+/// `object` has to be a live COM interface pointer.
+pub unsafe fn find_slot_function(
+    slot: &VtableSlot,
+    object: *const c_void,
+) -> Result<*const c_void, HookError> {
+    if object.is_null() || !is_readable(object) {
+        return Err(HookError::InvalidTarget(object as usize));
+    }
+    let slot_address = slot.slot_address(object);
+    if !is_readable(slot_address as _) {
+        return Err(HookError::InvalidTarget(slot_address as usize));
+    }
+    let function = *slot_address;
+    validate_target(function, slot.module)?;
+    Ok(function)
+}
+
+// A hooked function, `F` being its `extern "system" fn` type
+struct TypedHook<F> {
+    detour: detour::RawDetour,
+    _function: PhantomData<F>,
+}
+
+/// One hooked vtable method, installed at most once per process.
+///
+/// Shared by every thread of explorer, each window running on its own.
+pub struct VtableHook<F: Copy> {
+    slot: VtableSlot,
+    hook: Mutex<Option<TypedHook<F>>>,
+}
+
+// The detour only holds addresses of code and copies of it
+unsafe impl<F: Copy> Send for TypedHook<F> {}
+
+impl<F: Copy> VtableHook<F> {
+    pub fn new(slot: VtableSlot) -> Self {
+        Self {
+            slot,
+            hook: Mutex::new(None),
+        }
+    }
+
+    /// Hooks the method in the vtable of `object` with `replacement`, and enables the hook.
+    ///
+    /// Enables the existing hook when already installed.
+    ///
+    /// # Safety
+    /// `object` has to be a live COM interface pointer with the method at the slot, and `F` the
+    /// type of the method.
+    pub unsafe fn install(&self, object: *const c_void, replacement: F) -> Result<(), HookError> {
+        assert_eq!(
+            std::mem::size_of::<F>(),
+            std::mem::size_of::<*const ()>(),
+            "hooks take function pointers"
+        );
+        let mut hook = self.hook.lock().unwrap();
+        if hook.is_none() {
+            log::info!("Hooking {}::{}", self.slot.interface, self.slot.method);
+            let target = find_slot_function(&self.slot, object)?;
+            let replacement: *const () = std::mem::transmute_copy(&replacement);
+            let raw_detour = detour::RawDetour::new(target as _, replacement)
+                .map_err(|e| HookError::Create(e.to_string()))?;
+            *hook = Some(TypedHook {
+                detour: raw_detour,
+                _function: PhantomData,
+            });
+        }
+        let hook = hook.as_ref().unwrap();
+        hook.detour
+            .enable()
+            .map_err(|e| HookError::Enable(e.to_string()))
+    }
+
+    /// The original method, for detours to call. `None` until installed.
+    pub fn trampoline(&self) -> Option<F> {
+        let hook = self.hook.lock().unwrap();
+        let trampoline: *const () = hook.as_ref()?.detour.trampoline();
+        // Same size checked in `install`
+        Some(unsafe { std::mem::transmute_copy(&trampoline) })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        let hook = self.hook.lock().unwrap();
+        hook.as_ref().map_or(false, |hook| hook.detour.is_enabled())
+    }
+
+    pub fn set_enabled(&self, enabled: bool) -> Result<(), HookError> {
+        let hook = self.hook.lock().unwrap();
+        let hook = match hook.as_ref() {
+            Some(hook) => hook,
+            None => return Ok(()),
+        };
+        unsafe {
+            match enabled {
+                true => hook.detour.enable(),
+                false => hook.detour.disable(),
+            }
+        }
+        .map_err(|e| HookError::Enable(e.to_string()))
+    }
+
+    /// Puts the original method back, the hook can be enabled again later.
+    ///
+    /// Hooks are never uninstalled, the detour and its trampoline stay allocated for the life
+    /// of the process: another thread may still be running the trampoline when this returns.
+    pub fn disable(&self) {
+        if let Err(e) = self.set_enabled(false) {
+            log::error!(
+                "Could not disable {}::{}:{}",
+                self.slot.interface,
+                self.slot.method,
+                e
+            );
+        }
+    }
+}
+
+/// Raw pointer of a COM interface, to address its vtable.
+pub fn interface_pointer<T: Interface>(object: &T) -> *const c_void {
+    // Interfaces are a single pointer to the object
+    unsafe { std::mem::transmute_copy(object) }
+}
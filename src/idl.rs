@@ -1,4 +1,6 @@
-use windows::Win32::UI::Shell::{ILClone, ILFree, ILIsEqual, Common::ITEMIDLIST};
+use windows::Win32::UI::Shell::{
+    Common::ITEMIDLIST, ILClone, ILCombine, ILFree, ILGetSize, ILIsEqual, SHParseDisplayName,
+};
 
 pub struct Idl(*const ITEMIDLIST);
 
@@ -19,9 +21,70 @@ impl Idl {
         Self(unsafe { ILClone(ptr) })
     }
 
+    /// Takes ownership of an id list allocated by the shell.
+    pub fn from_raw(ptr: *const ITEMIDLIST) -> Self {
+        Self(ptr)
+    }
+
     pub fn get(&self) -> *const ITEMIDLIST {
         self.0
     }
+
+    /// Id list of an item of this folder, `child` being relative to it.
+    pub fn join(&self, child: &Idl) -> Self {
+        Self(unsafe { ILCombine(self.0, child.0) })
+    }
+
+    /// Parses a folder path, or a shell location like `shell:Downloads`.
+    pub fn from_display_name(name: &str) -> Option<Self> {
+        let mut pidl = std::ptr::null_mut();
+        unsafe {
+            SHParseDisplayName(name, None, &mut pidl, 0, std::ptr::null_mut()).ok()?;
+        }
+        Some(Self(pidl))
+    }
+
+    /// Raw bytes of the id list, terminator included.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        unsafe {
+            let size = ILGetSize(self.0) as usize;
+            std::slice::from_raw_parts(self.0 as *const u8, size).to_vec()
+        }
+    }
+
+    /// Rebuilds an id list saved with `to_bytes`, checking that every item fits in the buffer.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut offset = 0;
+        loop {
+            let size = u16::from_le_bytes([*bytes.get(offset)?, *bytes.get(offset + 1)?]) as usize;
+            if size == 0 {
+                break;
+            }
+            if size < 2 {
+                return None;
+            }
+            offset += size;
+        }
+        Some(Idl::new(bytes.as_ptr() as *const ITEMIDLIST))
+    }
+
+    pub fn to_hex(&self) -> String {
+        self.to_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        if hex.len() % 2 != 0 {
+            return None;
+        }
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+        Self::from_bytes(&bytes)
+    }
 }
 
 impl PartialEq for Idl {
@@ -0,0 +1,389 @@
+use std::io::{BufRead, Write};
+
+use super::protocol::{
+    decode_request, encode_response, Request, Response, TabInfo, WindowId, WindowInfo,
+};
+
+/// What the dispatcher drives, the tab bars of explorer in the dll.
+///
+/// Errors are messages sent back to the client as they are.
+pub trait Backend {
+    fn windows(&self) -> Vec<WindowInfo>;
+    // Without asking the window anything, unlike `windows`
+    fn has_window(&self, window: WindowId) -> bool;
+    // Most recently active window
+    fn main_window(&self) -> Option<WindowId>;
+    fn tabs(&self, window: WindowId) -> Result<Vec<TabInfo>, String>;
+    fn open(&self, window: WindowId, path: &str, background: bool) -> Result<(), String>;
+    fn activate(&self, window: WindowId, tab: usize) -> Result<(), String>;
+    fn close(&self, window: WindowId, tab: usize) -> Result<(), String>;
+    fn save_session(&self, window: WindowId, name: &str) -> Result<(), String>;
+    fn load_session(&self, window: WindowId, name: &str, replace: bool) -> Result<(), String>;
+    fn delete_session(&self, name: &str) -> Result<(), String>;
+    fn rename_session(&self, name: &str, new_name: &str) -> Result<(), String>;
+}
+
+fn resolve_window(backend: &dyn Backend, window: Option<WindowId>) -> Result<WindowId, String> {
+    match window {
+        Some(window) if backend.has_window(window) => Ok(window),
+        Some(window) => Err(format!("no tab bar in window {}", window)),
+        None => backend
+            .main_window()
+            .ok_or_else(|| "no tab bar is running".to_owned()),
+    }
+}
+
+fn done(result: Result<(), String>) -> Response {
+    match result {
+        Ok(()) => Response::Done,
+        Err(message) => Response::Error { message },
+    }
+}
+
+pub fn dispatch(backend: &dyn Backend, request: Request) -> Response {
+    let run = || -> Result<Response, String> {
+        Ok(match request {
+            Request::ListWindows => Response::Windows {
+                windows: backend.windows(),
+            },
+            Request::ListTabs { window } => Response::Tabs {
+                tabs: backend.tabs(resolve_window(backend, window)?)?,
+            },
+            Request::Open {
+                path,
+                background,
+                window,
+            } => done(backend.open(resolve_window(backend, window)?, &path, background)),
+            Request::Activate { tab, window } => {
+                done(backend.activate(resolve_window(backend, window)?, tab))
+            }
+            Request::Close { tab, window } => {
+                let window = resolve_window(backend, window)?;
+                // Tab bars keep their last tab, closing it would do nothing
+                if backend.tabs(window)?.len() <= 1 {
+                    return Err(format!("tab {} is the last tab of window {}", tab, window));
+                }
+                done(backend.close(window, tab))
+            }
+            Request::SaveSession { name, window } => {
+                done(backend.save_session(resolve_window(backend, window)?, &name))
+            }
+            Request::LoadSession {
+                name,
+                replace,
+                window,
+            } => done(backend.load_session(resolve_window(backend, window)?, &name, replace)),
+            Request::DeleteSession { name } => done(backend.delete_session(&name)),
+            Request::RenameSession { name, new_name } => {
+                done(backend.rename_session(&name, &new_name))
+            }
+        })
+    };
+    run().unwrap_or_else(|message| Response::Error { message })
+}
+
+/// Answers a request line with a response line, bad requests included.
+pub fn handle_line(backend: &dyn Backend, line: &str) -> String {
+    let response = match decode_request(line) {
+        Ok(request) => dispatch(backend, request),
+        Err(message) => Response::Error { message },
+    };
+    encode_response(&response)
+}
+
+/// Serves one connection until the client closes it.
+///
+/// Any byte stream works, the server passes the pipe and anything in memory does for testing.
+pub fn serve_connection(
+    backend: &dyn Backend,
+    reader: impl BufRead,
+    mut writer: impl Write,
+) -> std::io::Result<()> {
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        writeln!(writer, "{}", handle_line(backend, &line))?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::io::Cursor;
+
+    use super::super::protocol::{decode_response, encode_request};
+    use super::*;
+
+    // Two windows, the second active, recording what the dispatcher asked for
+    struct FakeBackend {
+        windows: Vec<WindowInfo>,
+        calls: RefCell<Vec<String>>,
+    }
+
+    impl FakeBackend {
+        fn new() -> Self {
+            let window = |id, active| WindowInfo {
+                id,
+                title: format!("Window {}", id),
+                tab_count: 2,
+                active,
+            };
+            Self {
+                windows: vec![window(1, false), window(2, true)],
+                calls: RefCell::new(Vec::new()),
+            }
+        }
+
+        fn record(&self, call: String) {
+            self.calls.borrow_mut().push(call);
+        }
+    }
+
+    impl Backend for FakeBackend {
+        fn windows(&self) -> Vec<WindowInfo> {
+            self.windows.clone()
+        }
+
+        fn has_window(&self, window: WindowId) -> bool {
+            self.windows.iter().any(|info| info.id == window)
+        }
+
+        fn main_window(&self) -> Option<WindowId> {
+            self.windows
+                .iter()
+                .find(|window| window.active)
+                .map(|window| window.id)
+        }
+
+        fn tabs(&self, window: WindowId) -> Result<Vec<TabInfo>, String> {
+            let info = self.windows.iter().find(|info| info.id == window).unwrap();
+            Ok((0..info.tab_count)
+                .map(|index| TabInfo {
+                    index,
+                    title: format!("Tab of {}", window),
+                    path: "C:\\".to_owned(),
+                    selected: index == 0,
+                })
+                .collect())
+        }
+
+        fn open(&self, window: WindowId, path: &str, background: bool) -> Result<(), String> {
+            self.record(format!("open {} {} {}", window, path, background));
+            Ok(())
+        }
+
+        fn activate(&self, window: WindowId, tab: usize) -> Result<(), String> {
+            self.record(format!("activate {} {}", window, tab));
+            Ok(())
+        }
+
+        fn close(&self, window: WindowId, tab: usize) -> Result<(), String> {
+            self.record(format!("close {} {}", window, tab));
+            Err(format!("no tab at index {}", tab))
+        }
+
+        fn save_session(&self, window: WindowId, name: &str) -> Result<(), String> {
+            self.record(format!("save {} {}", window, name));
+            Ok(())
+        }
+
+        fn load_session(&self, window: WindowId, name: &str, replace: bool) -> Result<(), String> {
+            self.record(format!("load {} {} {}", window, name, replace));
+            Ok(())
+        }
+
+        fn delete_session(&self, name: &str) -> Result<(), String> {
+            self.record(format!("delete {}", name));
+            Ok(())
+        }
+
+        fn rename_session(&self, name: &str, new_name: &str) -> Result<(), String> {
+            self.record(format!("rename {} {}", name, new_name));
+            Ok(())
+        }
+    }
+
+    // Runs a whole connection in memory, one response per request
+    fn serve(backend: &FakeBackend, requests: &[Request]) -> Vec<Response> {
+        let input: String = requests
+            .iter()
+            .map(|request| encode_request(request) + "\n")
+            .collect();
+        serve_lines(backend, &input)
+    }
+
+    fn serve_lines(backend: &FakeBackend, input: &str) -> Vec<Response> {
+        let mut output = Vec::new();
+        serve_connection(backend, Cursor::new(input.as_bytes()), &mut output).unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| decode_response(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn answers_each_request_in_order() {
+        let backend = FakeBackend::new();
+        let responses = serve(
+            &backend,
+            &[
+                Request::ListWindows,
+                Request::Activate {
+                    tab: 1,
+                    window: Some(1),
+                },
+            ],
+        );
+        assert_eq!(
+            responses,
+            vec![
+                Response::Windows {
+                    windows: backend.windows.clone()
+                },
+                Response::Done
+            ]
+        );
+        assert_eq!(*backend.calls.borrow(), vec!["activate 1 1"]);
+    }
+
+    #[test]
+    fn requests_without_window_go_to_the_active_one() {
+        let backend = FakeBackend::new();
+        serve(
+            &backend,
+            &[
+                Request::Open {
+                    path: "C:\\Users".to_owned(),
+                    background: true,
+                    window: None,
+                },
+                Request::SaveSession {
+                    name: "work".to_owned(),
+                    window: None,
+                },
+                Request::LoadSession {
+                    name: "work".to_owned(),
+                    replace: true,
+                    window: None,
+                },
+            ],
+        );
+        assert_eq!(
+            *backend.calls.borrow(),
+            vec!["open 2 C:\\Users true", "save 2 work", "load 2 work true"]
+        );
+    }
+
+    #[test]
+    fn workspace_changes_need_no_window() {
+        let mut backend = FakeBackend::new();
+        backend.windows.clear();
+        let responses = serve(
+            &backend,
+            &[
+                Request::RenameSession {
+                    name: "work".to_owned(),
+                    new_name: "office".to_owned(),
+                },
+                Request::DeleteSession {
+                    name: "office".to_owned(),
+                },
+            ],
+        );
+        assert_eq!(responses, vec![Response::Done, Response::Done]);
+        assert_eq!(
+            *backend.calls.borrow(),
+            vec!["rename work office", "delete office"]
+        );
+    }
+
+    #[test]
+    fn unknown_window_is_an_error() {
+        let backend = FakeBackend::new();
+        let responses = serve(&backend, &[Request::ListTabs { window: Some(7) }]);
+        assert_eq!(
+            responses,
+            vec![Response::Error {
+                message: "no tab bar in window 7".to_owned()
+            }]
+        );
+    }
+
+    #[test]
+    fn no_window_running_is_an_error() {
+        let mut backend = FakeBackend::new();
+        backend.windows.clear();
+        let responses = serve(&backend, &[Request::ListTabs { window: None }]);
+        assert_eq!(
+            responses,
+            vec![Response::Error {
+                message: "no tab bar is running".to_owned()
+            }]
+        );
+    }
+
+    #[test]
+    fn backend_errors_are_sent_back() {
+        let backend = FakeBackend::new();
+        let responses = serve(
+            &backend,
+            &[Request::Close {
+                tab: 5,
+                window: None,
+            }],
+        );
+        assert_eq!(
+            responses,
+            vec![Response::Error {
+                message: "no tab at index 5".to_owned()
+            }]
+        );
+    }
+
+    #[test]
+    fn closing_the_last_tab_is_an_error() {
+        let mut backend = FakeBackend::new();
+        backend.windows[1].tab_count = 1;
+        let responses = serve(
+            &backend,
+            &[Request::Close {
+                tab: 0,
+                window: None,
+            }],
+        );
+        assert_eq!(
+            responses,
+            vec![Response::Error {
+                message: "tab 0 is the last tab of window 2".to_owned()
+            }]
+        );
+        assert!(backend.calls.borrow().is_empty());
+    }
+
+    #[test]
+    fn bad_lines_do_not_end_the_connection() {
+        let backend = FakeBackend::new();
+        let input = format!(
+            "not json\n\n   \n{}\n",
+            encode_request(&Request::ListTabs { window: Some(1) })
+        );
+        let responses = serve_lines(&backend, &input);
+        assert_eq!(responses.len(), 2);
+        assert!(
+            matches!(&responses[0], Response::Error { message } if message.starts_with("invalid request"))
+        );
+        assert!(matches!(&responses[1], Response::Tabs { tabs } if tabs[0].title == "Tab of 1"));
+    }
+
+    #[test]
+    fn last_line_without_newline_is_served() {
+        let backend = FakeBackend::new();
+        let responses = serve_lines(&backend, r#"{"command":"list_windows"}"#);
+        assert_eq!(responses.len(), 1);
+    }
+}
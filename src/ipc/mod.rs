@@ -0,0 +1,23 @@
+//! Local control API, so scripts and other tools can drive the tab bars.
+//!
+//! `protocol` and `dispatcher` don't depend on Windows, the named pipe lives in `server`.
+
+use std::sync::Mutex;
+
+use crate::tabs::tab_bar::TabBar;
+
+// Only the pipe server drives it, which elsewhere is left out
+#[cfg_attr(not(windows), allow(dead_code))]
+pub mod dispatcher;
+// Shared with extabctl, which uses the client half
+#[allow(dead_code)]
+pub mod protocol;
+#[cfg(windows)]
+pub mod server;
+
+/// Command run by a window on its own thread.
+pub type UiCommand = Box<dyn FnOnce(&TabBar) + Send>;
+
+/// Passed by pointer with `IPC_MESSAGE`, the window takes the command out and releases the
+/// pointer's reference. Emptied by the pipe thread when the window does not answer in time.
+pub type PendingCommand = Mutex<Option<UiCommand>>;
@@ -0,0 +1,124 @@
+//! Requests and responses exchanged over the control pipe.
+//!
+//! Every message is a JSON object on a single line, the server answers each request line with
+//! one response line, in order. Requests are tagged by `command`:
+//!
+//! ```text
+//! {"command":"list_windows"}
+//! {"command":"list_tabs","window":1234}
+//! {"command":"open","path":"C:\\Users","background":true}
+//! {"command":"activate","tab":2}
+//! {"command":"close","window":1234,"tab":0}
+//! {"command":"save_session","name":"work"}
+//! {"command":"load_session","name":"work","replace":true}
+//! {"command":"delete_session","name":"work"}
+//! {"command":"rename_session","name":"work","new_name":"office"}
+//! ```
+//!
+//! `window` is the id given by `list_windows`, the most recently active window when left out.
+//! `tab` is the index given by `list_tabs`. Responses are tagged by `result`:
+//!
+//! ```text
+//! {"result":"done"}
+//! {"result":"windows","windows":[{"id":1234,"title":"Users","tab_count":3,"active":true}]}
+//! {"result":"tabs","tabs":[{"index":0,"title":"Users","path":"C:\\Users","selected":true}]}
+//! {"result":"error","message":"no tab at index 7"}
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+pub const PIPE_NAME: &str = r"\\.\pipe\extabbar";
+
+// Window handle of the explorer window
+pub type WindowId = isize;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Request {
+    ListWindows,
+    ListTabs {
+        #[serde(default)]
+        window: Option<WindowId>,
+    },
+    Open {
+        path: String,
+        #[serde(default)]
+        background: bool,
+        #[serde(default)]
+        window: Option<WindowId>,
+    },
+    Activate {
+        tab: usize,
+        #[serde(default)]
+        window: Option<WindowId>,
+    },
+    Close {
+        tab: usize,
+        #[serde(default)]
+        window: Option<WindowId>,
+    },
+    SaveSession {
+        name: String,
+        #[serde(default)]
+        window: Option<WindowId>,
+    },
+    LoadSession {
+        name: String,
+        // Close the current tabs, as opening a workspace from the menu does
+        #[serde(default)]
+        replace: bool,
+        #[serde(default)]
+        window: Option<WindowId>,
+    },
+    // Workspaces are shared by all windows, changing them takes no window
+    DeleteSession {
+        name: String,
+    },
+    RenameSession {
+        name: String,
+        new_name: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct WindowInfo {
+    pub id: WindowId,
+    pub title: String,
+    pub tab_count: usize,
+    // The window requests without a window go to
+    pub active: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TabInfo {
+    pub index: usize,
+    pub title: String,
+    pub path: String,
+    pub selected: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum Response {
+    Done,
+    Windows { windows: Vec<WindowInfo> },
+    Tabs { tabs: Vec<TabInfo> },
+    Error { message: String },
+}
+
+pub fn encode_request(request: &Request) -> String {
+    // Plain data, serializing can't fail
+    serde_json::to_string(request).unwrap()
+}
+
+pub fn decode_request(line: &str) -> Result<Request, String> {
+    serde_json::from_str(line.trim()).map_err(|e| format!("invalid request: {}", e))
+}
+
+pub fn encode_response(response: &Response) -> String {
+    serde_json::to_string(response).unwrap()
+}
+
+pub fn decode_response(line: &str) -> Result<Response, String> {
+    serde_json::from_str(line.trim()).map_err(|e| format!("invalid response: {}", e))
+}
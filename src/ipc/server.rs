@@ -0,0 +1,234 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::os::windows::io::FromRawHandle;
+use std::sync::{mpsc, Arc, Mutex, Once};
+
+use windows::core::{Error, Result};
+use windows::Win32::Foundation::*;
+use windows::Win32::Storage::FileSystem::{
+    FILE_FLAGS_AND_ATTRIBUTES, FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_DUPLEX,
+};
+use windows::Win32::System::Pipes::{
+    ConnectNamedPipe, CreateNamedPipeW, DisconnectNamedPipe, PIPE_READMODE_BYTE,
+    PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    RegisterWindowMessageW, SendMessageTimeoutW, SMTO_ABORTIFHUNG,
+};
+
+use crate::tabs::tab_bar::{delete_workspace, rename_workspace, TabBar, TabIndex};
+use crate::window_registry::{all_windows, get_window_title, main_window};
+use crate::IPC_MESSAGE;
+
+use super::dispatcher::{serve_connection, Backend};
+use super::protocol::{TabInfo, WindowId, WindowInfo, PIPE_NAME};
+use super::{PendingCommand, UiCommand};
+
+// Longer than anything a tab bar does, short enough for a script to notice a stuck window
+const WINDOW_TIMEOUT_MS: u32 = 5000;
+
+// Tab bars live on the thread of their window, the pipe thread only sends them commands
+fn run_on_window<T: Send + 'static>(
+    window: WindowId,
+    command: impl FnOnce(&TabBar) -> T + Send + 'static,
+) -> std::result::Result<T, String> {
+    let (sender, receiver) = mpsc::channel();
+    let command: UiCommand = Box::new(move |tab_bar| {
+        let _ = sender.send(command(tab_bar));
+    });
+    let pending: Arc<PendingCommand> = Arc::new(Mutex::new(Some(command)));
+    // Owned by the message, a message never delivered leaks it rather than risk a dangling one
+    let message_reference = Arc::into_raw(pending.clone());
+    let mut answer = 0;
+    let sent = unsafe {
+        let message_id = RegisterWindowMessageW(IPC_MESSAGE);
+        SendMessageTimeoutW(
+            HWND(window),
+            message_id,
+            WPARAM(0),
+            LPARAM(message_reference as _),
+            SMTO_ABORTIFHUNG,
+            WINDOW_TIMEOUT_MS,
+            &mut answer,
+        )
+    };
+    if sent.0 == 0 {
+        // The window may still get to the message later, it must find nothing to run then
+        pending.lock().unwrap().take();
+    }
+    receiver
+        .try_recv()
+        .map_err(|_| format!("window {} did not answer", window))
+}
+
+// Checks the index is a tab of the strip first, not a group header
+fn run_on_tab_bar(
+    tab_bar: &TabBar,
+    tab: usize,
+    command: impl FnOnce(&TabBar, TabIndex) -> Result<()>,
+) -> std::result::Result<(), String> {
+    if !tab_bar.get_tab_infos().iter().any(|info| info.index == tab) {
+        return Err(format!("no tab at index {}", tab));
+    }
+    command(tab_bar, tab).map_err(|e| format!("{:?}", e))
+}
+
+fn run_on_tab(
+    window: WindowId,
+    tab: usize,
+    command: impl FnOnce(&TabBar, TabIndex) -> Result<()> + Send + 'static,
+) -> std::result::Result<(), String> {
+    run_on_window(window, move |tab_bar| run_on_tab_bar(tab_bar, tab, command))?
+}
+
+struct TabBarBackend;
+
+impl Backend for TabBarBackend {
+    fn windows(&self) -> Vec<WindowInfo> {
+        let main = main_window();
+        all_windows()
+            .into_iter()
+            .map(|window| WindowInfo {
+                id: window.0,
+                title: get_window_title(window),
+                tab_count: run_on_window(window.0, |tab_bar| tab_bar.get_tab_infos().len())
+                    .unwrap_or(0),
+                active: Some(window) == main,
+            })
+            .collect()
+    }
+
+    fn has_window(&self, window: WindowId) -> bool {
+        all_windows().contains(&HWND(window))
+    }
+
+    fn main_window(&self) -> Option<WindowId> {
+        main_window().map(|window| window.0)
+    }
+
+    fn tabs(&self, window: WindowId) -> std::result::Result<Vec<TabInfo>, String> {
+        run_on_window(window, |tab_bar| tab_bar.get_tab_infos())
+    }
+
+    fn open(
+        &self,
+        window: WindowId,
+        path: &str,
+        background: bool,
+    ) -> std::result::Result<(), String> {
+        let path = path.to_owned();
+        run_on_window(window, move |tab_bar| {
+            tab_bar
+                .open_path(&path, background)
+                .map_err(|_| format!("could not open {:?}", path))
+        })?
+    }
+
+    fn activate(&self, window: WindowId, tab: usize) -> std::result::Result<(), String> {
+        run_on_tab(window, tab, |tab_bar, index| tab_bar.switch_tab(index))
+    }
+
+    // Locked tabs and the last tab stay, which is an error here rather than nothing done
+    fn close(&self, window: WindowId, tab: usize) -> std::result::Result<(), String> {
+        run_on_window(window, move |tab_bar| {
+            let count = tab_bar.get_tab_infos().len();
+            run_on_tab_bar(tab_bar, tab, |tab_bar, index| tab_bar.remove_tab(index))?;
+            match tab_bar.get_tab_infos().len() < count {
+                true => Ok(()),
+                false => Err(format!("tab {} can't be closed", tab)),
+            }
+        })?
+    }
+
+    fn save_session(&self, window: WindowId, name: &str) -> std::result::Result<(), String> {
+        let name = name.to_owned();
+        run_on_window(window, move |tab_bar| {
+            tab_bar
+                .save_workspace(&name)
+                .map_err(|_| format!("could not save workspace {:?}", name))
+        })?
+    }
+
+    fn load_session(
+        &self,
+        window: WindowId,
+        name: &str,
+        replace: bool,
+    ) -> std::result::Result<(), String> {
+        let name = name.to_owned();
+        run_on_window(window, move |tab_bar| {
+            tab_bar
+                .open_workspace(&name, replace)
+                .map_err(|_| format!("could not open workspace {:?}", name))
+        })?
+    }
+
+    // The workspaces file is locked while it changes, any thread can do it
+    fn delete_session(&self, name: &str) -> std::result::Result<(), String> {
+        delete_workspace(name).map_err(|_| format!("could not delete workspace {:?}", name))
+    }
+
+    fn rename_session(&self, name: &str, new_name: &str) -> std::result::Result<(), String> {
+        rename_workspace(name, new_name)
+            .map_err(|_| format!("could not rename workspace {:?}", name))
+    }
+}
+
+/// Starts serving the control pipe, once per process.
+pub fn start() {
+    static STARTED: Once = Once::new();
+    STARTED.call_once(|| {
+        std::thread::spawn(|| {
+            if let Err(e) = run_server() {
+                log::error!("Control pipe stopped:{:?}", e);
+            }
+        });
+    });
+}
+
+fn create_pipe_instance(first: bool) -> Result<HANDLE> {
+    // The first instance fails when another explorer process already serves the pipe
+    let first_flag = match first {
+        true => FILE_FLAG_FIRST_PIPE_INSTANCE,
+        false => FILE_FLAGS_AND_ATTRIBUTES(0),
+    };
+    let pipe = unsafe {
+        CreateNamedPipeW(
+            PIPE_NAME,
+            PIPE_ACCESS_DUPLEX | first_flag,
+            PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+            PIPE_UNLIMITED_INSTANCES,
+            4096,
+            4096,
+            0,
+            std::ptr::null(),
+        )
+    };
+    match pipe.is_invalid() {
+        true => Err(Error::from_win32()),
+        false => Ok(pipe),
+    }
+}
+
+// Each client gets its own thread, a client that never sends its request blocks only itself
+fn run_server() -> Result<()> {
+    let mut pipe = create_pipe_instance(true)?;
+    loop {
+        let connected = unsafe { ConnectNamedPipe(pipe, std::ptr::null_mut()) }.as_bool()
+            || unsafe { GetLastError() } == ERROR_PIPE_CONNECTED;
+        if !connected {
+            unsafe { DisconnectNamedPipe(pipe) };
+            continue;
+        }
+        // Created before handing this one off, so the pipe always has an instance of ours
+        let next = create_pipe_instance(false)?;
+        // Closes the pipe when dropped, which disconnects the client
+        let file = unsafe { File::from_raw_handle(pipe.0 as _) };
+        std::thread::spawn(move || {
+            if let Err(e) = serve_connection(&TabBarBackend, BufReader::new(&file), &file) {
+                log::error!("Control pipe connection error:{:?}", e);
+            }
+        });
+        pipe = next;
+    }
+}
@@ -1,16 +1,31 @@
 #![allow(clippy::forget_copy)]
 
+mod bookmarks;
+mod delayed_save;
 mod detour;
+mod frecency;
+mod fuzzy;
+mod global_history;
+mod hooks;
 mod idl;
+mod ipc;
+mod mouse;
+mod palette;
+mod path_chars;
+mod routing;
 mod settings;
+mod tab_color;
 mod tabs;
+mod title_rules;
+mod vtable;
+mod window_registry;
+mod workspaces;
 
 use std::ffi::c_void;
 use std::path::PathBuf;
 use std::rc::{Rc, Weak};
 use std::sync::Mutex;
 
-use once_cell::sync::Lazy;
 use tabs::tab_bar::get_current_folder_path;
 use windows::core::{implement, Result, GUID, PCWSTR};
 use windows::Win32::System::LibraryLoader::DisableThreadLibraryCalls;
@@ -45,10 +60,12 @@ pub const EXT_TAB_GUID: GUID = GUID::from_values(
 );
 
 static mut DLL_LOCK: i32 = 0;
-static MAIN_BAR_OPEN: Lazy<Mutex<bool>> = Lazy::new(|| Mutex::new(false));
 
 pub const BROWSE_OBJECT_MESSAGE: &str = "extabbar_BrowseObject";
 pub const SHOW_WINDOW_MESSAGE: &str = "extabbar_ShowWindow";
+pub const RECEIVE_TAB_MESSAGE: &str = "extabbar_ReceiveTab";
+pub const MERGE_WINDOW_MESSAGE: &str = "extabbar_MergeWindow";
+pub const IPC_MESSAGE: &str = "extabbar_Ipc";
 
 #[derive(Clone)]
 struct BrowserEventHandlerContent {
@@ -184,7 +201,10 @@ impl DWebBrowserEvents2_Impl for BrowserEventHandler {}
 impl BrowserEventHandlerContent {
     fn navigate_complete(&self, _params: &[VARIANT]) -> Result<VARIANT> {
         let path = get_current_folder_path(&self.browser);
-        self.tab_bar.upgrade().unwrap().navigated(path)?;
+        let tab_bar = self.tab_bar.upgrade().unwrap();
+        tab_bar.navigated(path)?;
+        // A torn off window may get its bar before it is tied to the tab, it browses after that
+        tab_bar.adopt_torn_off_tab();
 
         Ok(Default::default())
     }
@@ -252,8 +272,9 @@ impl IDockingWindow_Impl for DeskBand {
         log::info!("CloseDW");
         if let Some(data) = &*self.data.lock().unwrap() {
             let handle = data.tab_bar.get_handle();
-            if data.tab_bar.is_main() {
-                *MAIN_BAR_OPEN.lock().unwrap() = false;
+            window_registry::unregister_window(data.tab_bar.get_explorer_handle());
+            if window_registry::main_window().is_none() {
+                detour::disable_hooks();
             }
             unsafe {
                 ShowWindow(handle, SW_HIDE);
@@ -351,24 +372,16 @@ impl IObjectWithSite_Impl for DeskBand {
         let explorer_handle = unsafe { shell_browser.GetWindow()? };
 
         let settings = current_settings();
-        let mut is_main = false;
-        {
-            let mut bar_open = MAIN_BAR_OPEN.lock().unwrap();
-            if !*bar_open {
-                *bar_open = true;
-                is_main = true;
-            }
-        }
         let tab_bar = tabs::tab_bar::TabBar::new(
             parent_window_handle,
             explorer_handle,
             travel_toolbar_handle,
             shell_browser.clone(),
             settings,
-            is_main,
         );
 
         tab_bar.add_tab(get_current_folder_path(&shell_browser), 0)?;
+        tab_bar.adopt_torn_off_tab();
 
         log::info!("Connecting to event handler");
         let browser_event_handler = BrowserEventHandler(Mutex::new(BrowserEventHandlerContent {
@@ -391,13 +404,14 @@ impl IObjectWithSite_Impl for DeskBand {
             p_input_object_site: Rc::new(input_object_site),
         });
 
+        // Failed hooks are logged and listed in the tab menu, the bar runs without them
         unsafe {
-            detour::hook_browse_object(shell_browser);
-            detour::hook_show_window();
-            if is_main {
-                detour::set_main_explorer(explorer_handle);
-            }
+            let _ = detour::hook_browse_object(shell_browser);
+            let _ = detour::hook_show_window();
         }
+        window_registry::register_window(explorer_handle);
+        #[cfg(windows)]
+        ipc::server::start();
 
         log::info!("Set Site Ok");
         Ok(())
@@ -0,0 +1,353 @@
+use serde::Deserialize;
+
+// One notch of a standard mouse wheel
+const WHEEL_DELTA: i32 = 120;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyModifier {
+    Shift,
+    Ctrl,
+    Alt,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct WheelSettings {
+    // Wheel over the tab strip moves to the previous or next tab
+    pub switch_tabs: bool,
+    // Going past the last tab comes back to the first one
+    pub wrap_around: bool,
+    // Wheel down goes to the previous tab instead of the next
+    pub reverse: bool,
+    // Held to scroll the strip instead when tabs don't fit
+    pub scroll_modifier: KeyModifier,
+}
+
+impl Default for WheelSettings {
+    fn default() -> Self {
+        Self {
+            switch_tabs: true,
+            wrap_around: false,
+            reverse: false,
+            scroll_modifier: KeyModifier::Shift,
+        }
+    }
+}
+
+/// Turns wheel deltas into whole steps.
+///
+/// High resolution wheels and touchpads send fractions of a notch, they add up until a step
+/// is reached. Turning the other way drops what was left over.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct WheelAccumulator {
+    remainder: i32,
+}
+
+impl WheelAccumulator {
+    // Positive steps for a wheel turned away from the user
+    pub fn add(&mut self, delta: i32) -> i32 {
+        if (delta > 0 && self.remainder < 0) || (delta < 0 && self.remainder > 0) {
+            self.remainder = 0;
+        }
+        self.remainder += delta;
+        let steps = self.remainder / WHEEL_DELTA;
+        self.remainder -= steps * WHEEL_DELTA;
+        steps
+    }
+
+    pub fn reset(&mut self) {
+        self.remainder = 0;
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+}
+
+// Part of the tab strip under the mouse
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ClickTarget {
+    Tab,
+    Empty,
+    CloseButton,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ClickKind {
+    Click,
+    DoubleClick,
+}
+
+impl Default for ClickKind {
+    fn default() -> Self {
+        ClickKind::Click
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MouseAction {
+    Switch,
+    Close,
+    Duplicate,
+    NewTab,
+    OpenParent,
+    ToggleLock,
+    Rename,
+    ToggleMark,
+    Menu,
+    // Overrides a default binding
+    Nothing,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct MouseBinding {
+    pub button: MouseButton,
+    // Modifiers that have to be held, and no others
+    #[serde(default)]
+    pub modifiers: Vec<KeyModifier>,
+    pub target: ClickTarget,
+    #[serde(default)]
+    pub kind: ClickKind,
+    pub action: MouseAction,
+}
+
+impl MouseBinding {
+    fn new(
+        button: MouseButton,
+        modifiers: &[KeyModifier],
+        target: ClickTarget,
+        kind: ClickKind,
+        action: MouseAction,
+    ) -> Self {
+        MouseBinding {
+            button,
+            modifiers: modifiers.to_vec(),
+            target,
+            kind,
+            action,
+        }
+    }
+
+    fn matches(&self, click: &Click, kind: ClickKind) -> bool {
+        self.button == click.button
+            && self.target == click.target
+            && self.kind == kind
+            && self.modifiers.iter().all(|m| click.modifiers.contains(m))
+            && click.modifiers.iter().all(|m| self.modifiers.contains(m))
+    }
+}
+
+pub struct Click {
+    pub button: MouseButton,
+    pub modifiers: Vec<KeyModifier>,
+    pub target: ClickTarget,
+    pub kind: ClickKind,
+}
+
+/// Bindings used when the settings don't have one for a click.
+pub fn default_mouse_bindings() -> Vec<MouseBinding> {
+    use ClickKind::*;
+    use ClickTarget::*;
+    use KeyModifier::*;
+    use MouseAction::*;
+    use MouseButton::*;
+    vec![
+        MouseBinding::new(Left, &[], Tab, Click, Switch),
+        MouseBinding::new(Left, &[Ctrl], Tab, Click, Duplicate),
+        MouseBinding::new(Left, &[Shift], Tab, Click, ToggleMark),
+        MouseBinding::new(Left, &[], Tab, DoubleClick, Rename),
+        MouseBinding::new(Left, &[], Empty, DoubleClick, NewTab),
+        MouseBinding::new(Left, &[], CloseButton, Click, Close),
+        MouseBinding::new(Middle, &[], Tab, Click, Close),
+        MouseBinding::new(Middle, &[], CloseButton, Click, Close),
+        MouseBinding::new(Right, &[], Tab, Click, Menu),
+        MouseBinding::new(Right, &[], Empty, Click, Menu),
+        MouseBinding::new(Right, &[], CloseButton, Click, Menu),
+    ]
+}
+
+/// Action bound to a click, the configured bindings come before the default ones.
+///
+/// A double click nothing is bound to counts as a single click, so quick successive clicks
+/// aren't lost.
+pub fn find_mouse_action(bindings: &[MouseBinding], click: &Click) -> MouseAction {
+    let defaults = default_mouse_bindings();
+    let find = |kind| {
+        bindings
+            .iter()
+            .chain(defaults.iter())
+            .find(|binding| binding.matches(click, kind))
+            .map(|binding| binding.action)
+    };
+    find(click.kind)
+        .or_else(|| find(ClickKind::Click))
+        .unwrap_or(MouseAction::Nothing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fractions_of_a_notch_add_up() {
+        let mut wheel = WheelAccumulator::default();
+        assert_eq!(wheel.add(40), 0);
+        assert_eq!(wheel.add(40), 0);
+        assert_eq!(wheel.add(40), 1);
+        assert_eq!(wheel.add(-60), 0);
+        assert_eq!(wheel.add(-60), -1);
+    }
+
+    #[test]
+    fn leftover_carries_over_to_the_next_notch() {
+        let mut wheel = WheelAccumulator::default();
+        assert_eq!(wheel.add(300), 2);
+        assert_eq!(wheel.add(60), 1);
+        assert_eq!(wheel.add(WHEEL_DELTA), 1);
+    }
+
+    #[test]
+    fn turning_back_drops_the_leftover() {
+        let mut wheel = WheelAccumulator::default();
+        assert_eq!(wheel.add(100), 0);
+        // Without the reset the 100 would cancel most of this
+        assert_eq!(wheel.add(-WHEEL_DELTA), -1);
+        assert_eq!(wheel.add(-100), 0);
+        assert_eq!(wheel.add(100), 0);
+        assert_eq!(wheel.add(20), 1);
+        wheel.add(100);
+        wheel.reset();
+        assert_eq!(wheel.add(20), 0);
+    }
+
+    fn click(
+        button: MouseButton,
+        modifiers: &[KeyModifier],
+        target: ClickTarget,
+        kind: ClickKind,
+    ) -> Click {
+        Click {
+            button,
+            modifiers: modifiers.to_vec(),
+            target,
+            kind,
+        }
+    }
+
+    #[test]
+    fn configured_bindings_come_first() {
+        let middle_click = click(MouseButton::Middle, &[], ClickTarget::Tab, ClickKind::Click);
+        assert_eq!(find_mouse_action(&[], &middle_click), MouseAction::Close);
+        let bindings = [MouseBinding::new(
+            MouseButton::Middle,
+            &[],
+            ClickTarget::Tab,
+            ClickKind::Click,
+            MouseAction::Duplicate,
+        )];
+        assert_eq!(
+            find_mouse_action(&bindings, &middle_click),
+            MouseAction::Duplicate
+        );
+        // Only that click changes
+        let close_click = click(
+            MouseButton::Middle,
+            &[],
+            ClickTarget::CloseButton,
+            ClickKind::Click,
+        );
+        assert_eq!(
+            find_mouse_action(&bindings, &close_click),
+            MouseAction::Close
+        );
+    }
+
+    #[test]
+    fn nothing_overrides_a_default() {
+        let bindings = [MouseBinding::new(
+            MouseButton::Right,
+            &[],
+            ClickTarget::Empty,
+            ClickKind::Click,
+            MouseAction::Nothing,
+        )];
+        let right_click = click(
+            MouseButton::Right,
+            &[],
+            ClickTarget::Empty,
+            ClickKind::Click,
+        );
+        assert_eq!(
+            find_mouse_action(&bindings, &right_click),
+            MouseAction::Nothing
+        );
+    }
+
+    #[test]
+    fn unbound_double_click_falls_back_to_a_click() {
+        let double_click = click(
+            MouseButton::Middle,
+            &[],
+            ClickTarget::Tab,
+            ClickKind::DoubleClick,
+        );
+        assert_eq!(find_mouse_action(&[], &double_click), MouseAction::Close);
+        // A bound double click is not a click
+        let double_click = click(
+            MouseButton::Left,
+            &[],
+            ClickTarget::Tab,
+            ClickKind::DoubleClick,
+        );
+        assert_eq!(find_mouse_action(&[], &double_click), MouseAction::Rename);
+    }
+
+    #[test]
+    fn modifiers_must_match_exactly() {
+        use KeyModifier::*;
+        let left_click = |modifiers: &[KeyModifier]| {
+            click(
+                MouseButton::Left,
+                modifiers,
+                ClickTarget::Tab,
+                ClickKind::Click,
+            )
+        };
+        assert_eq!(
+            find_mouse_action(&[], &left_click(&[])),
+            MouseAction::Switch
+        );
+        assert_eq!(
+            find_mouse_action(&[], &left_click(&[Ctrl])),
+            MouseAction::Duplicate
+        );
+        assert_eq!(
+            find_mouse_action(&[], &left_click(&[Ctrl, Shift])),
+            MouseAction::Nothing
+        );
+        let bindings = [MouseBinding::new(
+            MouseButton::Left,
+            &[Ctrl, Shift],
+            ClickTarget::Tab,
+            ClickKind::Click,
+            MouseAction::OpenParent,
+        )];
+        // Held in any order
+        assert_eq!(
+            find_mouse_action(&bindings, &left_click(&[Shift, Ctrl])),
+            MouseAction::OpenParent
+        );
+        assert_eq!(
+            find_mouse_action(&bindings, &left_click(&[Ctrl, Shift, Alt])),
+            MouseAction::Nothing
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::HashSet;
+
+use crate::fuzzy::fuzzy_score;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CandidateSource {
+    Tab,
+    Bookmark,
+    History,
+}
+
+impl CandidateSource {
+    // Open tabs come first when matches are close
+    fn bonus(self) -> i32 {
+        match self {
+            CandidateSource::Tab => 30,
+            CandidateSource::Bookmark => 20,
+            CandidateSource::History => 0,
+        }
+    }
+}
+
+/// Entry of the command palette.
+#[derive(Clone, Debug)]
+pub struct Candidate {
+    pub title: String,
+    // Full path, also used to drop duplicates
+    pub detail: String,
+    pub source: CandidateSource,
+}
+
+// Matches on the full path count less than matches on the title
+const DETAIL_PENALTY: i32 = 20;
+
+/// Guesses whether the query is a path to open rather than a search.
+pub fn is_typed_path(query: &str) -> bool {
+    let query = query.trim();
+    let bytes = query.as_bytes();
+    let drive_path = bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':';
+    drive_path
+        || query.starts_with("\\\\")
+        || query.starts_with("::{")
+        || query.to_lowercase().starts_with("shell:")
+}
+
+fn score(query: &str, candidate: &Candidate) -> Option<i32> {
+    let title_score = fuzzy_score(query, &candidate.title);
+    let detail_score = fuzzy_score(query, &candidate.detail).map(|s| s - DETAIL_PENALTY);
+    let best = title_score.into_iter().chain(detail_score).max()?;
+    Some(best + candidate.source.bonus())
+}
+
+/// Indices of the candidates matching the query, best first.
+///
+/// Candidates sharing a path only appear once, the first of them in the input wins ties.
+/// With an empty query the input order is kept.
+pub fn rank_candidates(query: &str, candidates: &[Candidate], limit: usize) -> Vec<usize> {
+    let query = query.trim();
+    let mut scored: Vec<(i32, usize)> = candidates
+        .iter()
+        .enumerate()
+        .filter_map(|(index, candidate)| Some((score(query, candidate)?, index)))
+        .collect();
+    if !query.is_empty() {
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    }
+
+    let mut seen = HashSet::new();
+    scored
+        .into_iter()
+        .map(|(_, index)| index)
+        .filter(|index| {
+            let detail = candidates[*index].detail.to_lowercase();
+            detail.is_empty() || seen.insert(detail)
+        })
+        .take(limit)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(title: &str, detail: &str, source: CandidateSource) -> Candidate {
+        Candidate {
+            title: title.to_owned(),
+            detail: detail.to_owned(),
+            source,
+        }
+    }
+
+    #[test]
+    fn typed_paths() {
+        assert!(is_typed_path("C:\\Users"));
+        assert!(is_typed_path("  d:"));
+        assert!(is_typed_path("\\\\server\\share"));
+        assert!(is_typed_path("::{20D04FE0-3AEA-1069-A2D8-08002B30309D}"));
+        assert!(is_typed_path("Shell:Downloads"));
+        assert!(!is_typed_path("downloads"));
+        assert!(!is_typed_path("1:"));
+        assert!(!is_typed_path("c"));
+        assert!(!is_typed_path(""));
+    }
+
+    #[test]
+    fn best_match_first() {
+        let candidates = [
+            candidate("Music", "C:\\Users\\me\\Music", CandidateSource::History),
+            candidate(
+                "Documents",
+                "C:\\Users\\me\\Documents",
+                CandidateSource::History,
+            ),
+            candidate(
+                "Old docs",
+                "D:\\Archive\\Old docs",
+                CandidateSource::History,
+            ),
+        ];
+        assert_eq!(rank_candidates("docu", &candidates, 10), vec![1]);
+        assert_eq!(rank_candidates("doc", &candidates, 10), vec![1, 2]);
+    }
+
+    #[test]
+    fn tabs_win_close_matches() {
+        let candidates = [
+            candidate("Projects", "C:\\Projects", CandidateSource::History),
+            candidate("Projects", "D:\\Projects", CandidateSource::Bookmark),
+            candidate("Projects", "E:\\Projects", CandidateSource::Tab),
+        ];
+        assert_eq!(rank_candidates("proj", &candidates, 10), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn title_matches_beat_path_matches() {
+        let candidates = [
+            candidate("Reports", "C:\\Work\\Reports", CandidateSource::History),
+            candidate("Work", "C:\\Work", CandidateSource::History),
+        ];
+        assert_eq!(rank_candidates("work", &candidates, 10), vec![1, 0]);
+    }
+
+    #[test]
+    fn duplicate_paths_appear_once() {
+        let candidates = [
+            candidate("Users", "C:\\Users", CandidateSource::History),
+            candidate("Users", "c:\\users", CandidateSource::Tab),
+            candidate("Users", "C:\\Users", CandidateSource::Bookmark),
+        ];
+        // The open tab scores best, the other entries for its path are dropped
+        assert_eq!(rank_candidates("users", &candidates, 10), vec![1]);
+    }
+
+    #[test]
+    fn empty_query_keeps_input_order() {
+        let candidates = [
+            candidate("B", "C:\\B", CandidateSource::History),
+            candidate("A", "C:\\A", CandidateSource::Tab),
+            candidate("B again", "C:\\B", CandidateSource::Bookmark),
+        ];
+        assert_eq!(rank_candidates("  ", &candidates, 10), vec![0, 1]);
+    }
+
+    #[test]
+    fn limit_and_no_match() {
+        let candidates: Vec<_> = (0..20)
+            .map(|i| {
+                let title = format!("Folder {}", i);
+                candidate(&title, &format!("C:\\{}", title), CandidateSource::History)
+            })
+            .collect();
+        assert_eq!(rank_candidates("folder", &candidates, 5).len(), 5);
+        assert!(rank_candidates("xyz", &candidates, 5).is_empty());
+    }
+}
@@ -0,0 +1,11 @@
+//! Character comparisons for the folder paths rules are written against.
+
+/// Both slash styles separate path components.
+pub fn is_separator(c: char) -> bool {
+    c == '\\' || c == '/'
+}
+
+/// Compares path characters ignoring case and separator style.
+pub fn path_chars_equal(a: char, b: char) -> bool {
+    (is_separator(a) && is_separator(b)) || a.to_lowercase().eq(b.to_lowercase())
+}
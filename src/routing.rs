@@ -0,0 +1,220 @@
+use serde::Deserialize;
+
+use crate::mouse::KeyModifier;
+use crate::title_rules::{glob_match, DriveKind};
+
+/// Where a folder explorer would open in a new window ends up.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Route {
+    ForegroundTab,
+    BackgroundTab,
+    NewWindow,
+}
+
+/// Routes new windows matching every condition given.
+///
+/// `pattern` is a glob on the folder path, as in title rules. Virtual folders like the control
+/// panel are matched on their parsing name, `::{...}`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct RoutingRule {
+    #[serde(default)]
+    pub pattern: Option<String>,
+    #[serde(default)]
+    pub drive: Option<DriveKind>,
+    // Folders with no file system path
+    #[serde(default)]
+    pub virtual_folder: Option<bool>,
+    // Has to be held when the window opens
+    #[serde(default)]
+    pub modifier: Option<KeyModifier>,
+    pub route: Route,
+}
+
+pub struct RoutingContext<'a> {
+    pub path: &'a str,
+    pub drive: Option<DriveKind>,
+    pub virtual_folder: bool,
+    pub modifiers: &'a [KeyModifier],
+}
+
+impl RoutingRule {
+    fn matches(&self, context: &RoutingContext) -> bool {
+        let pattern_matches = match &self.pattern {
+            Some(pattern) => glob_match(pattern, context.path).is_some(),
+            None => true,
+        };
+        pattern_matches
+            && (self.drive.is_none() || self.drive == context.drive)
+            && self.virtual_folder.map_or(true, |virtual_folder| {
+                virtual_folder == context.virtual_folder
+            })
+            && self
+                .modifier
+                .map_or(true, |modifier| context.modifiers.contains(&modifier))
+    }
+}
+
+/// Route of the first matching rule, or `default` when none match.
+pub fn route_new_window(rules: &[RoutingRule], default: Route, context: &RoutingContext) -> Route {
+    rules
+        .iter()
+        .find(|rule| rule.matches(context))
+        .map_or(default, |rule| rule.route)
+}
+
+// Shift opens a real window, as it would without tabs
+pub fn default_routing_rules() -> Vec<RoutingRule> {
+    vec![RoutingRule {
+        pattern: None,
+        drive: None,
+        virtual_folder: None,
+        modifier: Some(KeyModifier::Shift),
+        route: Route::NewWindow,
+    }]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use KeyModifier::*;
+    use Route::*;
+
+    // Rules as written in the settings file
+    fn rules(json: &str) -> Vec<RoutingRule> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn context<'a>(path: &'a str, modifiers: &'a [KeyModifier]) -> RoutingContext<'a> {
+        let virtual_folder = path.starts_with("::");
+        RoutingContext {
+            path,
+            drive: match virtual_folder {
+                true => None,
+                false => Some(DriveKind::Fixed),
+            },
+            virtual_folder,
+            modifiers,
+        }
+    }
+
+    const CONTROL_PANEL: &str = "::{26EE0668-A00A-44D7-9371-BEB064C98683}";
+
+    #[test]
+    fn default_rules_by_modifier() {
+        let rules = default_routing_rules();
+        let table: [(&[KeyModifier], Route); 5] = [
+            (&[], ForegroundTab),
+            (&[Shift], NewWindow),
+            (&[Ctrl], ForegroundTab),
+            (&[Alt], ForegroundTab),
+            (&[Ctrl, Shift], NewWindow),
+        ];
+        for (modifiers, expected) in table.iter() {
+            let route = route_new_window(&rules, ForegroundTab, &context("C:\\", modifiers));
+            assert_eq!(route, *expected, "modifiers {:?}", modifiers);
+        }
+    }
+
+    #[test]
+    fn each_modifier_selects_its_rule() {
+        let rules = rules(
+            r#"[
+                {"modifier": "ctrl", "route": "background_tab"},
+                {"modifier": "alt", "route": "new_window"},
+                {"modifier": "shift", "route": "foreground_tab"}
+            ]"#,
+        );
+        let table: [(&[KeyModifier], Route); 5] = [
+            (&[], NewWindow),
+            (&[Ctrl], BackgroundTab),
+            (&[Alt], NewWindow),
+            (&[Shift], ForegroundTab),
+            // The first rule listed wins when several modifiers are held
+            (&[Shift, Ctrl], BackgroundTab),
+        ];
+        for (modifiers, expected) in table.iter() {
+            let route = route_new_window(&rules, NewWindow, &context("C:\\", modifiers));
+            assert_eq!(route, *expected, "modifiers {:?}", modifiers);
+        }
+    }
+
+    #[test]
+    fn file_system_and_virtual_folders() {
+        let rules = rules(
+            r#"[
+                {"virtual_folder": true, "route": "new_window"},
+                {"virtual_folder": false, "route": "background_tab"}
+            ]"#,
+        );
+        let table = [
+            ("C:\\Users", BackgroundTab),
+            ("\\\\server\\share", BackgroundTab),
+            (CONTROL_PANEL, NewWindow),
+        ];
+        for (path, expected) in table.iter() {
+            let route = route_new_window(&rules, ForegroundTab, &context(path, &[]));
+            assert_eq!(route, *expected, "path {}", path);
+        }
+    }
+
+    #[test]
+    fn patterns_and_drives() {
+        let rules = rules(
+            r#"[
+                {"pattern": "C:\\Windows\\**", "route": "new_window"},
+                {"pattern": "::{26EE0668-A00A-44D7-9371-BEB064C98683}", "route": "new_window"},
+                {"drive": "removable", "route": "background_tab"}
+            ]"#,
+        );
+        let removable = RoutingContext {
+            drive: Some(DriveKind::Removable),
+            ..context("E:\\Photos", &[])
+        };
+        assert_eq!(
+            route_new_window(&rules, ForegroundTab, &removable),
+            BackgroundTab
+        );
+        let table = [
+            ("C:\\Windows\\System32", NewWindow),
+            ("C:\\Users", ForegroundTab),
+            (CONTROL_PANEL, NewWindow),
+            ("E:\\Photos", ForegroundTab),
+        ];
+        for (path, expected) in table.iter() {
+            let route = route_new_window(&rules, ForegroundTab, &context(path, &[]));
+            assert_eq!(route, *expected, "path {}", path);
+        }
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let rules = rules(
+            r#"[
+                {"pattern": "C:\\Projects\\**", "modifier": "ctrl", "route": "new_window"},
+                {"pattern": "C:\\Projects\\**", "route": "background_tab"},
+                {"pattern": "C:\\**", "route": "new_window"}
+            ]"#,
+        );
+        let table: [(&str, &[KeyModifier], Route); 5] = [
+            // Every condition of a rule has to hold
+            ("C:\\Projects\\app", &[Ctrl], NewWindow),
+            ("C:\\Projects\\app", &[], BackgroundTab),
+            ("C:\\Users", &[Ctrl], NewWindow),
+            ("C:\\Users", &[], NewWindow),
+            ("D:\\Data", &[Ctrl], ForegroundTab),
+        ];
+        for (path, modifiers, expected) in table.iter() {
+            let route = route_new_window(&rules, ForegroundTab, &context(path, modifiers));
+            assert_eq!(route, *expected, "path {} modifiers {:?}", path, modifiers);
+        }
+    }
+
+    #[test]
+    fn no_rules_use_the_default() {
+        assert_eq!(
+            route_new_window(&[], BackgroundTab, &context("C:\\", &[Shift])),
+            BackgroundTab
+        );
+    }
+}
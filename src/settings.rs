@@ -1,16 +1,59 @@
 use std::error::Error;
 
 use crate::get_dll_path;
+use crate::mouse::{MouseBinding, WheelSettings};
+use crate::routing::{default_routing_rules, Route, RoutingRule};
+use crate::tab_color::{ColorRule, TabColorStyle};
+use crate::title_rules::TitleRule;
 use serde::Deserialize;
 
 #[derive(Deserialize, Debug)]
+#[serde(default)]
 pub struct Settings {
     pub dark_mode: bool,
+    pub title_rules: Vec<TitleRule>,
+    pub color_rules: Vec<ColorRule>,
+    pub tab_color_style: TabColorStyle,
+    // Entries kept on each side of a tab's history, 0 keeps everything
+    pub history_depth: usize,
+    // Collapse repeated folders and back-and-forth navigation in tab histories
+    pub collapse_history: bool,
+    // Limits of the history shared by all tabs, 0 keeps everything
+    pub global_history_entries: usize,
+    pub global_history_days: u32,
+    pub mouse_wheel: WheelSettings,
+    // Checked before the default bindings
+    pub mouse_bindings: Vec<MouseBinding>,
+    pub show_close_button: bool,
+    // Folder opened by new tabs, anything the address bar accepts
+    pub home_folder: String,
+    // Folders opened from the view with a middle click or ctrl+double click stay in the background
+    pub open_in_background: bool,
+    // Decide where folders explorer would open in a new window go, first match wins
+    pub routing_rules: Vec<RoutingRule>,
+    pub default_route: Route,
 }
 
 impl Default for Settings {
     fn default() -> Self {
-        Self { dark_mode: true }
+        Self {
+            dark_mode: true,
+            title_rules: Vec::new(),
+            color_rules: Vec::new(),
+            tab_color_style: TabColorStyle::Stripe,
+            history_depth: 50,
+            collapse_history: false,
+            global_history_entries: 2000,
+            global_history_days: 90,
+            mouse_wheel: WheelSettings::default(),
+            mouse_bindings: Vec::new(),
+            show_close_button: false,
+            // This PC
+            home_folder: "::{20D04FE0-3AEA-1069-A2D8-08002B30309D}".to_owned(),
+            open_in_background: true,
+            routing_rules: default_routing_rules(),
+            default_route: Route::ForegroundTab,
+        }
     }
 }
 
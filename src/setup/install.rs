@@ -0,0 +1,175 @@
+use winreg::{
+    enums::{HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE, KEY_ALL_ACCESS},
+    RegKey,
+};
+
+// {9ecce421-925a-4484-b2cf-c00b182bc32a}
+const EXT_TAB_GUID: &str = "{9ecce421-925a-4484-b2cf-c00b182bc32a}";
+
+// Verb added next to the folder verbs, made the default verb only when asked to
+const FOLDER_VERB: &str = "extabbar";
+const FOLDER_CLASSES: [&str; 2] = ["Folder", "Directory"];
+// Value names are the keys setup had to create, the only ones uninstall may remove
+const CREATED_KEYS_KEY: &str = "Software\\extabbar\\CreatedKeys";
+// Default verb of each class before ours, a missing value is kept as a missing value
+const VERB_BACKUP_KEY: &str = "Software\\extabbar\\VerbBackup";
+
+fn ask(question: &str) -> String {
+    println!("{}", question);
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input).unwrap();
+    input.trim().to_owned()
+}
+
+// Folders opened with our verb go to extabctl, which hands them to a running tab bar
+fn register_folder_handler(hkcu: &RegKey) {
+    let extabctl = std::env::current_exe()
+        .unwrap()
+        .with_file_name("extabctl.exe");
+    // %V is the folder path even when the item is a link or a shell location
+    let command = format!("\"{}\" open \"%V\"", extabctl.display());
+    let (created_keys, _) = hkcu.create_subkey(CREATED_KEYS_KEY).unwrap();
+    for class in FOLDER_CLASSES {
+        let class_key = format!("Software\\Classes\\{}", class);
+        let shell_key = format!("{}\\shell", class_key);
+        for key in [&class_key, &shell_key] {
+            if hkcu.open_subkey(key).is_err() {
+                created_keys.set_value(key, &"").unwrap();
+            }
+        }
+        let (verb, _) = hkcu
+            .create_subkey(format!("{}\\{}", shell_key, FOLDER_VERB))
+            .unwrap();
+        verb.set_value("", &"Open in Tab").unwrap();
+        let (verb_command, _) = verb.create_subkey("command").unwrap();
+        verb_command.set_value("", &command).unwrap();
+    }
+}
+
+// Folders opened with ShellExecute or by explorer itself then go to extabctl too.
+// Only the default value of each `shell` key changes, the other verbs are left as they are.
+fn make_default_verb(hkcu: &RegKey) {
+    let (backup, _) = hkcu.create_subkey(VERB_BACKUP_KEY).unwrap();
+    for class in FOLDER_CLASSES {
+        let (shell, _) = hkcu
+            .create_subkey(format!("Software\\Classes\\{}\\shell", class))
+            .unwrap();
+        // Installing twice must not back up our own verb
+        if backup.open_subkey(class).is_err() {
+            let (class_backup, _) = backup.create_subkey(class).unwrap();
+            if let Ok(original) = shell.get_raw_value("") {
+                class_backup.set_raw_value("default", &original).unwrap();
+            }
+        }
+        shell.set_value("", &FOLDER_VERB).unwrap();
+    }
+}
+
+// Puts back the default verbs as they were, value type included
+fn restore_default_verb(hkcu: &RegKey) {
+    let backup = match hkcu.open_subkey(VERB_BACKUP_KEY) {
+        Ok(backup) => backup,
+        // Never made the default
+        Err(_) => return,
+    };
+    for class in FOLDER_CLASSES {
+        let class_backup = match backup.open_subkey(class) {
+            Ok(class_backup) => class_backup,
+            Err(_) => continue,
+        };
+        let shell = match hkcu.open_subkey_with_flags(
+            format!("Software\\Classes\\{}\\shell", class),
+            KEY_ALL_ACCESS,
+        ) {
+            Ok(shell) => shell,
+            Err(_) => continue,
+        };
+        let _ = match class_backup.get_raw_value("default") {
+            Ok(original) => shell.set_raw_value("", &original),
+            Err(_) => shell.delete_value(""),
+        };
+    }
+    let _ = hkcu.delete_subkey_all(VERB_BACKUP_KEY);
+}
+
+// Removes our verb, then the keys created for it when nothing else was put in them since
+fn unregister_folder_handler(hkcu: &RegKey) {
+    restore_default_verb(hkcu);
+    let created_keys = hkcu.open_subkey(CREATED_KEYS_KEY).ok();
+    let was_created = |key: &str| {
+        created_keys.as_ref().map_or(false, |created_keys| {
+            created_keys.get_raw_value(key).is_ok()
+        })
+    };
+    for class in FOLDER_CLASSES {
+        let class_key = format!("Software\\Classes\\{}", class);
+        let shell_key = format!("{}\\shell", class_key);
+        let _ = hkcu.delete_subkey_all(format!("{}\\{}", shell_key, FOLDER_VERB));
+        for key in [&shell_key, &class_key] {
+            let is_empty = hkcu
+                .open_subkey(key)
+                .and_then(|key| key.query_info())
+                .map_or(false, |info| info.sub_keys == 0 && info.values == 0);
+            if was_created(key) && is_empty {
+                let _ = hkcu.delete_subkey(key);
+            }
+        }
+    }
+    let _ = hkcu.delete_subkey_all("Software\\extabbar");
+}
+
+pub fn run() -> std::io::Result<()> {
+    let hkcu = RegKey::predef(HKEY_LOCAL_MACHINE);
+    let user = RegKey::predef(HKEY_CURRENT_USER);
+
+    let uninstall = match ask("Install/Uninstall ? (i/u)").as_str() {
+        "u" => true,
+        "i" => false,
+        _ => panic!("Invalid input"),
+    };
+
+    let clsid = hkcu.open_subkey("Software\\Classes\\CLSID").unwrap();
+    let (toolbar, _) = hkcu
+        .create_subkey("Software\\Microsoft\\Internet Explorer\\Toolbar")
+        .unwrap();
+    if uninstall {
+        clsid.delete_subkey_all(EXT_TAB_GUID).unwrap();
+        toolbar.delete_value(EXT_TAB_GUID).unwrap();
+        unregister_folder_handler(&user);
+    } else {
+        let clsid = hkcu.open_subkey("Software\\Classes\\CLSID").unwrap();
+
+        let (class, _) = clsid.create_subkey(EXT_TAB_GUID).unwrap();
+        class.set_value("", &"exttabbar").unwrap();
+        let (inproc, _) = class.create_subkey("InProcServer32").unwrap();
+        inproc.set_value("ThreadingModel", &"Apartment").unwrap();
+        inproc
+            .set_value(
+                "",
+                &std::env::current_exe()
+                    .unwrap()
+                    .with_file_name("extabbar.dll")
+                    .as_os_str(),
+            )
+            .unwrap();
+
+        toolbar.set_value(EXT_TAB_GUID, &"extabbar").unwrap();
+
+        // Adds an "Open in Tab" entry to the folder context menu
+        let answer = ask("Add Open in Tab to the folder menu ? (y/n)");
+        match answer.as_str() {
+            "y" => {
+                register_folder_handler(&user);
+                let answer =
+                    ask("Open folders in tabs by default, from other applications too ? (y/n)");
+                match answer.as_str() {
+                    "y" => make_default_verb(&user),
+                    _ => restore_default_verb(&user),
+                }
+            }
+            _ => unregister_folder_handler(&user),
+        }
+    }
+
+    Ok(())
+}
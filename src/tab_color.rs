@@ -0,0 +1,111 @@
+use std::convert::TryFrom;
+
+use serde::Deserialize;
+
+use crate::path_chars::{is_separator, path_chars_equal};
+
+/// Color in the Win32 COLORREF layout, `0x00bbggrr`.
+///
+/// Read from settings either as `#rrggbb` or as one of the preset names.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub struct TabColor(pub u32);
+
+pub const PRESET_COLORS: [(&str, TabColor); 7] = [
+    ("Red", TabColor::from_rgb(0xd1, 0x34, 0x38)),
+    ("Orange", TabColor::from_rgb(0xe8, 0x7a, 0x1e)),
+    ("Yellow", TabColor::from_rgb(0xe0, 0xc2, 0x1a)),
+    ("Green", TabColor::from_rgb(0x2e, 0xa0, 0x43)),
+    ("Blue", TabColor::from_rgb(0x1e, 0x78, 0xd7)),
+    ("Purple", TabColor::from_rgb(0x88, 0x4a, 0xd0)),
+    ("Gray", TabColor::from_rgb(0x80, 0x80, 0x80)),
+];
+
+impl TabColor {
+    pub const fn from_rgb(r: u8, g: u8, b: u8) -> Self {
+        TabColor(r as u32 | (g as u32) << 8 | (b as u32) << 16)
+    }
+
+    fn channels(self) -> [u32; 3] {
+        [self.0 & 0xff, (self.0 >> 8) & 0xff, (self.0 >> 16) & 0xff]
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if let Some((_, color)) = PRESET_COLORS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(text))
+        {
+            return Some(*color);
+        }
+
+        let hex = text.strip_prefix('#')?;
+        if hex.len() != 6 {
+            return None;
+        }
+        let rgb = u32::from_str_radix(hex, 16).ok()?;
+        Some(Self::from_rgb(
+            (rgb >> 16) as u8,
+            (rgb >> 8) as u8,
+            rgb as u8,
+        ))
+    }
+
+    /// Mixes `amount` (0 to 255) of this color into `background`.
+    pub fn blend(self, background: u32, amount: u32) -> u32 {
+        let amount = amount.min(255);
+        let background = TabColor(background).channels();
+        let color = self.channels();
+        (0..3).fold(0, |output, channel| {
+            let mixed = (color[channel] * amount + background[channel] * (255 - amount)) / 255;
+            output | mixed << (channel * 8)
+        })
+    }
+}
+
+impl TryFrom<String> for TabColor {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        Self::parse(&text).ok_or_else(|| format!("invalid color {:?}", text))
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TabColorStyle {
+    Stripe,
+    Tint,
+}
+
+/// Colors tabs whose folder path starts with `prefix`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ColorRule {
+    pub prefix: String,
+    pub color: TabColor,
+}
+
+// Prefix match on whole path components, ignoring case and separator style
+fn path_starts_with(path: &str, prefix: &str) -> bool {
+    let prefix = prefix.trim_end_matches(is_separator);
+    let mut path_chars = path.chars();
+    for prefix_char in prefix.chars() {
+        let path_char = match path_chars.next() {
+            Some(c) => c,
+            None => return false,
+        };
+        if !path_chars_equal(path_char, prefix_char) {
+            return false;
+        }
+    }
+    prefix.is_empty() || path_chars.next().map_or(true, is_separator)
+}
+
+/// Color of the longest rule prefix matching the path.
+pub fn match_color_rules(rules: &[ColorRule], path: &str) -> Option<TabColor> {
+    rules
+        .iter()
+        .filter(|rule| path_starts_with(path, &rule.prefix))
+        .max_by_key(|rule| rule.prefix.trim_end_matches(is_separator).len())
+        .map(|rule| rule.color)
+}
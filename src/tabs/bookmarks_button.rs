@@ -0,0 +1,392 @@
+use std::ptr::addr_of_mut;
+use std::rc::Weak;
+
+use windows::core::{Result, PCWSTR};
+use windows::Win32::Foundation::*;
+use windows::Win32::Graphics::Gdi::{MapWindowPoints, HFONT};
+use windows::Win32::UI::Input::KeyboardAndMouse::{GetKeyState, VK_CONTROL};
+use windows::Win32::UI::Shell::{DefSubclassProc, RemoveWindowSubclass, SetWindowSubclass};
+use windows::Win32::UI::WindowsAndMessaging::*;
+
+use crate::bookmarks::{current_bookmarks, update_bookmarks, BookmarkNode, Bookmarks};
+
+use super::inline_edit::InlineEdit;
+use super::popup_menu::BOOKMARK_MENU_IDS;
+use super::tab_bar::{TabBar, DLL_INSTANCE};
+
+enum BookmarkCommand {
+    Open(Vec<usize>),
+    OpenAll(Vec<usize>),
+    AddCurrentFolder(Vec<usize>),
+    NewFolder(Vec<usize>),
+    Rename(Vec<usize>),
+    Remove(Vec<usize>),
+    // Node, then the folder it goes to
+    Move(Vec<usize>, Vec<usize>),
+}
+
+/// Button at the end of the tab strip listing the bookmarks.
+///
+/// A sibling of the strip, which leaves it room, see `WM_WINDOWPOSCHANGING` in `TabControl`.
+pub struct BookmarksButton {
+    handle: HWND,
+    // The tab control
+    strip: HWND,
+    tab_bar: Weak<TabBar>,
+}
+
+impl BookmarksButton {
+    pub const WIDTH: i32 = 28;
+
+    const SUBCLASS_UID: usize = 45;
+    pub extern "system" fn subclass_proc(
+        hwnd: HWND,
+        message: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+        _uid_subclass: usize,
+        ref_data: usize,
+    ) -> LRESULT {
+        let obj = unsafe { (ref_data as *const BookmarksButton).as_ref() };
+        if let (WM_LBUTTONDOWN, Some(obj)) = (message, obj) {
+            if let Err(e) = obj.show_menu() {
+                log::error!("Error showing bookmarks:{:?}", e);
+            }
+            return LRESULT(0);
+        }
+        unsafe { DefSubclassProc(hwnd, message, wparam, lparam) }
+    }
+
+    pub fn new(strip: HWND, tab_bar: Weak<TabBar>) -> Box<Self> {
+        let parent = unsafe { GetParent(strip) };
+        let handle = unsafe {
+            CreateWindowExW(
+                WINDOW_EX_STYLE(0),
+                "BUTTON",
+                "\u{2605}",
+                WS_CHILD | WS_VISIBLE | WINDOW_STYLE(BS_PUSHBUTTON as _),
+                0,
+                0,
+                0,
+                0,
+                parent,
+                HMENU(0),
+                DLL_INSTANCE.unwrap(),
+                std::ptr::null(),
+            )
+        };
+        let new = Box::new(BookmarksButton {
+            handle,
+            strip,
+            tab_bar,
+        });
+        unsafe {
+            let font = SendMessageW(strip, WM_GETFONT, WPARAM(0), LPARAM(0));
+            SendMessageW(handle, WM_SETFONT, WPARAM(font.0 as _), LPARAM(true as _));
+            SetWindowSubclass(
+                handle,
+                Some(Self::subclass_proc),
+                Self::SUBCLASS_UID,
+                &*new as *const _ as usize,
+            )
+        };
+        new
+    }
+
+    // Keeps the button right of the strip, as tall as it
+    pub fn place_beside_strip(&self) {
+        unsafe {
+            let mut rect = RECT::default();
+            if !GetWindowRect(self.strip, addr_of_mut!(rect)).as_bool() {
+                return;
+            }
+            let mut corners = [
+                POINT {
+                    x: rect.left,
+                    y: rect.top,
+                },
+                POINT {
+                    x: rect.right,
+                    y: rect.bottom,
+                },
+            ];
+            MapWindowPoints(HWND(0), GetParent(self.handle), &mut corners);
+            let [top_left, bottom_right] = corners;
+            SetWindowPos(
+                self.handle,
+                HWND(0),
+                bottom_right.x,
+                top_left.y,
+                Self::WIDTH,
+                bottom_right.y - top_left.y,
+                SWP_NOZORDER | SWP_NOACTIVATE,
+            );
+        }
+    }
+
+    // Menu ids are the index of the command, commands past the end of the range are grayed out
+    unsafe fn append_command(
+        menu: HMENU,
+        commands: &mut Vec<BookmarkCommand>,
+        command: BookmarkCommand,
+        label: &str,
+    ) {
+        match BOOKMARK_MENU_IDS.id(commands.len()) {
+            Some(id) => {
+                commands.push(command);
+                AppendMenuW(menu, MF_STRING, id, label)
+            }
+            None => AppendMenuW(menu, MF_GRAYED, 0, label),
+        };
+    }
+
+    // Folders by their path from the root, for the bookmarks to move to
+    fn folder_label(bookmarks: &Bookmarks, folder: &[usize]) -> String {
+        let mut label = "Bookmarks".to_owned();
+        for depth in 1..=folder.len() {
+            let name = bookmarks
+                .get(&folder[..depth])
+                .map_or("", BookmarkNode::name);
+            label = format!("{} / {}", label, name);
+        }
+        label
+    }
+
+    fn create_folder_menu(
+        bookmarks: &Bookmarks,
+        folder: &[usize],
+        commands: &mut Vec<BookmarkCommand>,
+    ) -> Result<HMENU> {
+        let children = bookmarks.children(folder).unwrap_or(&[]);
+        let node_path = |index: usize| [folder, &[index]].concat();
+        let bookmark_indices: Vec<usize> = children
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| matches!(node, BookmarkNode::Bookmark { .. }))
+            .map(|(index, _)| index)
+            .collect();
+
+        let menu = unsafe { CreatePopupMenu()? };
+        unsafe {
+            if children.is_empty() {
+                AppendMenuW(menu, MF_GRAYED, 0, "No Bookmarks");
+            }
+            for (index, node) in children.iter().enumerate() {
+                match node {
+                    BookmarkNode::Bookmark { name, .. } => Self::append_command(
+                        menu,
+                        commands,
+                        BookmarkCommand::Open(node_path(index)),
+                        name.as_str(),
+                    ),
+                    BookmarkNode::Folder { name, .. } => {
+                        let submenu =
+                            Self::create_folder_menu(bookmarks, &node_path(index), commands)?;
+                        AppendMenuW(menu, MF_POPUP, submenu.0 as _, name.as_str());
+                    }
+                }
+            }
+            AppendMenuW(menu, MF_SEPARATOR, 0, PCWSTR::default());
+
+            if !bookmark_indices.is_empty() {
+                Self::append_command(
+                    menu,
+                    commands,
+                    BookmarkCommand::OpenAll(folder.to_vec()),
+                    "Open All In New Tabs",
+                );
+            }
+            Self::append_command(
+                menu,
+                commands,
+                BookmarkCommand::AddCurrentFolder(folder.to_vec()),
+                "Bookmark Current Folder",
+            );
+            Self::append_command(
+                menu,
+                commands,
+                BookmarkCommand::NewFolder(folder.to_vec()),
+                "New Folder...",
+            );
+
+            if !bookmark_indices.is_empty() {
+                let rename_menu = CreatePopupMenu()?;
+                let remove_menu = CreatePopupMenu()?;
+                let move_menu = CreatePopupMenu()?;
+                let destinations: Vec<Vec<usize>> = bookmarks
+                    .all_folders()
+                    .into_iter()
+                    .filter(|destination| destination.as_slice() != folder)
+                    .collect();
+                for index in bookmark_indices {
+                    let name = children[index].name();
+                    Self::append_command(
+                        rename_menu,
+                        commands,
+                        BookmarkCommand::Rename(node_path(index)),
+                        name,
+                    );
+                    Self::append_command(
+                        remove_menu,
+                        commands,
+                        BookmarkCommand::Remove(node_path(index)),
+                        name,
+                    );
+                    let destination_menu = CreatePopupMenu()?;
+                    if destinations.is_empty() {
+                        AppendMenuW(destination_menu, MF_GRAYED, 0, "No Other Folder");
+                    }
+                    for destination in destinations.iter() {
+                        Self::append_command(
+                            destination_menu,
+                            commands,
+                            BookmarkCommand::Move(node_path(index), destination.clone()),
+                            &Self::folder_label(bookmarks, destination),
+                        );
+                    }
+                    AppendMenuW(move_menu, MF_POPUP, destination_menu.0 as _, name);
+                }
+                AppendMenuW(menu, MF_POPUP, rename_menu.0 as _, "Rename Bookmark");
+                AppendMenuW(menu, MF_POPUP, move_menu.0 as _, "Move Bookmark");
+                AppendMenuW(menu, MF_POPUP, remove_menu.0 as _, "Remove Bookmark");
+            }
+            if !folder.is_empty() {
+                Self::append_command(
+                    menu,
+                    commands,
+                    BookmarkCommand::Rename(folder.to_vec()),
+                    "Rename Folder...",
+                );
+                Self::append_command(
+                    menu,
+                    commands,
+                    BookmarkCommand::Remove(folder.to_vec()),
+                    "Delete Folder",
+                );
+            }
+        }
+        Ok(menu)
+    }
+
+    // Bookmarks open in the selected tab, or in a new one with ctrl held
+    fn show_menu(&self) -> Result<()> {
+        let bookmarks = current_bookmarks();
+        let mut commands = Vec::new();
+        let menu = Self::create_folder_menu(&bookmarks, &[], &mut commands)?;
+        let (command, new_tab) = unsafe {
+            let mut rect = RECT::default();
+            GetWindowRect(self.handle, addr_of_mut!(rect)).ok()?;
+            let command = TrackPopupMenu(
+                menu,
+                TPM_RIGHTALIGN | TPM_TOPALIGN | TPM_RETURNCMD,
+                rect.right,
+                rect.bottom,
+                0,
+                self.handle,
+                0 as _,
+            );
+            let new_tab = GetKeyState(VK_CONTROL.0 as _) < 0;
+            DestroyMenu(menu).ok()?;
+            (command.0 as usize, new_tab)
+        };
+
+        let command = BOOKMARK_MENU_IDS
+            .index(command)
+            .and_then(|index| commands.get(index));
+        match command {
+            Some(command) => self.run_command(&bookmarks, command, new_tab),
+            None => Ok(()),
+        }
+    }
+
+    fn run_command(
+        &self,
+        bookmarks: &Bookmarks,
+        command: &BookmarkCommand,
+        new_tab: bool,
+    ) -> Result<()> {
+        let tab_bar = match self.tab_bar.upgrade() {
+            Some(tab_bar) => tab_bar,
+            None => return Ok(()),
+        };
+        match command {
+            BookmarkCommand::Open(path) => {
+                let idl = bookmarks.get(path).and_then(BookmarkNode::get_idl);
+                match new_tab {
+                    true => tab_bar.open_tab(idl),
+                    false => tab_bar.browse_to(idl),
+                }
+            }
+            BookmarkCommand::OpenAll(folder) => {
+                let children = bookmarks.children(folder).unwrap_or(&[]);
+                for idl in children.iter().filter_map(BookmarkNode::get_idl) {
+                    tab_bar.open_tab(Some(idl))?;
+                }
+                Ok(())
+            }
+            BookmarkCommand::AddCurrentFolder(folder) => {
+                let index = tab_bar.get_selected_tab_index().ok_or(E_FAIL)?;
+                tab_bar.bookmark_tab(index, folder)
+            }
+            BookmarkCommand::NewFolder(folder) => {
+                let folder = folder.clone();
+                self.prompt("New Folder", move |name| {
+                    update_bookmarks(|bookmarks| {
+                        let node = BookmarkNode::Folder {
+                            name,
+                            children: Vec::new(),
+                        };
+                        bookmarks.insert(&folder, node)
+                    });
+                })
+            }
+            // The menu showed `bookmarks`, the changes only apply to the nodes it showed
+            BookmarkCommand::Rename(path) => {
+                let expected = bookmarks.get(path).ok_or(E_FAIL)?.clone();
+                let name = expected.name().to_owned();
+                let path = path.clone();
+                self.prompt(&name, move |name| {
+                    update_bookmarks(|bookmarks| bookmarks.rename(&path, &expected, name));
+                })
+            }
+            BookmarkCommand::Remove(path) => {
+                let expected = bookmarks.get(path).ok_or(E_FAIL)?;
+                update_bookmarks(|bookmarks| bookmarks.remove(path, expected));
+                Ok(())
+            }
+            BookmarkCommand::Move(path, folder) => {
+                let expected = bookmarks.get(path).ok_or(E_FAIL)?;
+                update_bookmarks(|bookmarks| bookmarks.move_node(path, expected, folder));
+                Ok(())
+            }
+        }
+    }
+
+    // Asks for a name over the tab strip, empty names are ignored
+    fn prompt(&self, text: &str, on_commit: impl FnOnce(String) + 'static) -> Result<()> {
+        let mut rect = RECT::default();
+        unsafe { GetClientRect(self.strip, addr_of_mut!(rect)).ok()? };
+        let font = unsafe { SendMessageW(self.handle, WM_GETFONT, WPARAM(0), LPARAM(0)) };
+        InlineEdit::show(
+            self.strip,
+            rect,
+            text,
+            HFONT(font.0),
+            Box::new(move |name| {
+                let name = name.trim();
+                if !name.is_empty() {
+                    on_commit(name.to_owned());
+                }
+            }),
+        );
+        Ok(())
+    }
+}
+
+impl Drop for BookmarksButton {
+    fn drop(&mut self) {
+        unsafe {
+            RemoveWindowSubclass(self.handle, Some(Self::subclass_proc), Self::SUBCLASS_UID);
+        }
+    }
+}
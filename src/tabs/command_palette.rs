@@ -0,0 +1,302 @@
+use std::cell::Cell;
+use std::ptr::addr_of_mut;
+use std::rc::{Rc, Weak};
+
+use windows::core::Result;
+use windows::Win32::Foundation::*;
+use windows::Win32::UI::Input::KeyboardAndMouse::{SetFocus, VK_DOWN, VK_ESCAPE, VK_RETURN, VK_UP};
+use windows::Win32::UI::Shell::{DefSubclassProc, RemoveWindowSubclass, SetWindowSubclass};
+use windows::Win32::UI::WindowsAndMessaging::*;
+
+use crate::idl::Idl;
+use crate::palette::{is_typed_path, rank_candidates, Candidate, CandidateSource};
+
+use super::inline_edit::get_window_text;
+use super::tab_bar::{TabBar, TabKey, DLL_INSTANCE};
+
+const WIDTH: i32 = 600;
+const EDIT_HEIGHT: i32 = 26;
+const LIST_HEIGHT: i32 = 320;
+const TOP_MARGIN: i32 = 80;
+const MAX_RESULTS: usize = 50;
+
+#[derive(Clone)]
+pub enum PaletteAction {
+    SwitchTab(TabKey),
+    Open(Idl),
+}
+
+/// Quick switcher over the tabs, bookmarks and history, opened with ctrl+shift+P.
+///
+/// The popup owns itself and goes away when it loses focus.
+pub struct CommandPalette {
+    handle: HWND,
+    edit: HWND,
+    list: HWND,
+    tab_bar: Weak<TabBar>,
+    candidates: Vec<Candidate>,
+    actions: Vec<PaletteAction>,
+    // Candidates in list order, after the typed path if there is one
+    shown: Vec<usize>,
+    typed_path: Option<String>,
+    closing: Cell<bool>,
+}
+
+impl CommandPalette {
+    const SUBCLASS_UID: usize = 46;
+    const EDIT_SUBCLASS_UID: usize = 47;
+
+    pub extern "system" fn subclass_proc(
+        hwnd: HWND,
+        message: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+        _uid_subclass: usize,
+        ref_data: usize,
+    ) -> LRESULT {
+        let obj = ref_data as *mut CommandPalette;
+        match message {
+            WM_COMMAND => {
+                let obj = unsafe { &mut *obj };
+                let notification = ((wparam.0 >> 16) & 0xffff) as u32;
+                if HWND(lparam.0) == obj.edit && notification == EN_CHANGE {
+                    obj.refresh();
+                } else if HWND(lparam.0) == obj.list && notification == LBN_DBLCLK {
+                    obj.accept();
+                }
+                LRESULT(0)
+            }
+            WM_ACTIVATE if (wparam.0 & 0xffff) as u32 == WA_INACTIVE => {
+                unsafe { &*obj }.close();
+                LRESULT(0)
+            }
+            WM_NCDESTROY => unsafe {
+                RemoveWindowSubclass(hwnd, Some(Self::subclass_proc), Self::SUBCLASS_UID);
+                drop(Box::from_raw(obj));
+                DefSubclassProc(hwnd, message, wparam, lparam)
+            },
+            _ => unsafe { DefSubclassProc(hwnd, message, wparam, lparam) },
+        }
+    }
+
+    pub extern "system" fn edit_subclass_proc(
+        hwnd: HWND,
+        message: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+        _uid_subclass: usize,
+        ref_data: usize,
+    ) -> LRESULT {
+        let obj = ref_data as *mut CommandPalette;
+        let key = wparam.0 as u16;
+        match message {
+            WM_GETDLGCODE => LRESULT(DLGC_WANTALLKEYS as _),
+            WM_KEYDOWN if key == VK_UP.0 || key == VK_DOWN.0 => {
+                unsafe { &*obj }.move_selection(if key == VK_UP.0 { -1 } else { 1 });
+                LRESULT(0)
+            }
+            WM_KEYDOWN if key == VK_RETURN.0 => {
+                unsafe { &*obj }.accept();
+                LRESULT(0)
+            }
+            WM_KEYDOWN if key == VK_ESCAPE.0 => {
+                unsafe { &*obj }.close();
+                LRESULT(0)
+            }
+            // Avoid the beep for enter and escape
+            WM_CHAR if key == VK_RETURN.0 || key == VK_ESCAPE.0 => LRESULT(0),
+            WM_NCDESTROY => unsafe {
+                RemoveWindowSubclass(
+                    hwnd,
+                    Some(Self::edit_subclass_proc),
+                    Self::EDIT_SUBCLASS_UID,
+                );
+                DefSubclassProc(hwnd, message, wparam, lparam)
+            },
+            _ => unsafe { DefSubclassProc(hwnd, message, wparam, lparam) },
+        }
+    }
+
+    pub fn open(tab_bar: &Rc<TabBar>) -> Result<()> {
+        let (candidates, actions) = tab_bar.get_palette_candidates();
+        let owner = tab_bar.get_explorer_handle();
+        let font = unsafe { SendMessageW(tab_bar.get_handle(), WM_GETFONT, WPARAM(0), LPARAM(0)) };
+
+        let mut owner_rect = RECT::default();
+        unsafe { GetWindowRect(owner, addr_of_mut!(owner_rect)).ok()? };
+        let x = owner_rect.left + (owner_rect.right - owner_rect.left - WIDTH) / 2;
+        let y = owner_rect.top + TOP_MARGIN;
+
+        let create_child = |class: &str, style: WINDOW_STYLE, y: i32, height: i32, parent: HWND| unsafe {
+            CreateWindowExW(
+                WINDOW_EX_STYLE(0),
+                class,
+                "",
+                WS_CHILD | WS_VISIBLE | WS_BORDER | style,
+                0,
+                y,
+                WIDTH,
+                height,
+                parent,
+                HMENU(0),
+                DLL_INSTANCE.unwrap(),
+                std::ptr::null(),
+            )
+        };
+        let handle = unsafe {
+            CreateWindowExW(
+                WS_EX_TOOLWINDOW,
+                "STATIC",
+                "",
+                WS_POPUP,
+                x,
+                y,
+                WIDTH,
+                EDIT_HEIGHT + LIST_HEIGHT,
+                owner,
+                HMENU(0),
+                DLL_INSTANCE.unwrap(),
+                std::ptr::null(),
+            )
+        };
+        if handle.0 == 0 {
+            return Err(E_FAIL.into());
+        }
+        let edit = create_child(
+            "EDIT",
+            WINDOW_STYLE(ES_AUTOHSCROLL as _),
+            0,
+            EDIT_HEIGHT,
+            handle,
+        );
+        let list = create_child(
+            "LISTBOX",
+            WS_VSCROLL | WINDOW_STYLE(LBS_NOTIFY as _),
+            EDIT_HEIGHT,
+            LIST_HEIGHT,
+            handle,
+        );
+
+        let obj = Box::into_raw(Box::new(CommandPalette {
+            handle,
+            edit,
+            list,
+            tab_bar: Rc::downgrade(tab_bar),
+            candidates,
+            actions,
+            shown: Vec::new(),
+            typed_path: None,
+            closing: Cell::new(false),
+        }));
+        unsafe {
+            for child in [edit, list] {
+                SendMessageW(child, WM_SETFONT, WPARAM(font.0 as _), LPARAM(true as _));
+            }
+            SetWindowSubclass(
+                handle,
+                Some(Self::subclass_proc),
+                Self::SUBCLASS_UID,
+                obj as _,
+            );
+            SetWindowSubclass(
+                edit,
+                Some(Self::edit_subclass_proc),
+                Self::EDIT_SUBCLASS_UID,
+                obj as _,
+            );
+            (*obj).refresh();
+            ShowWindow(handle, SW_SHOW);
+            SetFocus(edit);
+        }
+        Ok(())
+    }
+
+    fn refresh(&mut self) {
+        let query = get_window_text(self.edit);
+        self.shown = rank_candidates(&query, &self.candidates, MAX_RESULTS);
+        self.typed_path = match is_typed_path(&query) {
+            true => Some(query.trim().to_owned()),
+            false => None,
+        };
+
+        let labels = self
+            .typed_path
+            .iter()
+            .map(|path| format!("Open {}", path))
+            .chain(self.shown.iter().map(|index| {
+                let candidate = &self.candidates[*index];
+                let source = match candidate.source {
+                    CandidateSource::Tab => "Tab",
+                    CandidateSource::Bookmark => "Bookmark",
+                    CandidateSource::History => "History",
+                };
+                format!("{}\t{}  ({})", candidate.title, candidate.detail, source)
+            }));
+        unsafe {
+            SendMessageW(self.list, LB_RESETCONTENT, WPARAM(0), LPARAM(0));
+            for label in labels {
+                let label: Vec<u16> = label.encode_utf16().chain(std::iter::once(0)).collect();
+                SendMessageW(
+                    self.list,
+                    LB_ADDSTRING,
+                    WPARAM(0),
+                    LPARAM(label.as_ptr() as _),
+                );
+            }
+            SendMessageW(self.list, LB_SETCURSEL, WPARAM(0), LPARAM(0));
+        }
+    }
+
+    fn move_selection(&self, offset: isize) {
+        unsafe {
+            let count = SendMessageW(self.list, LB_GETCOUNT, WPARAM(0), LPARAM(0)).0;
+            let current = SendMessageW(self.list, LB_GETCURSEL, WPARAM(0), LPARAM(0)).0;
+            if count <= 0 {
+                return;
+            }
+            let selection = (current + offset).clamp(0, count - 1);
+            SendMessageW(self.list, LB_SETCURSEL, WPARAM(selection as _), LPARAM(0));
+        }
+    }
+
+    // The typed path comes first in the list when there is one
+    fn selected_action(&self) -> Option<Result<PaletteAction>> {
+        let selection = unsafe { SendMessageW(self.list, LB_GETCURSEL, WPARAM(0), LPARAM(0)).0 };
+        let mut selection = usize::try_from(selection).ok()?;
+        if let Some(path) = &self.typed_path {
+            if selection == 0 {
+                let idl = Idl::from_display_name(path).ok_or_else(|| E_INVALIDARG.into());
+                return Some(idl.map(PaletteAction::Open));
+            }
+            selection -= 1;
+        }
+        let index = self.shown.get(selection)?;
+        Some(Ok(self.actions[*index].clone()))
+    }
+
+    fn accept(&self) {
+        let action = self.selected_action();
+        let tab_bar = self.tab_bar.clone();
+        // Frees self
+        self.close();
+
+        let tab_bar = match tab_bar.upgrade() {
+            Some(tab_bar) => tab_bar,
+            None => return,
+        };
+        let result = match action {
+            Some(Ok(action)) => tab_bar.run_palette_action(action),
+            Some(Err(e)) => Err(e),
+            None => Ok(()),
+        };
+        if result.is_err() {
+            log::error!("Error running palette action:{:?}", result);
+        }
+    }
+
+    fn close(&self) {
+        if !self.closing.replace(true) {
+            unsafe { DestroyWindow(self.handle) };
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use std::rc::Weak;
+use std::sync::Arc;
 
 use windows::Win32::{
     Foundation::*,
@@ -7,11 +8,17 @@ use windows::Win32::{
             DefSubclassProc, RemoveWindowSubclass, SetWindowSubclass, SBSP_NAVIGATEBACK,
             SBSP_NAVIGATEFORWARD,
         },
-        WindowsAndMessaging::RegisterWindowMessageW,
+        WindowsAndMessaging::{RegisterWindowMessageW, WA_INACTIVE, WM_ACTIVATE},
     },
 };
 
-use crate::{idl::Idl, BROWSE_OBJECT_MESSAGE, SHOW_WINDOW_MESSAGE};
+use crate::ipc::PendingCommand;
+use crate::window_registry::window_activated;
+use crate::workspaces::TabState;
+use crate::{
+    idl::Idl, BROWSE_OBJECT_MESSAGE, IPC_MESSAGE, MERGE_WINDOW_MESSAGE, RECEIVE_TAB_MESSAGE,
+    SHOW_WINDOW_MESSAGE,
+};
 
 use super::tab_bar::TabBar;
 
@@ -21,6 +28,9 @@ pub struct ExplorerSubclass {
 
     show_window_message_id: u32,
     browse_object_message_id: u32,
+    receive_tab_message_id: u32,
+    merge_window_message_id: u32,
+    ipc_message_id: u32,
 }
 
 impl ExplorerSubclass {
@@ -45,6 +55,9 @@ impl ExplorerSubclass {
             tab_bar,
             show_window_message_id: unsafe { RegisterWindowMessageW(SHOW_WINDOW_MESSAGE) },
             browse_object_message_id: unsafe { RegisterWindowMessageW(BROWSE_OBJECT_MESSAGE) },
+            receive_tab_message_id: unsafe { RegisterWindowMessageW(RECEIVE_TAB_MESSAGE) },
+            merge_window_message_id: unsafe { RegisterWindowMessageW(MERGE_WINDOW_MESSAGE) },
+            ipc_message_id: unsafe { RegisterWindowMessageW(IPC_MESSAGE) },
         });
 
         log::info!(
@@ -78,8 +91,7 @@ impl ExplorerSubclass {
                 .tab_bar
                 .upgrade()
                 .unwrap()
-                .new_window(Some(Idl::new(lparam.0 as _)))
-                .is_ok();
+                .route_new_window(Idl::new(lparam.0 as _));
             return LRESULT(block_open as _);
         }
         if message == self.browse_object_message_id {
@@ -97,6 +109,35 @@ impl ExplorerSubclass {
             }
             return LRESULT(0);
         }
+        if message == self.receive_tab_message_id {
+            // Sent by another window of this process, see `send_tab`
+            let state = unsafe { &*(lparam.0 as *const TabState) };
+            let received = self.tab_bar.upgrade().unwrap().receive_tab(state);
+            if let Err(e) = &received {
+                log::error!("Could not receive tab:{:?}", e);
+            }
+            return LRESULT(received.is_ok() as _);
+        }
+        if message == self.merge_window_message_id {
+            let target = HWND(wparam.0 as _);
+            let merged = self.tab_bar.upgrade().unwrap().merge_into(target);
+            if let Err(e) = &merged {
+                log::error!("Could not merge window:{:?}", e);
+            }
+            return LRESULT(merged.is_ok() as _);
+        }
+        if message == self.ipc_message_id {
+            // Sent by the control pipe thread, which may have given up waiting already
+            let pending = unsafe { Arc::from_raw(lparam.0 as *const PendingCommand) };
+            let command = pending.lock().unwrap().take();
+            if let Some(command) = command {
+                command(&self.tab_bar.upgrade().unwrap());
+            }
+            return LRESULT(1);
+        }
+        if message == WM_ACTIVATE && (wparam.0 & 0xffff) as u32 != WA_INACTIVE {
+            window_activated(hwnd);
+        }
         unsafe { DefSubclassProc(hwnd, message, wparam, lparam) }
     }
 }
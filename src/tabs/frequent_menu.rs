@@ -0,0 +1,19 @@
+use windows::core::Result;
+use windows::Win32::UI::WindowsAndMessaging::HMENU;
+
+use crate::frecency::FrecencyEntry;
+
+use super::popup_menu::{create_list_menu, FREQUENT_MENU_IDS};
+
+pub const MAX_FREQUENT_MENU_ENTRIES: usize = FREQUENT_MENU_IDS.count();
+
+/// Lists the most frecent folders, best first.
+pub fn create_frequent_menu(entries: &[FrecencyEntry]) -> Result<HMENU> {
+    let names = entries.iter().map(|entry| entry.name.as_str());
+    create_list_menu(FREQUENT_MENU_IDS, names, "No Frequent Folders")
+}
+
+/// Index of the entry selected in a menu made by `create_frequent_menu`.
+pub fn frequent_menu_index(id: usize) -> Option<usize> {
+    FREQUENT_MENU_IDS.index(id)
+}
@@ -0,0 +1,41 @@
+use chrono::{Local, TimeZone};
+use windows::core::Result;
+use windows::Win32::Foundation::HWND;
+use windows::Win32::UI::WindowsAndMessaging::HMENU;
+
+use crate::global_history::HistoryEntry;
+
+use super::popup_menu::{create_list_menu, track_menu_at_cursor, GLOBAL_HISTORY_MENU_IDS};
+
+pub const MAX_GLOBAL_HISTORY_MENU_ENTRIES: usize = GLOBAL_HISTORY_MENU_IDS.count();
+
+fn format_time(time: i64) -> String {
+    let time = Local.timestamp(time, 0);
+    match time.date() == Local::today() {
+        true => time.format("%H:%M").to_string(),
+        false => time.format("%b %d").to_string(),
+    }
+}
+
+/// Lists history entries with the time they were visited, or a placeholder when empty.
+pub fn create_global_history_menu(entries: &[HistoryEntry], empty_text: &str) -> Result<HMENU> {
+    let labels = entries
+        .iter()
+        .map(|entry| format!("{}\t{}", entry.name, format_time(entry.time)));
+    create_list_menu(GLOBAL_HISTORY_MENU_IDS, labels, empty_text)
+}
+
+/// Index of the entry selected in a menu made by `create_global_history_menu`.
+pub fn global_history_menu_index(id: usize) -> Option<usize> {
+    GLOBAL_HISTORY_MENU_IDS.index(id)
+}
+
+/// Shows the entries at the cursor and returns the one picked.
+pub fn pick_global_history_entry(
+    owner: HWND,
+    entries: &[HistoryEntry],
+) -> Result<Option<HistoryEntry>> {
+    let menu = create_global_history_menu(entries, "No Matching Folders")?;
+    let command = track_menu_at_cursor(menu, owner)?;
+    Ok(global_history_menu_index(command).and_then(|index| entries.get(index).cloned()))
+}
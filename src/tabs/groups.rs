@@ -0,0 +1,362 @@
+use std::cell::RefMut;
+use std::collections::HashMap;
+use std::ops::Range;
+
+use windows::core::Result;
+use windows::Win32::Foundation::E_FAIL;
+
+use crate::tab_color::{TabColor, PRESET_COLORS};
+
+use super::tab_bar::{TabBar, TabIndex, TabKey};
+
+// Group headers are items of the tab strip, keyed like tabs
+#[derive(Clone)]
+pub struct TabGroup {
+    pub name: String,
+    pub color: TabColor,
+    pub collapsed: bool,
+    // Member tabs taken out of the strip while the group is collapsed
+    hidden_tabs: Vec<TabKey>,
+}
+
+impl TabGroup {
+    pub fn title(&self) -> String {
+        match self.collapsed {
+            true => format!("{} ({})", self.name, self.hidden_tabs.len()),
+            false => self.name.clone(),
+        }
+    }
+
+    pub fn hidden_tabs(&self) -> &[TabKey] {
+        &self.hidden_tabs
+    }
+}
+
+/// Groups of a tab bar by the key of their header.
+///
+/// Which group a tab belongs to is kept with the tab, see `Tab::group`.
+#[derive(Default)]
+pub struct TabGroups {
+    groups: HashMap<TabKey, TabGroup>,
+    // Groups ever added, removing one doesn't give its name and color to the next
+    added: usize,
+}
+
+impl TabGroups {
+    pub fn contains(&self, key: TabKey) -> bool {
+        self.groups.contains_key(&key)
+    }
+
+    pub fn get(&self, key: TabKey) -> Option<&TabGroup> {
+        self.groups.get(&key)
+    }
+
+    pub fn get_mut(&mut self, key: TabKey) -> Option<&mut TabGroup> {
+        self.groups.get_mut(&key)
+    }
+
+    // Numbered and colored in the order groups are added
+    pub fn add(&mut self, key: TabKey) -> &mut TabGroup {
+        let number = self.added;
+        let added = &mut self.added;
+        self.groups.entry(key).or_insert_with(|| {
+            *added += 1;
+            TabGroup {
+                name: format!("Group {}", number + 1),
+                color: PRESET_COLORS[number % PRESET_COLORS.len()].1,
+                collapsed: false,
+                hidden_tabs: Vec::new(),
+            }
+        })
+    }
+
+    pub fn remove(&mut self, key: TabKey) -> Option<TabGroup> {
+        self.groups.remove(&key)
+    }
+
+    /// Groups none of the tabs belong to, given the group of every tab.
+    pub fn empty_groups(&self, memberships: impl Iterator<Item = Option<TabKey>>) -> Vec<TabKey> {
+        let memberships: Vec<TabKey> = memberships.flatten().collect();
+        let mut empty: Vec<TabKey> = self
+            .groups
+            .keys()
+            .filter(|key| !memberships.contains(key))
+            .copied()
+            .collect();
+        empty.sort_unstable();
+        empty
+    }
+}
+
+impl TabBar {
+    pub(super) fn get_group_key(&self, index: TabIndex) -> Option<TabKey> {
+        let key = self.tab_control().get_tab_key(index).ok()?;
+        match self.0.borrow().groups.contains(key) {
+            true => Some(key),
+            false => None,
+        }
+    }
+
+    fn get_group_mut(&self, key: TabKey) -> Option<RefMut<TabGroup>> {
+        if self.0.borrow().groups.contains(key) {
+            Some(RefMut::map(self.0.borrow_mut(), |tab_bar| {
+                tab_bar.groups.get_mut(key).unwrap()
+            }))
+        } else {
+            None
+        }
+    }
+
+    pub fn is_group_header(&self, index: TabIndex) -> bool {
+        self.get_group_key(index).is_some()
+    }
+
+    pub fn is_grouped(&self, index: TabIndex) -> bool {
+        self.get_tab(index).map_or(false, |tab| tab.group.is_some())
+    }
+
+    pub fn get_group(&self, index: TabIndex) -> Option<TabGroup> {
+        let key = self.get_group_key(index)?;
+        self.0.borrow().groups.get(key).cloned()
+    }
+
+    // Group of a header, or group a tab belongs to
+    pub(super) fn get_item_group(&self, index: TabIndex) -> Option<TabKey> {
+        self.get_group_key(index)
+            .or_else(|| self.get_tab(index)?.group)
+    }
+
+    pub(super) fn get_group_block(&self, group_key: TabKey) -> Option<Range<TabIndex>> {
+        let start = self.get_tab_index(group_key)?;
+        let mut end = start + 1;
+        while self
+            .get_tab(end)
+            .map_or(false, |tab| tab.group == Some(group_key))
+        {
+            end += 1;
+        }
+        Some(start..end)
+    }
+
+    pub(super) fn remove_empty_groups(&self) -> Result<()> {
+        let empty_groups = {
+            let obj = self.0.borrow();
+            obj.groups
+                .empty_groups(obj.tabs.values().map(|tab| tab.group))
+        };
+
+        let tab_control = self.tab_control();
+        for group_key in empty_groups {
+            self.0.borrow_mut().groups.remove(group_key);
+            if let Some(index) = tab_control.get_tab_index(group_key) {
+                tab_control.remove_tab(index)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn create_group(&self, indices: &[TabIndex]) -> Result<()> {
+        let tab_control = self.tab_control();
+        let mut indices: Vec<TabIndex> = indices
+            .iter()
+            .copied()
+            .filter(|&index| self.get_tab(index).is_some())
+            .collect();
+        indices.sort_unstable();
+        let keys = indices
+            .iter()
+            .map(|&index| tab_control.get_tab_key(index))
+            .collect::<Result<Vec<TabKey>>>()?;
+
+        // Start the new group before the group the first tab is in, if any
+        let first_index = *indices.first().ok_or(E_FAIL)?;
+        let insert_index = self
+            .get_item_group(first_index)
+            .and_then(|group_key| self.get_tab_index(group_key))
+            .unwrap_or(first_index);
+
+        let (group_key, title) = {
+            let obj = &mut *self.0.borrow_mut();
+            let group_key = obj.tab_key_counter;
+            obj.tab_key_counter += 1;
+            (group_key, obj.groups.add(group_key).title())
+        };
+        tab_control.add_tab(title, insert_index, group_key)?;
+
+        for (offset, key) in keys.iter().enumerate() {
+            let index = tab_control.get_tab_index(*key).ok_or(E_FAIL)?;
+            tab_control.move_tab(index, insert_index + 1 + offset)?;
+            if let Some(tab) = self.0.borrow_mut().tabs.get_mut(key) {
+                tab.group = Some(group_key);
+                tab.marked = false;
+            }
+        }
+
+        self.remove_empty_groups()?;
+        self.refresh_tab_titles()?;
+        tab_control.redraw();
+        Ok(())
+    }
+
+    pub fn ungroup(&self, index: TabIndex) -> Result<()> {
+        let group_key = self.get_group_key(index).ok_or(E_FAIL)?;
+        self.set_group_collapsed(group_key, false)?;
+        {
+            let obj = &mut *self.0.borrow_mut();
+            obj.groups.remove(group_key);
+            obj.tabs
+                .values_mut()
+                .filter(|tab| tab.group == Some(group_key))
+                .for_each(|tab| tab.group = None);
+        }
+
+        let tab_control = self.tab_control();
+        tab_control.remove_tab(tab_control.get_tab_index(group_key).ok_or(E_FAIL)?)?;
+        tab_control.redraw();
+        Ok(())
+    }
+
+    pub fn remove_from_group(&self, index: TabIndex) -> Result<()> {
+        let group_key = self.get_tab(index).ok_or(E_FAIL)?.group.ok_or(E_FAIL)?;
+        let block = self.get_group_block(group_key).ok_or(E_FAIL)?;
+        self.tab_control().move_tab(index, block.end - 1)?;
+        self.get_tab(block.end - 1).ok_or(E_FAIL)?.group = None;
+        self.remove_empty_groups()?;
+        self.tab_control().redraw();
+        Ok(())
+    }
+
+    pub fn toggle_group_collapsed(&self, index: TabIndex) -> Result<()> {
+        let group = self.get_group(index).ok_or(E_FAIL)?;
+        let group_key = self.get_group_key(index).ok_or(E_FAIL)?;
+        self.set_group_collapsed(group_key, !group.collapsed)
+    }
+
+    fn set_group_collapsed(&self, group_key: TabKey, collapsed: bool) -> Result<()> {
+        if self.get_group_mut(group_key).ok_or(E_FAIL)?.collapsed == collapsed {
+            return Ok(());
+        }
+
+        let tab_control = self.tab_control();
+        let block = self.get_group_block(group_key).ok_or(E_FAIL)?;
+        let header_index = block.start;
+        if collapsed {
+            let selected_index = tab_control.get_selected_tab_index();
+            if selected_index.map_or(false, |index| block.contains(&index)) {
+                let next_index = self.find_nearest_tab(block.clone()).ok_or(E_FAIL)?;
+                self.switch_tab(next_index)?;
+            }
+
+            let mut hidden_tabs = Vec::new();
+            for _ in block.start + 1..block.end {
+                hidden_tabs.push(tab_control.get_tab_key(header_index + 1)?);
+                tab_control.remove_tab(header_index + 1)?;
+            }
+            let obj = &mut *self.0.borrow_mut();
+            for key in hidden_tabs.iter() {
+                if let Some(tab) = obj.tabs.get_mut(key) {
+                    tab.marked = false;
+                }
+            }
+            let group = obj.groups.get_mut(group_key).ok_or(E_FAIL)?;
+            group.hidden_tabs = hidden_tabs;
+            group.collapsed = true;
+        } else {
+            let hidden_tabs = {
+                let mut group = self.get_group_mut(group_key).ok_or(E_FAIL)?;
+                group.collapsed = false;
+                std::mem::take(&mut group.hidden_tabs)
+            };
+            for (offset, key) in hidden_tabs.into_iter().enumerate() {
+                tab_control.add_tab(String::new(), header_index + 1 + offset, key)?;
+            }
+        }
+        self.refresh_tab_titles()?;
+        tab_control.redraw();
+        Ok(())
+    }
+
+    // Puts the tabs of collapsed groups back in the strip
+    pub(super) fn expand_groups(&self) -> Result<()> {
+        let tab_control = self.tab_control();
+        let group_keys: Vec<TabKey> = (0..tab_control.get_tab_count())
+            .filter_map(|index| self.get_group_key(index))
+            .collect();
+        for group_key in group_keys {
+            self.set_group_collapsed(group_key, false)?;
+        }
+        Ok(())
+    }
+
+    pub fn set_group_color(&self, index: TabIndex, color: TabColor) -> Result<()> {
+        let group_key = self.get_group_key(index).ok_or(E_FAIL)?;
+        self.get_group_mut(group_key).ok_or(E_FAIL)?.color = color;
+        self.tab_control().redraw();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_groups_are_numbered_and_colored_in_turn() {
+        let mut groups = TabGroups::default();
+        for key in 0..PRESET_COLORS.len() + 1 {
+            groups.add(key);
+        }
+        let first = groups.get(0).unwrap();
+        assert_eq!(first.name, "Group 1");
+        assert_eq!(first.color, PRESET_COLORS[0].1);
+        assert_eq!(groups.get(1).unwrap().color, PRESET_COLORS[1].1);
+        let last = groups.get(PRESET_COLORS.len()).unwrap();
+        assert_eq!(last.name, format!("Group {}", PRESET_COLORS.len() + 1));
+        assert_eq!(last.color, PRESET_COLORS[0].1);
+    }
+
+    #[test]
+    fn removed_groups_do_not_give_away_their_name() {
+        let mut groups = TabGroups::default();
+        groups.add(1);
+        groups.add(2);
+        groups.remove(1);
+        let third = groups.add(3);
+        assert_eq!(third.name, "Group 3");
+        assert_eq!(third.color, PRESET_COLORS[2].1);
+        assert_eq!(groups.get(2).unwrap().name, "Group 2");
+    }
+
+    #[test]
+    fn adding_an_existing_group_keeps_it() {
+        let mut groups = TabGroups::default();
+        groups.add(4).name = "Photos".to_owned();
+        groups.add(4);
+        assert_eq!(groups.get(4).unwrap().name, "Photos");
+        assert_eq!(groups.add(5).name, "Group 2");
+    }
+
+    #[test]
+    fn collapsed_title_counts_hidden_tabs() {
+        let mut groups = TabGroups::default();
+        groups.add(1);
+        let group = groups.get_mut(1).unwrap();
+        assert_eq!(group.title(), "Group 1");
+        group.collapsed = true;
+        group.hidden_tabs = vec![2, 3];
+        assert_eq!(group.title(), "Group 1 (2)");
+    }
+
+    #[test]
+    fn finds_groups_without_tabs() {
+        let mut groups = TabGroups::default();
+        groups.add(10);
+        groups.add(11);
+        groups.add(12);
+        let memberships = vec![None, Some(11), None, Some(11)];
+        assert_eq!(groups.empty_groups(memberships.into_iter()), vec![10, 12]);
+        assert!(groups
+            .empty_groups(vec![Some(10), Some(11), Some(12)].into_iter())
+            .is_empty());
+    }
+}
@@ -0,0 +1,264 @@
+use std::collections::VecDeque;
+
+#[derive(Clone, Copy, Debug)]
+pub struct HistoryOptions {
+    // Entries kept on each side of the current one, 0 means no limit
+    pub max_depth: usize,
+    // Skip repeated entries and turn A -> B -> A into a step back
+    pub collapse_duplicates: bool,
+}
+
+/// Navigation history: the current entry with the entries behind and ahead of it.
+#[derive(Clone, Debug)]
+pub struct History<T> {
+    current: T,
+    // Oldest entries first
+    backward: VecDeque<T>,
+    // Furthest entries first
+    forward: Vec<T>,
+    options: HistoryOptions,
+}
+
+impl<T: Clone + PartialEq> History<T> {
+    pub fn new(current: T, options: HistoryOptions) -> Self {
+        Self {
+            current,
+            backward: VecDeque::new(),
+            forward: Vec::new(),
+            options,
+        }
+    }
+
+    /// Rebuilds a history from its entries, both sides nearest first.
+    pub fn from_entries(
+        current: T,
+        backward: Vec<T>,
+        forward: Vec<T>,
+        options: HistoryOptions,
+    ) -> Self {
+        let mut history = Self {
+            current,
+            backward: backward.into_iter().rev().collect(),
+            forward: forward.into_iter().rev().collect(),
+            options,
+        };
+        history.truncate();
+        history
+    }
+
+    pub fn current(&self) -> &T {
+        &self.current
+    }
+
+    /// Entries behind the current one, nearest first.
+    pub fn backward_entries(&self) -> impl Iterator<Item = &T> {
+        self.backward.iter().rev()
+    }
+
+    /// Entries ahead of the current one, nearest first.
+    pub fn forward_entries(&self) -> impl Iterator<Item = &T> {
+        self.forward.iter().rev()
+    }
+
+    pub fn can_go_back(&self) -> bool {
+        !self.backward.is_empty()
+    }
+
+    pub fn can_go_forward(&self) -> bool {
+        !self.forward.is_empty()
+    }
+
+    /// Changes the current entry without recording the previous one.
+    pub fn replace_current(&mut self, entry: T) {
+        self.current = entry;
+    }
+
+    /// Records a navigation to a new entry, the entries ahead are dropped.
+    pub fn push(&mut self, entry: T) {
+        if entry == self.current {
+            return;
+        }
+        if self.options.collapse_duplicates && self.backward.back() == Some(&entry) {
+            self.go(-1);
+            return;
+        }
+
+        self.forward.clear();
+        let previous = std::mem::replace(&mut self.current, entry);
+        self.backward.push_back(previous);
+        self.truncate();
+    }
+
+    /// Moves `offset` entries through the history, negative offsets go back.
+    ///
+    /// Returns the new current entry, or `None` without moving if there aren't enough entries.
+    pub fn go(&mut self, offset: isize) -> Option<&T> {
+        let steps = offset.unsigned_abs();
+        let available = match offset < 0 {
+            true => self.backward.len(),
+            false => self.forward.len(),
+        };
+        if steps == 0 || steps > available {
+            return None;
+        }
+
+        for _ in 0..steps {
+            if offset < 0 {
+                let entry = self.backward.pop_back()?;
+                self.forward
+                    .push(std::mem::replace(&mut self.current, entry));
+            } else {
+                let entry = self.forward.pop()?;
+                self.backward
+                    .push_back(std::mem::replace(&mut self.current, entry));
+            }
+        }
+        self.truncate();
+        Some(&self.current)
+    }
+
+    fn truncate(&mut self) {
+        let max_depth = self.options.max_depth;
+        if max_depth == 0 {
+            return;
+        }
+        while self.backward.len() > max_depth {
+            self.backward.pop_front();
+        }
+        if self.forward.len() > max_depth {
+            self.forward.drain(..self.forward.len() - max_depth);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNLIMITED: HistoryOptions = HistoryOptions {
+        max_depth: 0,
+        collapse_duplicates: false,
+    };
+
+    fn history(entries: &[&'static str], options: HistoryOptions) -> History<&'static str> {
+        let mut history = History::new(entries[0], options);
+        for entry in &entries[1..] {
+            history.push(entry);
+        }
+        history
+    }
+
+    fn backward(history: &History<&'static str>) -> Vec<&'static str> {
+        history.backward_entries().copied().collect()
+    }
+
+    fn forward(history: &History<&'static str>) -> Vec<&'static str> {
+        history.forward_entries().copied().collect()
+    }
+
+    #[test]
+    fn push_moves_current_behind() {
+        let history = history(&["a", "b", "c"], UNLIMITED);
+        assert_eq!(*history.current(), "c");
+        assert_eq!(backward(&history), vec!["b", "a"]);
+        assert!(history.can_go_back());
+        assert!(!history.can_go_forward());
+    }
+
+    #[test]
+    fn pushing_the_current_entry_does_nothing() {
+        let history = history(&["a", "b", "b"], UNLIMITED);
+        assert_eq!(backward(&history), vec!["a"]);
+    }
+
+    #[test]
+    fn back_and_forward() {
+        let mut history = history(&["a", "b", "c"], UNLIMITED);
+        assert_eq!(history.go(-1), Some(&"b"));
+        assert_eq!(backward(&history), vec!["a"]);
+        assert_eq!(forward(&history), vec!["c"]);
+        assert_eq!(history.go(1), Some(&"c"));
+        assert_eq!(backward(&history), vec!["b", "a"]);
+        assert!(forward(&history).is_empty());
+    }
+
+    #[test]
+    fn go_several_steps() {
+        let mut history = history(&["a", "b", "c", "d"], UNLIMITED);
+        assert_eq!(history.go(-3), Some(&"a"));
+        assert_eq!(forward(&history), vec!["b", "c", "d"]);
+        assert_eq!(history.go(2), Some(&"c"));
+        assert_eq!(backward(&history), vec!["b", "a"]);
+        assert_eq!(forward(&history), vec!["d"]);
+    }
+
+    #[test]
+    fn go_out_of_range_stays_put() {
+        let mut history = history(&["a", "b"], UNLIMITED);
+        assert_eq!(history.go(-2), None);
+        assert_eq!(history.go(1), None);
+        assert_eq!(history.go(0), None);
+        assert_eq!(*history.current(), "b");
+        assert_eq!(backward(&history), vec!["a"]);
+    }
+
+    #[test]
+    fn push_clears_forward() {
+        let mut history = history(&["a", "b", "c"], UNLIMITED);
+        history.go(-2);
+        history.push("d");
+        assert_eq!(*history.current(), "d");
+        assert_eq!(backward(&history), vec!["a"]);
+        assert!(!history.can_go_forward());
+    }
+
+    #[test]
+    fn depth_is_capped_on_both_sides() {
+        let options = HistoryOptions {
+            max_depth: 2,
+            ..UNLIMITED
+        };
+        let mut history = history(&["a", "b", "c", "d", "e"], options);
+        assert_eq!(backward(&history), vec!["d", "c"]);
+        history.go(-2);
+        history.push("x");
+        history.push("y");
+        history.push("z");
+        assert_eq!(backward(&history), vec!["y", "x"]);
+
+        let history = History::from_entries("c", vec!["b", "a", "0"], vec!["d", "e", "f"], options);
+        assert_eq!(backward(&history), vec!["b", "a"]);
+        assert_eq!(forward(&history), vec!["d", "e"]);
+    }
+
+    #[test]
+    fn from_entries_keeps_the_order() {
+        let mut history = History::from_entries("b", vec!["a"], vec!["c", "d"], UNLIMITED);
+        assert_eq!(history.go(2), Some(&"d"));
+        assert_eq!(backward(&history), vec!["c", "b", "a"]);
+    }
+
+    #[test]
+    fn collapsed_duplicates_step_back() {
+        let options = HistoryOptions {
+            collapse_duplicates: true,
+            ..UNLIMITED
+        };
+        let mut history = history(&["a", "b", "a"], options);
+        assert_eq!(*history.current(), "a");
+        assert!(backward(&history).is_empty());
+        assert_eq!(forward(&history), vec!["b"]);
+
+        // Only the entry just behind collapses
+        history.push("c");
+        history.push("d");
+        history.push("a");
+        assert_eq!(backward(&history), vec!["d", "c", "a"]);
+    }
+
+    #[test]
+    fn duplicates_are_kept_without_collapsing() {
+        let history = history(&["a", "b", "a"], UNLIMITED);
+        assert_eq!(backward(&history), vec!["b", "a"]);
+    }
+}
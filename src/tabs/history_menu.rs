@@ -0,0 +1,69 @@
+use windows::core::Result;
+use windows::Win32::UI::WindowsAndMessaging::*;
+
+use super::popup_menu::HISTORY_MENU_IDS;
+use super::tab_bar::{TabBar, TabIndex};
+
+// Entries on each side of the current folder, which is in the middle of the id range
+const MAX_HISTORY_MENU_ENTRIES: usize = HISTORY_MENU_IDS.count() / 2;
+
+fn history_menu_id(offset: isize) -> usize {
+    HISTORY_MENU_IDS
+        .id((MAX_HISTORY_MENU_ENTRIES as isize + offset) as usize)
+        .unwrap_or_default()
+}
+
+pub struct TabHistory {
+    // Nearest entries first
+    pub backward: Vec<String>,
+    pub current: String,
+    pub forward: Vec<String>,
+}
+
+/// Lists the history of a tab, furthest forward entry at the top.
+pub fn create_history_menu(tab_bar: &TabBar, index: TabIndex) -> Result<HMENU> {
+    let history = tab_bar.get_tab_history(index).unwrap_or(TabHistory {
+        backward: Vec::new(),
+        current: String::new(),
+        forward: Vec::new(),
+    });
+
+    let menu = unsafe { CreatePopupMenu()? };
+    let forward = history
+        .forward
+        .iter()
+        .enumerate()
+        .take(MAX_HISTORY_MENU_ENTRIES)
+        .rev();
+    for (offset, name) in forward {
+        let id = history_menu_id(offset as isize + 1);
+        unsafe { AppendMenuW(menu, MF_STRING, id, name.as_str()) };
+    }
+    unsafe {
+        AppendMenuW(
+            menu,
+            MF_CHECKED | MF_GRAYED,
+            history_menu_id(0),
+            history.current.as_str(),
+        )
+    };
+    let backward = history
+        .backward
+        .iter()
+        .enumerate()
+        .take(MAX_HISTORY_MENU_ENTRIES);
+    for (offset, name) in backward {
+        let id = history_menu_id(-(offset as isize) - 1);
+        unsafe { AppendMenuW(menu, MF_STRING, id, name.as_str()) };
+    }
+    Ok(menu)
+}
+
+/// History offset selected in a menu made by `create_history_menu`, negative when going back.
+pub fn history_menu_offset(id: usize) -> Option<isize> {
+    let offset = HISTORY_MENU_IDS.index(id)? as isize - MAX_HISTORY_MENU_ENTRIES as isize;
+    match offset {
+        0 => None,
+        offset => Some(offset),
+    }
+}
@@ -0,0 +1,125 @@
+use std::cell::Cell;
+
+use windows::Win32::Foundation::*;
+use windows::Win32::Graphics::Gdi::HFONT;
+use windows::Win32::UI::Controls::EM_SETSEL;
+use windows::Win32::UI::Input::KeyboardAndMouse::{SetFocus, VK_ESCAPE, VK_RETURN};
+use windows::Win32::UI::Shell::{DefSubclassProc, RemoveWindowSubclass, SetWindowSubclass};
+use windows::Win32::UI::WindowsAndMessaging::*;
+
+use super::tab_bar::DLL_INSTANCE;
+
+pub type InlineEditCallback = Box<dyn FnOnce(String)>;
+
+// Posted to the edit to end it, wparam is 1 to commit. Focus is often lost in the middle of
+// something else, like a menu or another window being created, the callback runs after that.
+const WM_END_INLINE_EDIT: u32 = WM_APP + 1;
+
+/// Single line edit control shown on top of another window.
+///
+/// The control owns itself until the edit is committed with enter or by losing focus,
+/// or cancelled with escape. The callback only runs on commit.
+pub struct InlineEdit {
+    on_commit: Option<InlineEditCallback>,
+    // The end message was posted, later keys and focus changes are ignored
+    ending: Cell<bool>,
+}
+
+impl InlineEdit {
+    const SUBCLASS_UID: usize = 44;
+    pub extern "system" fn subclass_proc(
+        hwnd: HWND,
+        message: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+        _uid_subclass: usize,
+        ref_data: usize,
+    ) -> LRESULT {
+        let end = match message {
+            WM_GETDLGCODE => return LRESULT(DLGC_WANTALLKEYS as _),
+            WM_KEYDOWN if wparam.0 == VK_RETURN.0 as usize => Some(true),
+            WM_KEYDOWN if wparam.0 == VK_ESCAPE.0 as usize => Some(false),
+            WM_CHAR if wparam.0 == VK_RETURN.0 as usize || wparam.0 == VK_ESCAPE.0 as usize => {
+                // Avoid the beep for enter and escape
+                return LRESULT(0);
+            }
+            WM_KILLFOCUS => Some(true),
+            WM_END_INLINE_EDIT | WM_NCDESTROY => None,
+            _ => return unsafe { DefSubclassProc(hwnd, message, wparam, lparam) },
+        };
+        if let Some(commit) = end {
+            let obj = unsafe { &*(ref_data as *const InlineEdit) };
+            if !obj.ending.replace(true) {
+                unsafe { PostMessageW(hwnd, WM_END_INLINE_EDIT, WPARAM(commit as _), LPARAM(0)) };
+            }
+            return match message {
+                WM_KILLFOCUS => unsafe { DefSubclassProc(hwnd, message, wparam, lparam) },
+                _ => LRESULT(0),
+            };
+        }
+
+        let commit = message == WM_END_INLINE_EDIT && wparam.0 == 1;
+
+        let obj = unsafe { Box::from_raw(ref_data as *mut InlineEdit) };
+        let text = get_window_text(hwnd);
+        unsafe {
+            RemoveWindowSubclass(hwnd, Some(Self::subclass_proc), Self::SUBCLASS_UID);
+            if message != WM_NCDESTROY {
+                DestroyWindow(hwnd);
+            }
+        }
+
+        if commit {
+            if let Some(on_commit) = obj.on_commit {
+                on_commit(text);
+            }
+        }
+        LRESULT(0)
+    }
+
+    pub fn show(parent: HWND, rect: RECT, text: &str, font: HFONT, on_commit: InlineEditCallback) {
+        let handle = unsafe {
+            CreateWindowExW(
+                WINDOW_EX_STYLE(0),
+                "EDIT",
+                text,
+                WS_CHILD | WS_VISIBLE | WS_BORDER | WINDOW_STYLE(ES_AUTOHSCROLL as _),
+                rect.left,
+                rect.top,
+                rect.right - rect.left,
+                rect.bottom - rect.top,
+                parent,
+                HMENU(0),
+                DLL_INSTANCE.unwrap(),
+                std::ptr::null(),
+            )
+        };
+        if handle.0 == 0 {
+            log::error!("failed to create inline edit");
+            return;
+        }
+
+        let obj = Box::new(InlineEdit {
+            on_commit: Some(on_commit),
+            ending: Cell::new(false),
+        });
+        unsafe {
+            SendMessageW(handle, WM_SETFONT, WPARAM(font.0 as _), LPARAM(true as _));
+            SetWindowSubclass(
+                handle,
+                Some(Self::subclass_proc),
+                Self::SUBCLASS_UID,
+                Box::into_raw(obj) as usize,
+            )
+            .expect("failed to install subclass");
+            SendMessageW(handle, EM_SETSEL, WPARAM(0), LPARAM(-1));
+            SetFocus(handle);
+        }
+    }
+}
+
+pub fn get_window_text(handle: HWND) -> String {
+    let mut text = [0u16; 512];
+    let size = unsafe { GetWindowTextW(handle, &mut text) } as usize;
+    String::from_utf16_lossy(&text[..size])
+}
@@ -0,0 +1,149 @@
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+use windows::Win32::Foundation::*;
+use windows::Win32::System::Threading::GetCurrentThreadId;
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    GetKeyState, VK_CONTROL, VK_ESCAPE, VK_MENU, VK_SHIFT, VK_TAB,
+};
+use windows::Win32::UI::WindowsAndMessaging::*;
+
+use super::command_palette::CommandPalette;
+use super::tab_bar::TabBar;
+use super::tab_switcher::TabSwitcher;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl Modifiers {
+    fn current() -> Self {
+        let pressed = |key: u16| unsafe { GetKeyState(key as _) } < 0;
+        Modifiers {
+            ctrl: pressed(VK_CONTROL.0),
+            shift: pressed(VK_SHIFT.0),
+            alt: pressed(VK_MENU.0),
+        }
+    }
+}
+
+const PALETTE_MODIFIERS: Modifiers = Modifiers {
+    ctrl: true,
+    shift: true,
+    alt: false,
+};
+const PALETTE_KEY: u16 = b'P' as u16;
+
+thread_local! {
+    // Tab bar of the explorer window running on this thread
+    static HOOKED_TAB_BAR: RefCell<Weak<TabBar>> = RefCell::new(Weak::new());
+    // Open while ctrl is held after ctrl+tab
+    static TAB_SWITCHER: RefCell<Option<Box<TabSwitcher>>> = RefCell::new(None);
+}
+
+/// Shortcuts of the tab bar, seen before the focused control of the window gets them.
+pub struct KeyboardHook {
+    hook: HHOOK,
+}
+
+impl KeyboardHook {
+    extern "system" fn hook_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+        // Bit 31 is set when the key is released, bit 30 when it was already down
+        let key_down = lparam.0 & (1 << 31) == 0;
+        let repeat = lparam.0 & (1 << 30) != 0;
+        if code == HC_ACTION as i32 {
+            let tab_bar = HOOKED_TAB_BAR.with(|tab_bar| tab_bar.borrow().upgrade());
+            if let Some(tab_bar) = tab_bar {
+                let key = wparam.0 as u16;
+                let used = match (key_down, repeat) {
+                    (true, false) => handle_key(&tab_bar, key, Modifiers::current()),
+                    // Holding tab down does not cycle the switcher, nor reaches the window
+                    (true, true) => key == VK_TAB.0 && is_switcher_open(),
+                    (false, _) => handle_key_up(&tab_bar, key),
+                };
+                if used {
+                    return LRESULT(1);
+                }
+            }
+        }
+        unsafe { CallNextHookEx(HHOOK(0), code, wparam, lparam) }
+    }
+
+    pub fn new(tab_bar: Weak<TabBar>) -> Box<Self> {
+        HOOKED_TAB_BAR.with(|hooked| *hooked.borrow_mut() = tab_bar);
+        let hook = unsafe {
+            SetWindowsHookExW(
+                WH_KEYBOARD,
+                Some(Self::hook_proc),
+                HINSTANCE(0),
+                GetCurrentThreadId(),
+            )
+        };
+        if hook.is_err() {
+            log::error!("Could not install keyboard hook:{:?}", hook);
+        }
+        Box::new(KeyboardHook {
+            hook: hook.unwrap_or_default(),
+        })
+    }
+}
+
+fn is_switcher_open() -> bool {
+    TAB_SWITCHER.with(|switcher| switcher.borrow().is_some())
+}
+
+// Returns true when the key was used, a shortcut that opened nothing goes on to the window
+fn handle_key(tab_bar: &Rc<TabBar>, key: u16, modifiers: Modifiers) -> bool {
+    if key == VK_TAB.0 && modifiers.ctrl && !modifiers.alt {
+        return TAB_SWITCHER.with(|switcher| {
+            let mut switcher = switcher.borrow_mut();
+            match &mut *switcher {
+                Some(switcher) => switcher.move_selection(modifiers.shift),
+                // Fails with fewer than two tabs
+                switcher => *switcher = TabSwitcher::open(tab_bar, modifiers.shift).ok(),
+            }
+            switcher.is_some()
+        });
+    }
+    if key == VK_ESCAPE.0 && is_switcher_open() {
+        TAB_SWITCHER.with(|switcher| switcher.borrow_mut().take());
+        return true;
+    }
+    if key == PALETTE_KEY && modifiers == PALETTE_MODIFIERS {
+        return match CommandPalette::open(tab_bar) {
+            Ok(_) => true,
+            Err(e) => {
+                log::error!("Error opening command palette:{:?}", e);
+                false
+            }
+        };
+    }
+    false
+}
+
+// Releasing ctrl picks the tab selected in the switcher
+fn handle_key_up(tab_bar: &Rc<TabBar>, key: u16) -> bool {
+    if key != VK_CONTROL.0 {
+        return false;
+    }
+    let key = TAB_SWITCHER.with(|switcher| switcher.borrow_mut().take().map(|s| s.selected_key()));
+    if let Some(key) = key {
+        if let Err(e) = tab_bar.switch_to_key(key) {
+            log::error!("Error switching to recent tab:{:?}", e);
+        }
+    }
+    false
+}
+
+impl Drop for KeyboardHook {
+    fn drop(&mut self) {
+        HOOKED_TAB_BAR.with(|hooked| *hooked.borrow_mut() = Weak::new());
+        TAB_SWITCHER.with(|switcher| switcher.borrow_mut().take());
+        if self.hook.0 != 0 {
+            unsafe { UnhookWindowsHookEx(self.hook) };
+        }
+    }
+}
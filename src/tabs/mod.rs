@@ -1,4 +1,21 @@
+mod bookmarks_button;
+mod command_palette;
 mod explorer_subclass;
+mod frequent_menu;
+mod global_history_menu;
+mod groups;
+mod history;
+mod history_menu;
+mod inline_edit;
+mod keyboard_hook;
+mod mru;
+mod popup_menu;
 pub mod tab_bar;
 mod tab_control;
+mod tab_switcher;
+mod tab_title;
+mod tab_transfer;
 mod travel_bar_control;
+mod view_subclass;
+mod window_menu;
+mod workspace_menu;
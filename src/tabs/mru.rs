@@ -0,0 +1,90 @@
+use super::tab_bar::TabKey;
+
+/// Order in which tabs were last activated, most recent first.
+///
+/// Tabs are tracked by key, so moving them around the strip doesn't change the order.
+#[derive(Default, Clone, Debug)]
+pub struct MruOrder {
+    keys: Vec<TabKey>,
+}
+
+impl MruOrder {
+    pub fn keys(&self) -> &[TabKey] {
+        &self.keys
+    }
+
+    pub fn activated(&mut self, key: TabKey) {
+        self.removed(key);
+        self.keys.insert(0, key);
+    }
+
+    // New tabs come right after the tab they were created from, or last
+    pub fn added(&mut self, key: TabKey, after: Option<TabKey>) {
+        self.removed(key);
+        let index = after
+            .and_then(|after| self.keys.iter().position(|k| *k == after))
+            .map_or(self.keys.len(), |index| index + 1);
+        self.keys.insert(index, key);
+    }
+
+    pub fn removed(&mut self, key: TabKey) {
+        self.keys.retain(|k| *k != key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Tabs 0 to 3 activated in turn, 3 being the last
+    fn activated_in_turn() -> MruOrder {
+        let mut order = MruOrder::default();
+        for key in 0..4 {
+            order.added(key, None);
+            order.activated(key);
+        }
+        order
+    }
+
+    #[test]
+    fn activating_moves_a_tab_first() {
+        let mut order = activated_in_turn();
+        assert_eq!(order.keys(), [3, 2, 1, 0]);
+        order.activated(1);
+        assert_eq!(order.keys(), [1, 3, 2, 0]);
+        order.activated(1);
+        assert_eq!(order.keys(), [1, 3, 2, 0]);
+    }
+
+    #[test]
+    fn closing_a_tab_keeps_the_order_of_the_others() {
+        // Closing shifts the index of the tabs after it in the strip, not their keys
+        let mut order = activated_in_turn();
+        order.removed(2);
+        assert_eq!(order.keys(), [3, 1, 0]);
+        order.activated(0);
+        assert_eq!(order.keys(), [0, 3, 1]);
+        order.removed(7);
+        assert_eq!(order.keys(), [0, 3, 1]);
+    }
+
+    #[test]
+    fn duplicates_come_right_after_their_source() {
+        let mut order = activated_in_turn();
+        order.added(4, Some(2));
+        assert_eq!(order.keys(), [3, 2, 4, 1, 0]);
+        // A source already closed leaves the new tab last
+        order.added(5, Some(9));
+        assert_eq!(order.keys(), [3, 2, 4, 1, 0, 5]);
+    }
+
+    #[test]
+    fn adding_a_known_tab_moves_it() {
+        // Listed once, wherever it was
+        let mut order = activated_in_turn();
+        order.added(3, Some(1));
+        assert_eq!(order.keys(), [2, 1, 3, 0]);
+        order.added(2, None);
+        assert_eq!(order.keys(), [1, 3, 0, 2]);
+    }
+}
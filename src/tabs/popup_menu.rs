@@ -0,0 +1,131 @@
+//! Popup menus listing entries, with the command id ranges of every menu of the tab bar.
+//!
+//! Submenus are tracked together with the tab menu, so the ranges must not overlap:
+//!
+//! | Ids         | Menu                                      |
+//! |-------------|-------------------------------------------|
+//! | 1001..1100  | Tab menu commands, see `TabControl`       |
+//! | 1100..2000  | Tab colors, see `TabControl`              |
+//! | 2080..2121  | Tab history, see `history_menu`           |
+//! | 3000..3020  | Recent folders, see `global_history_menu` |
+//! | 3100..3115  | Frequent folders, see `frequent_menu`     |
+//! | 4000..5000  | Bookmarks, see `BookmarksButton`          |
+//! | 5000..5250  | Workspaces, see `workspace_menu`          |
+//! | 6000..6020  | Other windows, see `window_menu`          |
+use windows::core::Result;
+use windows::Win32::Foundation::{HWND, POINT};
+use windows::Win32::UI::WindowsAndMessaging::*;
+
+/// A range of menu command ids, entry `index` of a menu gets `start + index`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MenuIds {
+    start: usize,
+    len: usize,
+}
+
+impl MenuIds {
+    pub const fn new(start: usize, len: usize) -> Self {
+        MenuIds { start, len }
+    }
+
+    pub const fn count(&self) -> usize {
+        self.len
+    }
+
+    /// Id of entry `index`, none past the end of the range.
+    pub fn id(&self, index: usize) -> Option<usize> {
+        match index < self.len {
+            true => Some(self.start + index),
+            false => None,
+        }
+    }
+
+    /// Index of the entry with `id`, none when the id belongs to another menu.
+    pub fn index(&self, id: usize) -> Option<usize> {
+        match id.checked_sub(self.start) {
+            Some(index) if index < self.len => Some(index),
+            _ => None,
+        }
+    }
+}
+
+pub const HISTORY_MENU_IDS: MenuIds = MenuIds::new(2080, 41);
+pub const GLOBAL_HISTORY_MENU_IDS: MenuIds = MenuIds::new(3000, 20);
+pub const FREQUENT_MENU_IDS: MenuIds = MenuIds::new(3100, 15);
+pub const BOOKMARK_MENU_IDS: MenuIds = MenuIds::new(4000, 1000);
+pub const WORKSPACE_MENU_IDS: MenuIds = MenuIds::new(5000, 250);
+pub const WINDOW_MENU_IDS: MenuIds = MenuIds::new(6000, 20);
+
+/// Lists `labels` with the ids of `ids`, or a grayed `empty_text` when there are none.
+///
+/// Labels past the end of the range are left out.
+pub fn create_list_menu<I>(ids: MenuIds, labels: I, empty_text: &str) -> Result<HMENU>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let menu = unsafe { CreatePopupMenu()? };
+    let mut empty = true;
+    for (index, label) in labels.into_iter().take(ids.count()).enumerate() {
+        empty = false;
+        let id = ids.id(index).unwrap_or_default();
+        unsafe { AppendMenuW(menu, MF_STRING, id, label.as_ref()) };
+    }
+    if empty {
+        unsafe { AppendMenuW(menu, MF_GRAYED, 0, empty_text) };
+    }
+    Ok(menu)
+}
+
+/// Shows `menu` at the cursor, destroys it and returns the id picked, 0 when nothing was.
+pub fn track_menu_at_cursor(menu: HMENU, owner: HWND) -> Result<usize> {
+    unsafe {
+        let mut point = POINT::default();
+        GetCursorPos(&mut point as *mut _).ok()?;
+        let command = TrackPopupMenu(
+            menu,
+            TPM_LEFTALIGN | TPM_RIGHTBUTTON | TPM_RETURNCMD,
+            point.x,
+            point.y,
+            0,
+            owner,
+            0 as _,
+        );
+        DestroyMenu(menu).ok()?;
+        Ok(command.0 as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_do_not_overlap() {
+        let mut ranges = [
+            HISTORY_MENU_IDS,
+            GLOBAL_HISTORY_MENU_IDS,
+            FREQUENT_MENU_IDS,
+            BOOKMARK_MENU_IDS,
+            WORKSPACE_MENU_IDS,
+            WINDOW_MENU_IDS,
+        ];
+        ranges.sort_by_key(|ids| ids.start);
+        // Below are the commands and colors of the tab menu
+        assert!(ranges[0].start >= 2000);
+        for pair in ranges.windows(2) {
+            assert!(pair[0].start + pair[0].len <= pair[1].start, "{:?}", pair);
+        }
+    }
+
+    #[test]
+    fn ids_map_back_to_indices() {
+        let ids = MenuIds::new(100, 3);
+        assert_eq!(ids.id(0), Some(100));
+        assert_eq!(ids.id(2), Some(102));
+        assert_eq!(ids.id(3), None);
+        assert_eq!(ids.index(99), None);
+        assert_eq!(ids.index(101), Some(1));
+        assert_eq!(ids.index(103), None);
+    }
+}
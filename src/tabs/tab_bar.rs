@@ -1,54 +1,121 @@
 use std::cell::{RefCell, RefMut};
 use std::collections::HashMap;
+use std::error::Error;
+use std::ops::Range;
+use std::path::PathBuf;
 use std::rc::Rc;
+use std::sync::Mutex;
 
+use once_cell::sync::Lazy;
 use windows::core::{Interface, Result};
 use windows::Win32::Foundation::*;
+use windows::Win32::Storage::FileSystem::GetDriveTypeW;
+use windows::Win32::System::WindowsProgramming::{
+    DRIVE_CDROM, DRIVE_FIXED, DRIVE_RAMDISK, DRIVE_REMOTE, DRIVE_REMOVABLE,
+};
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    GetAsyncKeyState, VK_CONTROL, VK_MENU, VK_SHIFT,
+};
 use windows::Win32::UI::Shell::*;
-use windows::Win32::UI::WindowsAndMessaging::SetForegroundWindow;
+use windows::Win32::UI::WindowsAndMessaging::{PostMessageW, SetForegroundWindow, WM_CLOSE};
 
+use crate::bookmarks::{current_bookmarks, update_bookmarks, BookmarkNode};
+use crate::frecency;
+use crate::get_dll_path;
+use crate::global_history::{self, HistoryEntry, Retention};
 use crate::idl::Idl;
+use crate::ipc::protocol::TabInfo;
+use crate::mouse::{find_mouse_action, Click, KeyModifier, MouseAction};
+use crate::palette::{Candidate, CandidateSource};
+use crate::routing::{route_new_window, Route, RoutingContext};
 use crate::settings::Settings;
+use crate::tab_color::{match_color_rules, TabColor};
+use crate::title_rules::{apply_title_rules, DriveKind, TitleContext, TitleRule};
+use crate::window_registry::all_windows;
+use crate::workspaces::{TabState, Workspace, Workspaces, WORKSPACES_FILE_NAME};
 
+use super::bookmarks_button::BookmarksButton;
+use super::command_palette::PaletteAction;
 use super::explorer_subclass::ExplorerSubclass;
+use super::groups::TabGroups;
+use super::history::{History, HistoryOptions};
+use super::history_menu::TabHistory;
+use super::keyboard_hook::KeyboardHook;
+use super::mru::MruOrder;
 use super::tab_control::{pwstr_to_string, TabControl};
+use super::tab_title::disambiguate_titles;
+use super::tab_transfer::{
+    get_shell_windows, is_tearing_off, open_torn_off_window, request_merge, send_tab,
+    take_torn_off_tab,
+};
 use super::travel_bar_control::TravelBarControl;
+use super::view_subclass::ViewSubclass;
 
 pub static mut DLL_INSTANCE: Option<HINSTANCE> = None;
 
 // A possible path for a tab
 pub type TabPath = Option<Idl>;
 
+const PALETTE_HISTORY_ENTRIES: usize = 200;
+
 pub type TabKey = usize;
 pub type TabIndex = usize;
 
+#[derive(Clone)]
+pub struct CustomTitle {
+    pub title: String,
+    // Keep the title when navigating away from the folder it was set in
+    pub sticky: bool,
+    path: TabPath,
+}
+
 #[derive(Clone)]
 pub struct Tab {
-    current_path: TabPath,
+    history: History<TabPath>,
+    custom_title: Option<CustomTitle>,
+    color: Option<TabColor>,
+    rule_color: Option<TabColor>,
+    pub(super) group: Option<TabKey>,
+    // Selected with shift+click, to create groups from
+    pub(super) marked: bool,
+    // Locked tabs can't be closed
+    locked: bool,
+}
 
-    forward_paths: Vec<TabPath>,
-    backward_paths: Vec<TabPath>,
+impl Tab {
+    fn current_path(&self) -> &TabPath {
+        self.history.current()
+    }
 }
 
-struct TabBar_ {
-    tabs: HashMap<TabKey, Tab>,
-    tab_key_counter: TabKey,
+pub(super) struct TabBar_ {
+    pub(super) tabs: HashMap<TabKey, Tab>,
+    pub(super) groups: TabGroups,
+    pub(super) tab_key_counter: TabKey,
+    mru: MruOrder,
 
     tab_control: Box<TabControl>,
     _explorer_subclass: Box<ExplorerSubclass>,
-    travel_toolbar: TravelBarControl,
+    travel_toolbar: Box<TravelBarControl>,
+    bookmarks_button: Box<BookmarksButton>,
+    _keyboard_hook: Box<KeyboardHook>,
+    view_subclass: Box<ViewSubclass>,
 
     explorer: IShellBrowser,
     explorer_handle: HWND,
-    is_main: bool,
+    // Window of the entries this window adds to the global history
+    history_window_id: i64,
+    settings: Settings,
 }
-pub struct TabBar(RefCell<TabBar_>);
+pub struct TabBar(pub(super) RefCell<TabBar_>);
 fn get_tab_name(pidl: &TabPath) -> String {
-    let pidl = match pidl {
-        None => return "???".to_owned(),
-        Some(pidl) => pidl,
-    };
+    match pidl {
+        None => "???".to_owned(),
+        Some(pidl) => get_idl_name(pidl),
+    }
+}
 
+pub(super) fn get_idl_name(pidl: &Idl) -> String {
     unsafe {
         let name = SHGetNameFromIDList(pidl.get(), SIGDN_NORMALDISPLAY);
         let name = match name {
@@ -59,6 +126,119 @@ fn get_tab_name(pidl: &TabPath) -> String {
     }
 }
 
+pub fn get_idl_path(pidl: &Idl) -> Option<String> {
+    unsafe {
+        let path = SHGetNameFromIDList(pidl.get(), SIGDN_FILESYSPATH)
+            .or_else(|_| SHGetNameFromIDList(pidl.get(), SIGDN_DESKTOPABSOLUTEPARSING))
+            .ok()?;
+        pwstr_to_string(path).ok()
+    }
+}
+
+fn get_file_system_path(pidl: &Idl) -> Option<String> {
+    unsafe {
+        let path = SHGetNameFromIDList(pidl.get(), SIGDN_FILESYSPATH).ok()?;
+        pwstr_to_string(path).ok()
+    }
+}
+
+fn get_drive_kind(path: &str) -> Option<DriveKind> {
+    if path.starts_with("\\\\") && !path.starts_with("\\\\?\\") {
+        return Some(DriveKind::Network);
+    }
+    if path.get(1..3) != Some(":\\") {
+        return None;
+    }
+
+    match unsafe { GetDriveTypeW(&path[..3]) } {
+        DRIVE_FIXED => Some(DriveKind::Fixed),
+        DRIVE_REMOVABLE => Some(DriveKind::Removable),
+        DRIVE_REMOTE => Some(DriveKind::Network),
+        DRIVE_CDROM => Some(DriveKind::Optical),
+        DRIVE_RAMDISK => Some(DriveKind::Ram),
+        _ => None,
+    }
+}
+
+fn get_rule_title(pidl: &Idl, title_rules: &[TitleRule]) -> Option<String> {
+    if title_rules.is_empty() {
+        return None;
+    }
+
+    let path = get_idl_path(pidl)?;
+    let context = TitleContext {
+        path: &path,
+        name: &get_idl_name(pidl),
+        drive: get_drive_kind(&path),
+    };
+    apply_title_rules(title_rules, &context)
+}
+
+// Display names of a path, from the folder itself up to the root
+fn get_path_components(pidl: &TabPath, title_rules: &[TitleRule]) -> Vec<String> {
+    let pidl = match pidl {
+        None => return vec![get_tab_name(pidl)],
+        Some(pidl) => pidl,
+    };
+
+    let name = get_rule_title(pidl, title_rules).unwrap_or_else(|| get_idl_name(pidl));
+    let mut components = vec![name];
+    let parent = pidl.clone();
+    unsafe {
+        while ILRemoveLastID(parent.get() as *mut _).as_bool() && (*parent.get()).mkid.cb != 0 {
+            components.push(get_idl_name(&parent));
+        }
+    }
+    components
+}
+
+fn workspaces_file_path() -> PathBuf {
+    get_dll_path().with_file_name(WORKSPACES_FILE_NAME)
+}
+
+fn load_workspaces() -> Result<Workspaces> {
+    Workspaces::load(&workspaces_file_path()).map_err(log_workspace_error)
+}
+
+fn save_workspaces(workspaces: &Workspaces) -> Result<()> {
+    workspaces
+        .save(&workspaces_file_path())
+        .map_err(log_workspace_error)
+}
+
+// Windows and the control pipe change the workspaces file from their own threads
+static WORKSPACES_LOCK: Lazy<Mutex<()>> = Lazy::new(Default::default);
+
+/// Loads the workspaces, changes them and saves them, with no other change in between.
+fn update_workspaces<T>(update: impl FnOnce(&mut Workspaces) -> Result<T>) -> Result<T> {
+    let _lock = WORKSPACES_LOCK.lock().unwrap();
+    let mut workspaces = load_workspaces()?;
+    let result = update(&mut workspaces)?;
+    save_workspaces(&workspaces)?;
+    Ok(result)
+}
+
+pub fn delete_workspace(name: &str) -> Result<()> {
+    update_workspaces(|workspaces| match workspaces.remove(name) {
+        Some(_) => Ok(()),
+        None => Err(E_FAIL.into()),
+    })
+}
+
+pub fn rename_workspace(name: &str, new_name: &str) -> Result<()> {
+    update_workspaces(|workspaces| {
+        workspaces.rename(name, new_name).map_err(|e| {
+            log::error!("Could not rename workspace:{}", e);
+            E_FAIL.into()
+        })
+    })
+}
+
+fn log_workspace_error(e: Box<dyn Error>) -> windows::core::Error {
+    log::error!("Workspace file error:{:?}", e);
+    E_FAIL.into()
+}
+
 pub fn get_current_folder_path(browser: &IShellBrowser) -> TabPath {
     unsafe {
         let folder_view: IFolderView = browser.QueryActiveShellView().ok()?.cast().ok()?;
@@ -78,34 +258,47 @@ impl TabBar {
         travel_toolbar_handle: HWND,
         browser: IShellBrowser,
         settings: Settings,
-        is_main: bool,
     ) -> Rc<TabBar> {
         Rc::new_cyclic(|weak| {
+            let tab_control = TabControl::new(
+                parent,
+                weak.clone(),
+                settings.dark_mode,
+                settings.tab_color_style,
+                settings.mouse_wheel.clone(),
+                settings.show_close_button,
+            );
             TabBar(RefCell::new(TabBar_ {
                 tabs: Default::default(),
+                groups: Default::default(),
                 tab_key_counter: 0,
-                tab_control: TabControl::new(parent, weak.clone(), settings.dark_mode),
-                travel_toolbar: TravelBarControl::new(travel_toolbar_handle),
+                mru: Default::default(),
+                bookmarks_button: BookmarksButton::new(tab_control.handle, weak.clone()),
+                tab_control,
+                travel_toolbar: TravelBarControl::new(travel_toolbar_handle, weak.clone()),
+                _keyboard_hook: KeyboardHook::new(weak.clone()),
+                view_subclass: ViewSubclass::new(weak.clone(), settings.open_in_background),
                 _explorer_subclass: ExplorerSubclass::new(explorer_handle, weak.clone()),
                 explorer: browser,
                 explorer_handle,
-                is_main,
+                history_window_id: global_history::new_window_id(),
+                settings,
             }))
         })
     }
 
-    pub fn is_main(&self) -> bool {
-        self.0.borrow().is_main
+    pub fn get_explorer_handle(&self) -> HWND {
+        self.0.borrow().explorer_handle
     }
 
     pub fn get_handle(&self) -> HWND {
         self.tab_control().handle
     }
-    fn tab_control(&self) -> Box<TabControl> {
+    pub(super) fn tab_control(&self) -> Box<TabControl> {
         return self.0.borrow().tab_control.clone();
     }
 
-    fn get_tab(&self, index: TabIndex) -> Option<RefMut<Tab>> {
+    pub(super) fn get_tab(&self, index: TabIndex) -> Option<RefMut<Tab>> {
         let key = self.tab_control().get_tab_key(index).ok()?;
         if self.0.borrow().tabs.contains_key(&key) {
             Some(RefMut::map(self.0.borrow_mut(), |tab_bar| {
@@ -116,18 +309,82 @@ impl TabBar {
         }
     }
 
+    // Items moved together with the item at `index`, a header brings its whole group
+    fn get_block(&self, index: TabIndex) -> Range<TabIndex> {
+        self.get_group_key(index)
+            .and_then(|group_key| self.get_group_block(group_key))
+            .unwrap_or(index..index + 1)
+    }
+
+    // Block next to `block`, grouped tabs stay in their group and other groups are skipped whole
+    fn get_neighbour_block(&self, block: &Range<TabIndex>, left: bool) -> Option<Range<TabIndex>> {
+        let neighbour = match left {
+            true => block.start.checked_sub(1)?,
+            false => block.end,
+        };
+        if neighbour >= self.tab_control().get_tab_count() {
+            return None;
+        }
+
+        let own_group = self.get_tab(block.start).and_then(|tab| tab.group);
+        let neighbour_group = self.get_item_group(neighbour);
+        match own_group {
+            Some(_) if neighbour_group == own_group && !self.is_group_header(neighbour) => {
+                Some(neighbour..neighbour + 1)
+            }
+            Some(_) => None,
+            None => match neighbour_group {
+                Some(group_key) => self.get_group_block(group_key),
+                None => Some(neighbour..neighbour + 1),
+            },
+        }
+    }
+
+    // Closest tab outside of `excluded`, looking left first
+    pub(super) fn find_nearest_tab(&self, excluded: Range<TabIndex>) -> Option<TabIndex> {
+        let count = self.tab_control().get_tab_count();
+        (0..excluded.start)
+            .rev()
+            .chain(excluded.end..count)
+            .find(|&index| self.get_tab(index).is_some())
+    }
+
+    fn get_rule_color(&self, path: &TabPath) -> Option<TabColor> {
+        let obj = self.0.borrow();
+        let color_rules = &obj.settings.color_rules;
+        if color_rules.is_empty() {
+            return None;
+        }
+        match_color_rules(color_rules, &get_idl_path(path.as_ref()?)?)
+    }
+
+    fn get_history_options(&self) -> HistoryOptions {
+        let settings = &self.0.borrow().settings;
+        HistoryOptions {
+            max_depth: settings.history_depth,
+            collapse_duplicates: settings.collapse_history,
+        }
+    }
+
     fn add_tab_entry(&self, path: TabPath) -> TabKey {
+        let rule_color = self.get_rule_color(&path);
+        let history_options = self.get_history_options();
         let obj = &mut *self.0.borrow_mut();
         let tabs = &mut obj.tabs;
         let key_counter = &mut obj.tab_key_counter;
         let key = *key_counter;
         *key_counter += 1;
+        obj.mru.added(key, None);
         tabs.insert(
             key,
             Tab {
-                current_path: path,
-                forward_paths: Vec::new(),
-                backward_paths: Vec::new(),
+                history: History::new(path, history_options),
+                custom_title: None,
+                color: None,
+                rule_color,
+                group: None,
+                marked: false,
+                locked: false,
             },
         );
         key
@@ -135,88 +392,298 @@ impl TabBar {
 
     pub fn add_tab(&self, path: TabPath, index: usize) -> Result<()> {
         let key = self.add_tab_entry(path.clone());
-        self.tab_control().add_tab(get_tab_name(&path), index, key)
+        self.tab_control()
+            .add_tab(get_tab_name(&path), index, key)?;
+        self.refresh_tab_titles()
     }
 
     pub fn remove_tab(&self, index: TabIndex) -> Result<()> {
+        if self.is_group_header(index) {
+            return self.ungroup(index);
+        }
+        if self.is_tab_locked(index) {
+            return Ok(());
+        }
+
         let key = self.tab_control().get_tab_key(index)?;
         if Some(index) == self.tab_control().get_selected_tab_index() {
-            if self.tab_control().get_tab_count() <= 1 {
+            match self.find_nearest_tab(index..index + 1) {
                 // Don't allow removing last tab
-                return Ok(());
-            } else if index == 0 {
-                self.switch_tab(1)?;
-            } else {
-                self.switch_tab(index - 1)?;
+                None => return Ok(()),
+                Some(next_index) => self.switch_tab(next_index)?,
             }
         }
         {
-            let tabs = &mut self.0.borrow_mut().tabs;
-            if tabs.contains_key(&key) {
-                tabs.remove(&key);
-            }
+            let obj = &mut *self.0.borrow_mut();
+            obj.tabs.remove(&key);
+            obj.mru.removed(key);
         }
         self.tab_control().remove_tab(index)?;
+        self.remove_empty_groups()?;
+        self.refresh_tab_titles()
+    }
+
+    pub fn is_tab_marked(&self, index: TabIndex) -> bool {
+        self.get_tab(index).map_or(false, |tab| tab.marked)
+    }
+
+    pub fn toggle_tab_marked(&self, index: TabIndex) -> Result<()> {
+        {
+            let mut tab = self.get_tab(index).ok_or(E_FAIL)?;
+            tab.marked = !tab.marked;
+        }
+        self.tab_control().redraw();
+        Ok(())
+    }
+
+    pub fn is_tab_locked(&self, index: TabIndex) -> bool {
+        self.get_tab(index).map_or(false, |tab| tab.locked)
+    }
+
+    pub fn toggle_tab_locked(&self, index: TabIndex) -> Result<()> {
+        let mut tab = self.get_tab(index).ok_or(E_FAIL)?;
+        tab.locked = !tab.locked;
+        Ok(())
+    }
+
+    pub fn get_marked_tabs(&self) -> Vec<TabIndex> {
+        (0..self.tab_control().get_tab_count())
+            .filter(|&index| self.is_tab_marked(index))
+            .collect()
+    }
+
+    // Moves a tab, or a whole group when `index` is a group header
+    pub fn move_tab(&self, index: TabIndex, left: bool) -> Result<()> {
+        let block = self.get_block(index);
+        let neighbour = match self.get_neighbour_block(&block, left) {
+            Some(neighbour) => neighbour,
+            None => return Ok(()),
+        };
+
+        let tab_control = self.tab_control();
+        for _ in neighbour.clone() {
+            match left {
+                true => tab_control.move_tab(neighbour.start, block.end - 1)?,
+                false => tab_control.move_tab(neighbour.end - 1, block.start)?,
+            }
+        }
+        tab_control.redraw();
+        Ok(())
+    }
+
+    pub fn get_selected_tab_index(&self) -> Option<TabIndex> {
+        self.tab_control().get_selected_tab_index()
+    }
+
+    pub fn get_tab_index(&self, key: TabKey) -> Option<TabIndex> {
+        self.tab_control().get_tab_index(key)
+    }
+
+    // Tabs with the same name get parent folders added to their titles
+    pub(super) fn refresh_tab_titles(&self) -> Result<()> {
+        let tab_control = self.tab_control();
+        let title_rules = self.0.borrow().settings.title_rules.clone();
+        let tabs: Vec<(Option<String>, Vec<String>)> = (0..tab_control.get_tab_count())
+            .map(|index| match self.get_tab(index) {
+                Some(tab) => match &tab.custom_title {
+                    Some(custom_title) => (Some(custom_title.title.clone()), Vec::new()),
+                    None => (None, get_path_components(tab.current_path(), &title_rules)),
+                },
+                None => (self.get_group(index).map(|group| group.title()), Vec::new()),
+            })
+            .collect();
+
+        // Renamed tabs and group headers don't take part in disambiguation
+        let (fixed_titles, paths): (Vec<_>, Vec<_>) = tabs.into_iter().unzip();
+        let automatic_paths: Vec<Vec<String>> = fixed_titles
+            .iter()
+            .zip(paths)
+            .filter(|(fixed_title, _)| fixed_title.is_none())
+            .map(|(_, path)| path)
+            .collect();
+        let mut automatic_titles = disambiguate_titles(&automatic_paths).into_iter();
+        let titles = fixed_titles
+            .into_iter()
+            .map(|fixed_title| fixed_title.or_else(|| automatic_titles.next()));
+
+        for (index, title) in titles.enumerate() {
+            let title = title.unwrap_or_default();
+            if tab_control.get_tab_text(index).ok().as_ref() != Some(&title) {
+                tab_control.set_tab_title(index, title)?;
+            }
+        }
         Ok(())
     }
 
     pub fn navigated(&self, path: TabPath) -> Result<()> {
         let index = self.tab_control().get_selected_tab_index().ok_or(E_FAIL)?;
         log::info!("tab {:?}, navigated to {:?}", index, get_tab_name(&path));
-        {
+        let rule_color = self.get_rule_color(&path);
+        let changed_folder = {
             let mut tab = self.get_tab(index).ok_or(E_FAIL)?;
-            let current_path = tab.current_path.clone();
-            if tab.current_path != path && tab.current_path.is_some() {
-                tab.forward_paths.clear();
-                tab.backward_paths.push(current_path);
+            let changed_folder = tab.current_path() != &path;
+            let keep_custom_title = match &tab.custom_title {
+                Some(custom_title) => custom_title.sticky || custom_title.path == path,
+                None => true,
+            };
+            if !keep_custom_title {
+                tab.custom_title = None;
             }
-            tab.current_path = path.clone();
+            tab.rule_color = rule_color;
+            match tab.current_path() {
+                Some(_) => tab.history.push(path.clone()),
+                None => tab.history.replace_current(path.clone()),
+            }
+            changed_folder
+        };
+
+        // Switching tabs browses to their folder too, only count actual visits
+        if let (true, Some(pidl)) = (changed_folder, &path) {
+            self.record_global_history(&path);
+            frecency::visit(
+                pidl,
+                get_idl_name(pidl),
+                get_idl_path(pidl).unwrap_or_default(),
+            );
         }
 
-        self.tab_control()
-            .set_tab_title(index, get_tab_name(&path))?;
+        self.refresh_tab_titles()?;
 
-        let can_go_backward = !self.get_tab(index).ok_or(E_FAIL)?.backward_paths.is_empty();
-        let can_go_forward = !self.get_tab(index).ok_or(E_FAIL)?.forward_paths.is_empty();
+        let browser = self.0.borrow().explorer.clone();
+        if let Err(e) = self.0.borrow().view_subclass.attach(&browser) {
+            log::error!("Could not attach to the folder view:{:?}", e);
+        }
+
+        let (can_go_backward, can_go_forward) = {
+            let tab = self.get_tab(index).ok_or(E_FAIL)?;
+            (tab.history.can_go_back(), tab.history.can_go_forward())
+        };
         self.0
             .borrow()
             .travel_toolbar
-            .set_button_active(256, can_go_backward);
+            .set_button_active(TravelBarControl::BACK_BUTTON, can_go_backward);
         self.0
             .borrow()
             .travel_toolbar
-            .set_button_active(257, can_go_forward);
+            .set_button_active(TravelBarControl::FORWARD_BUTTON, can_go_forward);
 
         Ok(())
     }
 
-    pub fn navigate_back(&self) -> Result<()> {
-        let index = self.tab_control().get_selected_tab_index().ok_or(E_FAIL)?;
+    // Renames a group, or sets the custom title of a tab
+    pub fn rename(&self, key: TabKey, title: String) -> Result<()> {
+        if let Some(mut group) = self.get_group_mut(key) {
+            let title = title.trim();
+            if !title.is_empty() {
+                group.name = title.to_owned();
+            }
+            std::mem::drop(group);
+            return self.refresh_tab_titles();
+        }
+
+        let index = self.get_tab_index(key).ok_or(E_FAIL)?;
+        self.set_custom_title(index, title)
+    }
+
+    pub fn get_custom_title(&self, index: TabIndex) -> Option<CustomTitle> {
+        self.get_tab(index)?.custom_title.clone()
+    }
 
+    // An empty title restores the automatic name
+    pub fn set_custom_title(&self, index: TabIndex, title: String) -> Result<()> {
+        {
+            let mut tab = self.get_tab(index).ok_or(E_FAIL)?;
+            let title = title.trim();
+            tab.custom_title = match title {
+                "" => None,
+                _ => Some(CustomTitle {
+                    title: title.to_owned(),
+                    sticky: tab.custom_title.as_ref().map_or(false, |t| t.sticky),
+                    path: tab.current_path().clone(),
+                }),
+            };
+        }
+        self.refresh_tab_titles()
+    }
+
+    pub fn toggle_custom_title_sticky(&self, index: TabIndex) -> Result<()> {
         let mut tab = self.get_tab(index).ok_or(E_FAIL)?;
-        let current_path = tab.current_path.clone();
-        let next_path = tab.backward_paths.pop().ok_or(E_FAIL)?;
-        tab.forward_paths.push(current_path);
-        tab.current_path = next_path.clone();
-        std::mem::drop(tab);
+        let current_path = tab.current_path().clone();
+        let custom_title = tab.custom_title.as_mut().ok_or(E_FAIL)?;
+        custom_title.sticky = !custom_title.sticky;
+        custom_title.path = current_path;
+        Ok(())
+    }
 
-        self.browse_to(next_path)
+    // Color picked by hand, then the group color, then the one given by the color rules
+    pub fn get_tab_color(&self, index: TabIndex) -> Option<TabColor> {
+        let (color, group_key, rule_color) = {
+            let tab = self.get_tab(index)?;
+            (tab.color, tab.group, tab.rule_color)
+        };
+        let group_color =
+            group_key.and_then(|key| self.0.borrow().groups.get(key).map(|g| g.color));
+        color.or(group_color).or(rule_color)
+    }
+
+    pub fn get_custom_tab_color(&self, index: TabIndex) -> Option<TabColor> {
+        self.get_tab(index)?.color
+    }
+
+    pub fn set_tab_color(&self, index: TabIndex, color: Option<TabColor>) -> Result<()> {
+        self.get_tab(index).ok_or(E_FAIL)?.color = color;
+        self.tab_control().redraw();
+        Ok(())
+    }
+
+    pub fn navigate_back(&self) -> Result<()> {
+        let index = self.tab_control().get_selected_tab_index().ok_or(E_FAIL)?;
+        self.navigate_history(index, -1)
     }
 
     pub fn navigate_forward(&self) -> Result<()> {
         let index = self.tab_control().get_selected_tab_index().ok_or(E_FAIL)?;
+        self.navigate_history(index, 1)
+    }
 
-        let mut tab = self.get_tab(index).ok_or(E_FAIL)?;
-        let current_path = tab.current_path.clone();
-        let next_path = tab.forward_paths.pop().ok_or(E_FAIL)?;
-        tab.backward_paths.push(current_path);
-        tab.current_path = next_path.clone();
-        std::mem::drop(tab);
+    pub fn get_tab_history(&self, index: TabIndex) -> Option<TabHistory> {
+        let tab = self.get_tab(index)?;
+        Some(TabHistory {
+            backward: tab.history.backward_entries().map(get_tab_name).collect(),
+            current: get_tab_name(tab.current_path()),
+            forward: tab.history.forward_entries().map(get_tab_name).collect(),
+        })
+    }
+
+    // Moves `offset` steps through the history of a tab and shows it, negative offsets go back
+    pub fn navigate_history(&self, index: TabIndex, offset: isize) -> Result<()> {
+        self.get_tab(index)
+            .ok_or(E_FAIL)?
+            .history
+            .go(offset)
+            .ok_or(E_FAIL)?;
+        self.switch_tab(index)
+    }
 
-        self.browse_to(next_path)
+    pub fn strip_moved(&self) {
+        self.0.borrow().bookmarks_button.place_beside_strip();
     }
 
-    fn browse_to(&self, path: TabPath) -> Result<()> {
+    pub fn bookmark_tab(&self, index: TabIndex, folder: &[usize]) -> Result<()> {
+        let path = self.get_tab(index).ok_or(E_FAIL)?.current_path().clone();
+        let pidl = path.as_ref().ok_or(E_FAIL)?;
+        let node = BookmarkNode::Bookmark {
+            name: get_idl_name(pidl),
+            path: get_idl_path(pidl).unwrap_or_default(),
+            idl: pidl.to_hex(),
+        };
+        match update_bookmarks(|bookmarks| bookmarks.insert(folder, node)) {
+            true => Ok(()),
+            false => Err(E_FAIL.into()),
+        }
+    }
+
+    pub fn browse_to(&self, path: TabPath) -> Result<()> {
         let browser = self.0.borrow().explorer.clone();
         unsafe { browser.BrowseObject(path.ok_or(E_FAIL)?.get(), SBSP_SAMEBROWSER)? }
         Ok(())
@@ -236,14 +703,507 @@ impl TabBar {
 
     pub fn switch_tab(&self, index: TabIndex) -> Result<()> {
         log::info!("trying to switch to tab {:?}", index);
+        let path = self.get_tab(index).ok_or(E_FAIL)?.current_path().clone();
+        let key = self.tab_control().get_tab_key(index)?;
         self.tab_control().set_selected_tab(index)?;
-        let path = self.get_tab(index).ok_or(E_FAIL)?.current_path.clone();
+        self.0.borrow_mut().mru.activated(key);
         self.browse_to(path)
     }
 
+    // Moves the selection `offset` tabs along the strip, group headers don't count
+    pub fn switch_tab_by(&self, offset: isize, wrap_around: bool) -> Result<()> {
+        let tabs: Vec<TabIndex> = (0..self.tab_control().get_tab_count())
+            .filter(|&index| self.get_tab(index).is_some())
+            .collect();
+        let selected = self.get_selected_tab_index().ok_or(E_FAIL)?;
+        let position = tabs
+            .iter()
+            .position(|index| *index == selected)
+            .ok_or(E_FAIL)? as isize;
+        let count = tabs.len() as isize;
+        let target = match wrap_around {
+            true => (position + offset).rem_euclid(count),
+            false => (position + offset).clamp(0, count - 1),
+        };
+        match target == position {
+            true => Ok(()),
+            false => self.switch_tab(tabs[target as usize]),
+        }
+    }
+
+    pub fn switch_to_key(&self, key: TabKey) -> Result<()> {
+        self.switch_tab(self.get_tab_index(key).ok_or(E_FAIL)?)
+    }
+
+    // Tabs shown in the strip, most recently used first
+    pub fn get_recent_tabs(&self) -> Vec<(TabKey, String, TabPath)> {
+        let keys = self.0.borrow().mru.keys().to_vec();
+        let tab_control = self.tab_control();
+        keys.into_iter()
+            .filter_map(|key| {
+                let index = tab_control.get_tab_index(key)?;
+                let path = self.get_tab(index)?.current_path().clone();
+                let title = tab_control.get_tab_text(index).unwrap_or_default();
+                Some((key, title, path))
+            })
+            .collect()
+    }
+
+    // Adds a tab right after `index` and in the same group, as if opened from it
+    fn add_tab_after(&self, index: TabIndex, path: TabPath) -> Result<TabIndex> {
+        let group = self.get_tab(index).ok_or(E_FAIL)?.group;
+        self.add_tab(path, index + 1)?;
+        self.get_tab(index + 1).ok_or(E_FAIL)?.group = group;
+
+        let tab_control = self.tab_control();
+        let key = tab_control.get_tab_key(index)?;
+        let new_key = tab_control.get_tab_key(index + 1)?;
+        self.0.borrow_mut().mru.added(new_key, Some(key));
+        Ok(index + 1)
+    }
+
     pub fn clone_tab(&self, index: TabIndex) -> Result<()> {
-        let tab = self.get_tab(index).ok_or(E_FAIL)?.clone();
-        self.add_tab(tab.current_path, index + 1)
+        let path = self.get_tab(index).ok_or(E_FAIL)?.current_path().clone();
+        self.add_tab_after(index, path).map(|_| ())
+    }
+
+    // Opens a folder of the selected tab next to it
+    pub fn open_tab_beside(&self, path: TabPath, background: bool) -> Result<()> {
+        let selected = self.get_selected_tab_index().ok_or(E_FAIL)?;
+        let index = self.add_tab_after(selected, path)?;
+        match background {
+            true => Ok(()),
+            false => self.switch_tab(index),
+        }
+    }
+
+    // Adds a navigation of the selected tab to the history shared by all windows
+    fn record_global_history(&self, path: &TabPath) {
+        let pidl = match path {
+            Some(pidl) => pidl,
+            None => return,
+        };
+        let tab_control = self.tab_control();
+        let tab = match tab_control.get_selected_tab_index() {
+            Some(index) => tab_control.get_tab_key(index).unwrap_or_default(),
+            None => return,
+        };
+
+        let obj = self.0.borrow();
+        let entry = HistoryEntry {
+            time: chrono::Utc::now().timestamp(),
+            name: get_idl_name(pidl),
+            path: get_idl_path(pidl).unwrap_or_default(),
+            idl: pidl.to_hex(),
+            tab,
+            window: obj.history_window_id,
+        };
+        let retention = Retention {
+            max_entries: obj.settings.global_history_entries,
+            max_age_days: obj.settings.global_history_days,
+        };
+        global_history::record(entry, retention);
+    }
+
+    pub fn open_tab(&self, path: TabPath) -> Result<()> {
+        let index = self.tab_control().get_tab_count();
+        path.as_ref().ok_or(E_FAIL)?;
+        self.add_tab(path, index)?;
+        self.switch_tab(index)
+    }
+
+    pub fn open_home_tab(&self) -> Result<()> {
+        let home_folder = self.0.borrow().settings.home_folder.clone();
+        let pidl = Idl::from_display_name(&home_folder).ok_or(E_FAIL)?;
+        self.open_tab(Some(pidl))
+    }
+
+    // Shows the tab and browses to the folder containing its current one
+    pub fn open_parent(&self, index: TabIndex) -> Result<()> {
+        let parent = self
+            .get_tab(index)
+            .ok_or(E_FAIL)?
+            .current_path()
+            .clone()
+            .ok_or(E_FAIL)?;
+        unsafe {
+            if !ILRemoveLastID(parent.get() as *mut _).as_bool() {
+                return Ok(());
+            }
+        }
+        if self.get_selected_tab_index() != Some(index) {
+            self.switch_tab(index)?;
+        }
+        self.browse_to(Some(parent))
+    }
+
+    pub fn find_mouse_action(&self, click: &Click) -> MouseAction {
+        find_mouse_action(&self.0.borrow().settings.mouse_bindings, click)
+    }
+
+    // Opens the best frecency match for the query in the selected tab
+    pub fn jump_to_folder(&self, query: &str) -> Result<()> {
+        let entry = frecency::best_match(query.trim()).ok_or(E_FAIL)?;
+        self.browse_to(entry.get_idl())
+    }
+
+    // Tabs in strip order, including the ones hidden in collapsed groups
+    fn get_tab_keys(&self) -> Vec<TabKey> {
+        let tab_control = self.tab_control();
+        let obj = self.0.borrow();
+        (0..tab_control.get_tab_count())
+            .filter_map(|index| tab_control.get_tab_key(index).ok())
+            .flat_map(|key| match obj.groups.get(key) {
+                Some(group) => group.hidden_tabs().to_vec(),
+                None => vec![key],
+            })
+            .collect()
+    }
+
+    pub fn get_tab_state(&self, key: TabKey) -> Option<TabState> {
+        let obj = self.0.borrow();
+        let tab = obj.tabs.get(&key)?;
+        let pidl = tab.current_path().as_ref()?;
+        Some(TabState {
+            name: get_idl_name(pidl),
+            path: get_idl_path(pidl).unwrap_or_default(),
+            idl: pidl.to_hex(),
+            custom_title: tab.custom_title.as_ref().map(|t| t.title.clone()),
+            custom_title_sticky: tab.custom_title.as_ref().map_or(false, |t| t.sticky),
+            backward: tab
+                .history
+                .backward_entries()
+                .flatten()
+                .map(Idl::to_hex)
+                .collect(),
+            forward: tab
+                .history
+                .forward_entries()
+                .flatten()
+                .map(Idl::to_hex)
+                .collect(),
+        })
+    }
+
+    pub fn restore_tab(&self, state: &TabState, index: TabIndex) -> Result<()> {
+        let pidl = Idl::from_hex(&state.idl).ok_or(E_FAIL)?;
+        self.add_tab(Some(pidl), index)?;
+        self.apply_tab_state(index, state)
+    }
+
+    // Brings back the history and title of a saved tab
+    fn apply_tab_state(&self, index: TabIndex, state: &TabState) -> Result<()> {
+        let pidl = Idl::from_hex(&state.idl).ok_or(E_FAIL)?;
+        let from_hex = |entries: &[String]| -> Vec<TabPath> {
+            entries
+                .iter()
+                .filter_map(|idl| Idl::from_hex(idl))
+                .map(Some)
+                .collect()
+        };
+        let history = History::from_entries(
+            Some(pidl),
+            from_hex(&state.backward),
+            from_hex(&state.forward),
+            self.get_history_options(),
+        );
+        self.get_tab(index).ok_or(E_FAIL)?.history = history;
+        match &state.custom_title {
+            Some(title) => {
+                self.set_custom_title(index, title.clone())?;
+                if let Some(custom_title) = &mut self.get_tab(index).ok_or(E_FAIL)?.custom_title {
+                    custom_title.sticky = state.custom_title_sticky;
+                }
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    pub fn get_workspace_names(&self) -> Vec<String> {
+        match load_workspaces() {
+            Ok(workspaces) => workspaces.list().iter().map(|w| w.name.clone()).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    pub fn save_workspace(&self, name: &str) -> Result<()> {
+        let selected_key = self
+            .get_selected_tab_index()
+            .and_then(|index| self.tab_control().get_tab_key(index).ok());
+        let (keys, tabs): (Vec<TabKey>, Vec<TabState>) = self
+            .get_tab_keys()
+            .into_iter()
+            .filter_map(|key| Some((key, self.get_tab_state(key)?)))
+            .unzip();
+        let workspace = Workspace {
+            name: name.to_owned(),
+            tabs,
+            selected: selected_key.and_then(|key| keys.iter().position(|k| *k == key)),
+        };
+
+        update_workspaces(|workspaces| {
+            workspaces.set(workspace);
+            Ok(())
+        })
+    }
+
+    // Adds the tabs of a workspace at the end, closing the current ones when replacing
+    pub fn open_workspace(&self, name: &str, replace: bool) -> Result<()> {
+        let workspace = load_workspaces()?.get(name).cloned().ok_or(E_FAIL)?;
+        let start = self.tab_control().get_tab_count();
+        let mut end = start;
+        for state in workspace.tabs.iter() {
+            match self.restore_tab(state, end) {
+                Ok(_) => end += 1,
+                Err(e) => log::error!("Could not restore tab {:?}:{:?}", state.path, e),
+            }
+        }
+        if end == start {
+            return Err(E_FAIL.into());
+        }
+
+        let selected = workspace.selected.filter(|s| start + s < end).unwrap_or(0);
+        self.switch_tab(start + selected)?;
+        if replace {
+            // Locked tabs stay. Tabs go by key since indices shift as tabs and emptied groups go
+            let tab_control = self.tab_control();
+            let opened: Vec<TabKey> = (start..end)
+                .filter_map(|index| tab_control.get_tab_key(index).ok())
+                .collect();
+            self.expand_groups()?;
+            for key in self.get_tab_keys() {
+                match self.get_tab_index(key) {
+                    Some(index) if !opened.contains(&key) && !self.is_tab_locked(index) => {
+                        self.remove_tab(index)?
+                    }
+                    _ => (),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Open tabs, then bookmarks, then recently visited folders
+    pub fn get_palette_candidates(&self) -> (Vec<Candidate>, Vec<PaletteAction>) {
+        let mut candidates = Vec::new();
+        let mut actions = Vec::new();
+        let mut add = |title: String, pidl: &Idl, source, action| {
+            candidates.push(Candidate {
+                title,
+                detail: get_idl_path(pidl).unwrap_or_default(),
+                source,
+            });
+            actions.push(action);
+        };
+
+        let tab_control = self.tab_control();
+        for index in 0..tab_control.get_tab_count() {
+            let (key, pidl) = match (tab_control.get_tab_key(index), self.get_tab(index)) {
+                (Ok(key), Some(tab)) => match tab.current_path() {
+                    Some(pidl) => (key, pidl.clone()),
+                    None => continue,
+                },
+                _ => continue,
+            };
+            let title = tab_control.get_tab_text(index).unwrap_or_default();
+            add(
+                title,
+                &pidl,
+                CandidateSource::Tab,
+                PaletteAction::SwitchTab(key),
+            );
+        }
+        for bookmark in current_bookmarks().all_bookmarks() {
+            if let Some(pidl) = bookmark.get_idl() {
+                let title = bookmark.name().to_owned();
+                add(
+                    title,
+                    &pidl,
+                    CandidateSource::Bookmark,
+                    PaletteAction::Open(pidl.clone()),
+                );
+            }
+        }
+        for entry in global_history::recent(PALETTE_HISTORY_ENTRIES) {
+            if let Some(pidl) = entry.get_idl() {
+                add(
+                    entry.name,
+                    &pidl,
+                    CandidateSource::History,
+                    PaletteAction::Open(pidl.clone()),
+                );
+            }
+        }
+        (candidates, actions)
+    }
+
+    pub fn run_palette_action(&self, action: PaletteAction) -> Result<()> {
+        match action {
+            PaletteAction::SwitchTab(key) => self.switch_to_key(key),
+            PaletteAction::Open(pidl) => self.open_tab(Some(pidl)),
+        }
+    }
+
+    // Returns false when explorer should open the window itself
+    pub fn route_new_window(&self, pidl: Idl) -> bool {
+        // The window a tab is being torn off into
+        if is_tearing_off() {
+            return false;
+        }
+        let path = get_idl_path(&pidl).unwrap_or_default();
+        let file_system_path = get_file_system_path(&pidl);
+        // Asynchronous state, the keys were pressed in another window's thread
+        let modifiers: Vec<KeyModifier> = [
+            (VK_SHIFT, KeyModifier::Shift),
+            (VK_CONTROL, KeyModifier::Ctrl),
+            (VK_MENU, KeyModifier::Alt),
+        ]
+        .iter()
+        .filter(|(key, _)| unsafe { GetAsyncKeyState(key.0 as i32) } < 0)
+        .map(|(_, modifier)| *modifier)
+        .collect();
+        let context = RoutingContext {
+            path: &path,
+            drive: file_system_path.as_deref().and_then(get_drive_kind),
+            virtual_folder: file_system_path.is_none(),
+            modifiers: &modifiers,
+        };
+        let route = {
+            let settings = &self.0.borrow().settings;
+            route_new_window(&settings.routing_rules, settings.default_route, &context)
+        };
+        log::info!("Routing new window for {:?} to {:?}", path, route);
+
+        let result = match route {
+            Route::NewWindow => return false,
+            Route::ForegroundTab => self.new_window(Some(pidl)),
+            Route::BackgroundTab => {
+                let index = self.tab_control().get_tab_count();
+                self.add_tab(Some(pidl), index)
+            }
+        };
+        result.is_ok()
+    }
+
+    /// Explorer windows with a tab bar other than this one, most recently active first.
+    pub fn get_other_windows(&self) -> Vec<HWND> {
+        let explorer_handle = self.get_explorer_handle();
+        all_windows()
+            .into_iter()
+            .filter(|&window| window != explorer_handle)
+            .collect()
+    }
+
+    // Adds a tab handed over by another window, history included
+    pub fn receive_tab(&self, state: &TabState) -> Result<()> {
+        let index = self.tab_control().get_tab_count();
+        self.restore_tab(state, index)?;
+        self.switch_tab(index)
+    }
+
+    pub fn move_tab_to_window(&self, index: TabIndex, window: HWND) -> Result<()> {
+        if self.is_tab_locked(index) {
+            return Ok(());
+        }
+        let key = self.tab_control().get_tab_key(index)?;
+        let state = self.get_tab_state(key).ok_or(E_FAIL)?;
+        if !send_tab(window, &state) {
+            return Err(E_FAIL.into());
+        }
+        match self.find_nearest_tab(index..index + 1) {
+            // Nothing left in this window
+            None => unsafe {
+                PostMessageW(self.get_explorer_handle(), WM_CLOSE, WPARAM(0), LPARAM(0));
+                Ok(())
+            },
+            Some(_) => self.remove_tab(index),
+        }
+    }
+
+    // Opens the tab in a new window, which picks up its history once it shows the folder
+    pub fn tear_off_tab(&self, index: TabIndex) -> Result<()> {
+        if self.is_tab_locked(index) || self.find_nearest_tab(index..index + 1).is_none() {
+            return Ok(());
+        }
+        let key = self.tab_control().get_tab_key(index)?;
+        let state = self.get_tab_state(key).ok_or(E_FAIL)?;
+        open_torn_off_window(state)?;
+        self.remove_tab(index)
+    }
+
+    // Hands the selected tab to `target` and closes this window, see `merge_all_windows`
+    pub fn merge_into(&self, target: HWND) -> Result<()> {
+        let index = self.get_selected_tab_index().ok_or(E_FAIL)?;
+        let key = self.tab_control().get_tab_key(index)?;
+        let state = self.get_tab_state(key).ok_or(E_FAIL)?;
+        if !send_tab(target, &state) {
+            return Err(E_FAIL.into());
+        }
+        unsafe { PostMessageW(self.get_explorer_handle(), WM_CLOSE, WPARAM(0), LPARAM(0)) };
+        Ok(())
+    }
+
+    /// Collects the selected tab of every other explorer window into this one and closes them.
+    pub fn merge_all_windows(&self) -> Result<()> {
+        let explorer_handle = self.get_explorer_handle();
+        let bar_windows = all_windows();
+        for &window in bar_windows
+            .iter()
+            .filter(|&&window| window != explorer_handle)
+        {
+            if !request_merge(window, explorer_handle) {
+                log::error!("Window {:?} could not be merged", window);
+            }
+        }
+        // Windows without a tab bar only give their folder
+        for (window, pidl) in get_shell_windows()? {
+            if window == explorer_handle || bar_windows.contains(&window) {
+                continue;
+            }
+            let index = self.tab_control().get_tab_count();
+            self.add_tab(Some(pidl), index)?;
+            unsafe { PostMessageW(window, WM_CLOSE, WPARAM(0), LPARAM(0)) };
+        }
+        Ok(())
+    }
+
+    /// Gives the first tab of a window opened by tearing off a tab the history of that tab.
+    pub fn adopt_torn_off_tab(&self) {
+        if let Some(state) = take_torn_off_tab(self.get_explorer_handle()) {
+            if let Err(e) = self.apply_tab_state(0, &state) {
+                log::error!("Could not restore torn off tab:{:?}", e);
+            }
+        }
+    }
+
+    /// Tabs of the strip for the control pipe, group headers and collapsed tabs left out.
+    pub fn get_tab_infos(&self) -> Vec<TabInfo> {
+        let tab_control = self.tab_control();
+        let selected = self.get_selected_tab_index();
+        (0..tab_control.get_tab_count())
+            .filter_map(|index| {
+                let state = self.get_tab_state(tab_control.get_tab_key(index).ok()?)?;
+                Some(TabInfo {
+                    index,
+                    title: state.custom_title.unwrap_or(state.name),
+                    path: state.path,
+                    selected: selected == Some(index),
+                })
+            })
+            .collect()
+    }
+
+    // Opens a path or shell location given as text, at the end of the strip
+    pub fn open_path(&self, path: &str, background: bool) -> Result<()> {
+        let pidl = Idl::from_display_name(path).ok_or(E_FAIL)?;
+        match background {
+            true => {
+                let index = self.tab_control().get_tab_count();
+                self.add_tab(Some(pidl), index)
+            }
+            false => self.new_window(Some(pidl)),
+        }
     }
 
     pub fn new_window(&self, path: TabPath) -> Result<()> {
@@ -1,13 +1,35 @@
 use std::ptr::{addr_of, addr_of_mut};
 use std::rc::{Rc, Weak};
 
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    GetKeyState, ReleaseCapture, SetCapture, VK_MENU,
+};
 use windows::Win32::{
     Foundation::*, Graphics::Gdi::*, UI::Controls::*, UI::Shell::*, UI::WindowsAndMessaging::*,
 };
 
 use windows::core::*;
 
-use super::tab_bar::{TabBar, TabIndex, TabKey, DLL_INSTANCE};
+use crate::detour::hook_status;
+use crate::frecency::{self, FrecencyEntry};
+use crate::global_history::{self, HistoryEntry};
+use crate::mouse::{
+    Click, ClickKind, ClickTarget, KeyModifier, MouseAction, MouseButton, WheelAccumulator,
+    WheelSettings,
+};
+use crate::tab_color::{TabColorStyle, PRESET_COLORS};
+
+use super::bookmarks_button::BookmarksButton;
+use super::frequent_menu::{create_frequent_menu, frequent_menu_index, MAX_FREQUENT_MENU_ENTRIES};
+use super::global_history_menu::{
+    create_global_history_menu, global_history_menu_index, pick_global_history_entry,
+    MAX_GLOBAL_HISTORY_MENU_ENTRIES,
+};
+use super::history_menu::{create_history_menu, history_menu_offset};
+use super::inline_edit::InlineEdit;
+use super::tab_bar::{delete_workspace, rename_workspace, TabBar, TabIndex, TabKey, DLL_INSTANCE};
+use super::window_menu::{create_window_menu, window_menu_index};
+use super::workspace_menu::{create_workspace_menu, workspace_menu_command, WorkspaceAction};
 
 #[derive(Clone)]
 struct FontHolder(HFONT);
@@ -19,12 +41,44 @@ impl Drop for FontHolder {
     }
 }
 
+// Lists the explorer hooks, true when one of them failed
+fn create_status_menu() -> Result<(HMENU, bool)> {
+    let menu = unsafe { CreatePopupMenu()? };
+    let status = hook_status();
+    if status.is_empty() {
+        unsafe { AppendMenuW(menu, MF_GRAYED, 0, "No Hooks Installed") };
+    }
+    for (hook, result) in status.iter() {
+        let text = match result {
+            Ok(()) => format!("{}: Active", hook.feature()),
+            Err(e) => format!("{}: Unavailable, {}", hook.feature(), e),
+        };
+        unsafe { AppendMenuW(menu, MF_GRAYED, 0, text.as_str()) };
+    }
+    Ok((menu, status.iter().any(|(_, result)| result.is_err())))
+}
+
 #[derive(Clone)]
 pub struct TabControl {
     pub handle: HWND,
     pub dark_mode: bool,
+    color_style: TabColorStyle,
     tab_bar: Weak<TabBar>,
     focused_tab: Option<TabIndex>,
+    menu_tab: Option<TabIndex>,
+    // Entries listed in the recent folders menu
+    menu_history: Vec<HistoryEntry>,
+    // Entries listed in the frequent folders menu
+    menu_frequent: Vec<FrecencyEntry>,
+    // Workspaces listed in the workspaces menu
+    menu_workspaces: Vec<String>,
+    // Windows listed in the move tab menu
+    menu_windows: Vec<HWND>,
+    // Tab held down with the left button, torn off when released away from the strip
+    drag_tab: Option<TabIndex>,
+    wheel_settings: WheelSettings,
+    wheel: WheelAccumulator,
+    show_close_button: bool,
     font: Rc<FontHolder>,
     _pin: std::marker::PhantomPinned,
 }
@@ -77,13 +131,20 @@ impl TabControl {
         unsafe { DefSubclassProc(hwnd, umsg, wparam, lparam) }
     }
 
-    pub fn new(parent_handle: HWND, tab_bar: Weak<TabBar>, dark_mode: bool) -> Box<TabControl> {
+    pub fn new(
+        parent_handle: HWND,
+        tab_bar: Weak<TabBar>,
+        dark_mode: bool,
+        color_style: TabColorStyle,
+        wheel_settings: WheelSettings,
+        show_close_button: bool,
+    ) -> Box<TabControl> {
         let handle = unsafe {
             CreateWindowExW(
                 WINDOW_EX_STYLE(0),
                 "SysTabControl32",
                 "",
-                WS_CHILD | WS_CLIPSIBLINGS | WS_VISIBLE,
+                WS_CHILD | WS_CLIPSIBLINGS | WS_CLIPCHILDREN | WS_VISIBLE,
                 0,
                 0,
                 0,
@@ -115,13 +176,28 @@ impl TabControl {
 
         let new = Box::new(TabControl {
             dark_mode,
+            color_style,
             handle,
             tab_bar,
             focused_tab: None,
+            menu_tab: None,
+            menu_history: Vec::new(),
+            menu_frequent: Vec::new(),
+            menu_workspaces: Vec::new(),
+            menu_windows: Vec::new(),
+            drag_tab: None,
+            wheel_settings,
+            wheel: Default::default(),
+            show_close_button,
             font: Rc::new(FontHolder(font)),
             _pin: Default::default(),
         });
         unsafe { SendMessageW(handle, WM_SETFONT, WPARAM(font.0 as _), LPARAM(true as _)) };
+        if show_close_button {
+            // Room for the button on the right of the title
+            let padding = Self::CLOSE_BUTTON_WIDTH as isize | 3 << 16;
+            unsafe { SendMessageW(handle, TCM_SETPADDING, WPARAM(0), LPARAM(padding)) };
+        }
 
         unsafe {
             SetWindowSubclass(
@@ -296,6 +372,37 @@ impl TabControl {
         }
     }
 
+    pub fn redraw(&self) {
+        unsafe {
+            InvalidateRect(self.handle, std::ptr::null(), BOOL(1));
+            UpdateWindow(self.handle);
+        }
+    }
+
+    pub fn get_tab_index(&self, key: TabKey) -> Option<TabIndex> {
+        (0..self.get_tab_count()).find(|&index| self.get_tab_key(index).ok() == Some(key))
+    }
+
+    // Moves a tab so that it ends up at index `to`, keeping the selection
+    pub fn move_tab(&self, from: TabIndex, to: TabIndex) -> Result<()> {
+        if from == to {
+            return Ok(());
+        }
+
+        let selected_key = self
+            .get_selected_tab_index()
+            .and_then(|index| self.get_tab_key(index).ok());
+        let key = self.get_tab_key(from)?;
+        let title = self.get_tab_text(from)?;
+        self.remove_tab(from)?;
+        self.add_tab(title, to, key)?;
+
+        if let Some(index) = selected_key.and_then(|key| self.get_tab_index(key)) {
+            self.set_selected_tab(index)?;
+        }
+        Ok(())
+    }
+
     pub fn remove_tab(&self, index: TabIndex) -> Result<()> {
         let handle = self.handle;
         unsafe {
@@ -322,9 +429,114 @@ impl TabControl {
         }
     }
 
-    fn create_popup_menu(&self) -> Result<()> {
+    const CLOSE_BUTTON_WIDTH: i32 = 16;
+    fn get_close_button_rect(&self, index: TabIndex) -> Result<RECT> {
+        let tab_rect = self.get_tab_rect(index)?;
+        Ok(RECT {
+            left: tab_rect.right - 2 - Self::CLOSE_BUTTON_WIDTH,
+            right: tab_rect.right - 2,
+            ..tab_rect
+        })
+    }
+
+    fn get_click_target(
+        &self,
+        tab_bar: &TabBar,
+        x: i32,
+        y: i32,
+    ) -> (ClickTarget, Option<TabIndex>) {
+        let index = match self.get_tab_at_coords(x, y) {
+            Some(index) => index,
+            None => return (ClickTarget::Empty, None),
+        };
+        let on_close_button = self.show_close_button
+            && !tab_bar.is_group_header(index)
+            && self
+                .get_close_button_rect(index)
+                .map_or(false, |rect| unsafe {
+                    PtInRect(addr_of!(rect), POINT { x, y }).as_bool()
+                });
+        match on_close_button {
+            true => (ClickTarget::CloseButton, Some(index)),
+            false => (ClickTarget::Tab, Some(index)),
+        }
+    }
+
+    const MENU_TOGGLE_DARK_MODE: usize = 1001;
+    const MENU_RENAME_TAB: usize = 1002;
+    const MENU_RESET_TAB_NAME: usize = 1003;
+    const MENU_KEEP_TAB_NAME: usize = 1004;
+    const MENU_MOVE_LEFT: usize = 1005;
+    const MENU_MOVE_RIGHT: usize = 1006;
+    const MENU_NEW_GROUP: usize = 1007;
+    const MENU_REMOVE_FROM_GROUP: usize = 1008;
+    const MENU_TOGGLE_GROUP: usize = 1009;
+    const MENU_UNGROUP: usize = 1010;
+    const MENU_SEARCH_HISTORY: usize = 1011;
+    const MENU_CLEAR_HISTORY: usize = 1012;
+    const MENU_JUMP_TO_FOLDER: usize = 1013;
+    const MENU_BOOKMARK_TAB: usize = 1014;
+    const MENU_SAVE_WORKSPACE: usize = 1015;
+    const MENU_LOCK_TAB: usize = 1016;
+    const MENU_MOVE_TO_NEW_WINDOW: usize = 1017;
+    const MENU_MERGE_WINDOWS: usize = 1018;
+    const MENU_TAB_COLOR_DEFAULT: usize = 1100;
+    fn create_popup_menu(&mut self, tab_bar: &TabBar) -> Result<()> {
+        self.menu_tab = self.focused_tab;
         let menu = unsafe { CreatePopupMenu()? };
-        unsafe { AppendMenuW(menu, MF_STRING, 1001, "Toggle Dark Mode") };
+        match self.menu_tab {
+            Some(index) if tab_bar.is_group_header(index) => {
+                self.append_group_menu_items(menu, tab_bar, index)?
+            }
+            Some(index) => {
+                self.menu_windows = tab_bar.get_other_windows();
+                self.append_tab_menu_items(menu, tab_bar, index)?
+            }
+            None => (),
+        }
+        unsafe {
+            AppendMenuW(
+                menu,
+                MF_POPUP,
+                self.create_recent_folders_menu()?.0 as _,
+                "Recent Folders",
+            );
+            AppendMenuW(
+                menu,
+                MF_POPUP,
+                self.create_frequent_folders_menu()?.0 as _,
+                "Frequent Folders",
+            );
+            self.menu_workspaces = tab_bar.get_workspace_names();
+            AppendMenuW(
+                menu,
+                MF_POPUP,
+                create_workspace_menu(&self.menu_workspaces, Self::MENU_SAVE_WORKSPACE)?.0 as _,
+                "Workspaces",
+            );
+            AppendMenuW(
+                menu,
+                MF_STRING,
+                Self::MENU_MERGE_WINDOWS,
+                "Merge All Windows",
+            );
+            AppendMenuW(
+                menu,
+                MF_STRING,
+                Self::MENU_TOGGLE_DARK_MODE,
+                "Toggle Dark Mode",
+            );
+            let (status_menu, degraded) = create_status_menu()?;
+            AppendMenuW(
+                menu,
+                MF_POPUP,
+                status_menu.0 as _,
+                match degraded {
+                    true => "Status (Some Features Unavailable)",
+                    false => "Status",
+                },
+            )
+        };
         unsafe {
             let mut point = POINT::default();
             GetCursorPos(&mut point as *mut _).ok()?;
@@ -343,6 +555,303 @@ impl TabControl {
         Ok(())
     }
 
+    fn append_tab_menu_items(&self, menu: HMENU, tab_bar: &TabBar, index: TabIndex) -> Result<()> {
+        let custom_title = tab_bar.get_custom_title(index);
+        let custom_flags = match &custom_title {
+            Some(_) => MF_STRING,
+            None => MF_GRAYED,
+        };
+        let keep_flags = match &custom_title {
+            Some(title) if title.sticky => MF_CHECKED,
+            _ => MF_STRING,
+        };
+        let grouped = tab_bar.is_grouped(index);
+        unsafe {
+            AppendMenuW(menu, MF_STRING, Self::MENU_RENAME_TAB, "Rename Tab");
+            AppendMenuW(
+                menu,
+                custom_flags,
+                Self::MENU_RESET_TAB_NAME,
+                "Reset Tab Name",
+            );
+            AppendMenuW(
+                menu,
+                custom_flags | keep_flags,
+                Self::MENU_KEEP_TAB_NAME,
+                "Keep Name In Other Folders",
+            );
+            AppendMenuW(
+                menu,
+                MF_POPUP,
+                self.create_color_menu(tab_bar, index)?.0 as _,
+                "Tab Color",
+            );
+            AppendMenuW(
+                menu,
+                MF_POPUP,
+                create_history_menu(tab_bar, index)?.0 as _,
+                "History",
+            );
+            AppendMenuW(menu, MF_STRING, Self::MENU_BOOKMARK_TAB, "Bookmark Folder");
+            AppendMenuW(
+                menu,
+                match tab_bar.is_tab_locked(index) {
+                    true => MF_CHECKED,
+                    false => MF_STRING,
+                },
+                Self::MENU_LOCK_TAB,
+                "Lock Tab",
+            );
+            AppendMenuW(menu, MF_SEPARATOR, 0, PCWSTR::default());
+            AppendMenuW(menu, MF_STRING, Self::MENU_MOVE_LEFT, "Move Tab Left");
+            AppendMenuW(menu, MF_STRING, Self::MENU_MOVE_RIGHT, "Move Tab Right");
+            AppendMenuW(
+                menu,
+                MF_POPUP,
+                create_window_menu(&self.menu_windows)?.0 as _,
+                "Move Tab To Window",
+            );
+            AppendMenuW(
+                menu,
+                MF_STRING,
+                Self::MENU_MOVE_TO_NEW_WINDOW,
+                "Move Tab To New Window",
+            );
+            AppendMenuW(
+                menu,
+                MF_STRING,
+                Self::MENU_NEW_GROUP,
+                "New Group From Selected Tabs",
+            );
+            if grouped {
+                AppendMenuW(
+                    menu,
+                    MF_STRING,
+                    Self::MENU_REMOVE_FROM_GROUP,
+                    "Remove From Group",
+                );
+            }
+            AppendMenuW(menu, MF_SEPARATOR, 0, PCWSTR::default());
+        }
+        Ok(())
+    }
+
+    fn append_group_menu_items(
+        &self,
+        menu: HMENU,
+        tab_bar: &TabBar,
+        index: TabIndex,
+    ) -> Result<()> {
+        let group = tab_bar.get_group(index).ok_or(E_FAIL)?;
+        unsafe {
+            AppendMenuW(
+                menu,
+                MF_STRING,
+                Self::MENU_TOGGLE_GROUP,
+                match group.collapsed {
+                    true => "Expand Group",
+                    false => "Collapse Group",
+                },
+            );
+            AppendMenuW(menu, MF_STRING, Self::MENU_RENAME_TAB, "Rename Group");
+            AppendMenuW(
+                menu,
+                MF_POPUP,
+                self.create_color_menu(tab_bar, index)?.0 as _,
+                "Group Color",
+            );
+            AppendMenuW(menu, MF_STRING, Self::MENU_MOVE_LEFT, "Move Group Left");
+            AppendMenuW(menu, MF_STRING, Self::MENU_MOVE_RIGHT, "Move Group Right");
+            AppendMenuW(menu, MF_STRING, Self::MENU_UNGROUP, "Ungroup");
+            AppendMenuW(menu, MF_SEPARATOR, 0, PCWSTR::default());
+        }
+        Ok(())
+    }
+
+    fn create_color_menu(&self, tab_bar: &TabBar, index: TabIndex) -> Result<HMENU> {
+        let group = tab_bar.get_group(index);
+        let current_color = match &group {
+            Some(group) => Some(group.color),
+            None => tab_bar.get_custom_tab_color(index),
+        };
+        let checked = |selected: bool| match selected {
+            true => MF_CHECKED,
+            false => MF_STRING,
+        };
+
+        let menu = unsafe { CreatePopupMenu()? };
+        unsafe {
+            if group.is_none() {
+                AppendMenuW(
+                    menu,
+                    checked(current_color.is_none()),
+                    Self::MENU_TAB_COLOR_DEFAULT,
+                    "Default",
+                );
+            }
+            for (offset, (name, color)) in PRESET_COLORS.iter().enumerate() {
+                AppendMenuW(
+                    menu,
+                    checked(current_color == Some(*color)),
+                    Self::MENU_TAB_COLOR_DEFAULT + 1 + offset,
+                    *name,
+                );
+            }
+        }
+        Ok(menu)
+    }
+
+    fn create_recent_folders_menu(&mut self) -> Result<HMENU> {
+        self.menu_history = global_history::recent(MAX_GLOBAL_HISTORY_MENU_ENTRIES);
+        let menu = create_global_history_menu(&self.menu_history, "No Recent Folders")?;
+        unsafe {
+            AppendMenuW(menu, MF_SEPARATOR, 0, PCWSTR::default());
+            AppendMenuW(
+                menu,
+                MF_STRING,
+                Self::MENU_SEARCH_HISTORY,
+                "Search History...",
+            );
+            AppendMenuW(menu, MF_STRING, Self::MENU_CLEAR_HISTORY, "Clear History");
+        }
+        Ok(menu)
+    }
+
+    fn create_frequent_folders_menu(&mut self) -> Result<HMENU> {
+        self.menu_frequent = frecency::top(MAX_FREQUENT_MENU_ENTRIES);
+        let menu = create_frequent_menu(&self.menu_frequent)?;
+        unsafe {
+            AppendMenuW(menu, MF_SEPARATOR, 0, PCWSTR::default());
+            AppendMenuW(
+                menu,
+                MF_STRING,
+                Self::MENU_JUMP_TO_FOLDER,
+                "Jump To Folder...",
+            );
+        }
+        Ok(menu)
+    }
+
+    // Shows an edit box over the whole tab strip, the callback gets the text entered
+    fn prompt(&self, text: &str, on_commit: Box<dyn FnOnce(Rc<TabBar>, String)>) -> Result<()> {
+        let mut rect = RECT::default();
+        unsafe { GetClientRect(self.handle, addr_of_mut!(rect)).ok()? };
+        let weak_tab_bar = self.tab_bar.clone();
+        InlineEdit::show(
+            self.handle,
+            rect,
+            text,
+            self.font.0,
+            Box::new(move |text| {
+                if let Some(tab_bar) = weak_tab_bar.upgrade() {
+                    on_commit(tab_bar, text);
+                }
+            }),
+        );
+        Ok(())
+    }
+
+    // Asks for a query, then lists the matching folders
+    fn start_history_search(&self) -> Result<()> {
+        let handle = self.handle;
+        self.prompt(
+            "",
+            Box::new(move |tab_bar, query| {
+                let entries = global_history::search(query.trim(), MAX_GLOBAL_HISTORY_MENU_ENTRIES);
+                let result = match pick_global_history_entry(handle, &entries) {
+                    Ok(Some(entry)) => tab_bar.open_tab(entry.get_idl()),
+                    result => result.map(|_| ()),
+                };
+                if result.is_err() {
+                    log::error!("Error searching history:{:?}", result);
+                }
+            }),
+        )
+    }
+
+    fn start_jump_to_folder(&self) -> Result<()> {
+        self.prompt(
+            "",
+            Box::new(|tab_bar, query| {
+                let result = tab_bar.jump_to_folder(&query);
+                if result.is_err() {
+                    log::error!("No folder to jump to for {:?}:{:?}", query, result);
+                }
+            }),
+        )
+    }
+
+    fn start_save_workspace(&self) -> Result<()> {
+        self.prompt(
+            "",
+            Box::new(|tab_bar, name| {
+                let name = name.trim();
+                if name.is_empty() {
+                    return;
+                }
+                let result = tab_bar.save_workspace(name);
+                if result.is_err() {
+                    log::error!("Error saving workspace {:?}:{:?}", name, result);
+                }
+            }),
+        )
+    }
+
+    fn run_workspace_action(
+        &self,
+        tab_bar: &TabBar,
+        index: usize,
+        action: WorkspaceAction,
+    ) -> Result<()> {
+        let name = self.menu_workspaces.get(index).ok_or(E_FAIL)?.clone();
+        match action {
+            WorkspaceAction::Open => tab_bar.open_workspace(&name, true),
+            WorkspaceAction::Append => tab_bar.open_workspace(&name, false),
+            WorkspaceAction::Overwrite => tab_bar.save_workspace(&name),
+            WorkspaceAction::Delete => delete_workspace(&name),
+            WorkspaceAction::Rename => self.prompt(
+                &name.clone(),
+                Box::new(move |_, new_name| {
+                    let new_name = new_name.trim();
+                    if new_name.is_empty() {
+                        return;
+                    }
+                    let result = rename_workspace(&name, new_name);
+                    if result.is_err() {
+                        log::error!("Error renaming workspace {:?}:{:?}", name, result);
+                    }
+                }),
+            ),
+        }
+    }
+
+    fn start_rename(&self, tab_bar: &TabBar, index: TabIndex) -> Result<()> {
+        let key = self.get_tab_key(index)?;
+        let text = match (tab_bar.get_group(index), tab_bar.get_custom_title(index)) {
+            (Some(group), _) => group.name,
+            (None, Some(custom_title)) => custom_title.title,
+            (None, None) => self.get_tab_text(index)?,
+        };
+        let weak_tab_bar = self.tab_bar.clone();
+        InlineEdit::show(
+            self.handle,
+            self.get_tab_rect(index)?,
+            &text,
+            self.font.0,
+            Box::new(move |title| {
+                let tab_bar = match weak_tab_bar.upgrade() {
+                    Some(tab_bar) => tab_bar,
+                    None => return,
+                };
+                let result = tab_bar.rename(key, title);
+                if result.is_err() {
+                    log::error!("Error renaming tab:{:?}", result);
+                }
+            }),
+        );
+        Ok(())
+    }
+
     // file under mouse : 0x4d4d4d
     // file selected : 0x777777
     // background : 0x191919
@@ -350,8 +859,12 @@ impl TabControl {
     const BG_FOCUSED_TAB: u32 = 0x4d4d4d;
     const BG_SELECTED_TAB: u32 = 0x191919;
     const BG_UNFOCUSED_TAB: u32 = 0x202020;
+    const BG_MARKED_TAB: u32 = 0x3a3a3a;
     const BORDER_COLOR: u32 = 0x2b2b2b;
-    fn paint(&self, handle: HWND) -> Result<()> {
+    const COLOR_STRIPE_HEIGHT: i32 = 3;
+    const COLOR_TINT_AMOUNT: u32 = 0x50;
+    const GROUP_HEADER_TINT_AMOUNT: u32 = 0xa0;
+    fn paint(&self, handle: HWND, tab_bar: &TabBar) -> Result<()> {
         unsafe {
             let mut paint_struct: PAINTSTRUCT = Default::default();
             let hdc = BeginPaint(handle, addr_of_mut!(paint_struct));
@@ -388,11 +901,24 @@ impl TabControl {
                 if !selected {
                     tab_rect.top += 2;
                 }
+                let tab_color = tab_bar.get_tab_color(index);
+                let group = tab_bar.get_group(index);
+                let marked = tab_bar.is_tab_marked(index);
                 {
-                    let color = match (selected, focused) {
-                        (true, false) => Self::BG_SELECTED_TAB,
-                        (false, false) => Self::BG_UNFOCUSED_TAB,
-                        (_, true) => Self::BG_FOCUSED_TAB,
+                    let color = match (selected, focused, marked) {
+                        (true, false, _) => Self::BG_SELECTED_TAB,
+                        (false, false, false) => Self::BG_UNFOCUSED_TAB,
+                        (false, false, true) => Self::BG_MARKED_TAB,
+                        (_, true, _) => Self::BG_FOCUSED_TAB,
+                    };
+                    let color = match (&group, tab_color, self.color_style) {
+                        (Some(group), _, _) => {
+                            group.color.blend(color, Self::GROUP_HEADER_TINT_AMOUNT)
+                        }
+                        (None, Some(tab_color), TabColorStyle::Tint) => {
+                            tab_color.blend(color, Self::COLOR_TINT_AMOUNT)
+                        }
+                        _ => color,
                     };
                     let brush = CreateSolidBrush(color);
                     FillRect(hdc, addr_of!(tab_rect), brush);
@@ -420,6 +946,22 @@ impl TabControl {
 
                 Polyline(hdc, &edges);
 
+                let stripe_color = match (&group, self.color_style) {
+                    (None, TabColorStyle::Stripe) => tab_color,
+                    _ => None,
+                };
+                if let Some(tab_color) = stripe_color {
+                    let stripe_rect = RECT {
+                        left: tab_rect.left + 1,
+                        top: tab_rect.top + 1,
+                        right: tab_rect.right,
+                        bottom: tab_rect.top + 1 + Self::COLOR_STRIPE_HEIGHT,
+                    };
+                    let brush = CreateSolidBrush(tab_color.0);
+                    FillRect(hdc, addr_of!(stripe_rect), brush);
+                    DeleteObject(brush);
+                }
+
                 let mut text_rect = tab_rect;
                 text_rect.top += 2;
                 SetBkMode(hdc, TRANSPARENT);
@@ -430,6 +972,13 @@ impl TabControl {
                     .encode_utf16()
                     .collect();
                 DrawTextW(hdc, &u16_tab_text, addr_of_mut!(text_rect), DT_CENTER);
+
+                if self.show_close_button && group.is_none() {
+                    let mut close_rect = self.get_close_button_rect(index)?;
+                    close_rect.top = text_rect.top;
+                    let close_text: Vec<u16> = "\u{00d7}".encode_utf16().collect();
+                    DrawTextW(hdc, &close_text, addr_of_mut!(close_rect), DT_CENTER);
+                }
             }
             SelectObject(hdc, hold_pen);
             DeleteObject(edge_pen);
@@ -440,18 +989,162 @@ impl TabControl {
         Ok(())
     }
 
-    fn handle_left_click(&mut self, tab_bar: Rc<TabBar>, flags: usize) -> Result<()> {
-        if flags & MK_CONTROL as usize != 0 {
-            match self.focused_tab {
-                Some(index) => tab_bar.clone_tab(index),
-                None => Ok(()),
+    // Returns true when the click shouldn't reach the tab control
+    fn handle_click(
+        &mut self,
+        tab_bar: Rc<TabBar>,
+        button: MouseButton,
+        kind: ClickKind,
+        wparam: WPARAM,
+        lparam: LPARAM,
+    ) -> Result<bool> {
+        let x = (lparam.0 & 0xffff) as i16;
+        let y = ((lparam.0 >> 16) & 0xffff) as i16;
+        let (target, index) = self.get_click_target(&tab_bar, x as _, y as _);
+
+        // Group headers open and close with the left button whatever the bindings
+        if let (MouseButton::Left, Some(index)) = (button, index) {
+            if tab_bar.is_group_header(index) {
+                tab_bar.toggle_group_collapsed(index)?;
+                return Ok(true);
             }
+        }
+
+        let flags = wparam.0 & 0xffff;
+        let modifiers = [
+            (flags & MK_SHIFT as usize != 0, KeyModifier::Shift),
+            (flags & MK_CONTROL as usize != 0, KeyModifier::Ctrl),
+            (
+                unsafe { GetKeyState(VK_MENU.0 as i32) < 0 },
+                KeyModifier::Alt,
+            ),
+        ];
+        let click = Click {
+            button,
+            modifiers: modifiers
+                .iter()
+                .filter(|(held, _)| *held)
+                .map(|(_, modifier)| *modifier)
+                .collect(),
+            target,
+            kind,
+        };
+        let action = tab_bar.find_mouse_action(&click);
+        log::info!("{:?} on {:?} {:?}: {:?}", button, target, index, action);
+        self.run_mouse_action(&tab_bar, action, index)?;
+        // A tab dragged away from the strip is torn off, see `end_drag`
+        if (button, kind, action) == (MouseButton::Left, ClickKind::Click, MouseAction::Switch) {
+            if let Some(index) = index {
+                self.drag_tab = Some(index);
+                unsafe { SetCapture(self.handle) };
+            }
+        }
+        // The tab control selects the tab itself when switching
+        Ok(action != MouseAction::Switch)
+    }
+
+    // Distance from the strip a tab has to be dropped at to be torn off
+    const TEAR_OFF_DISTANCE: i32 = 40;
+
+    fn end_drag(&mut self, tab_bar: &TabBar, lparam: LPARAM) -> Result<()> {
+        let index = match self.drag_tab.take() {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+        unsafe { ReleaseCapture() };
+        let y = ((lparam.0 >> 16) & 0xffff) as i16 as i32;
+        let mut rect = RECT::default();
+        unsafe { GetClientRect(self.handle, &mut rect).ok()? };
+        if y < rect.top - Self::TEAR_OFF_DISTANCE || y > rect.bottom + Self::TEAR_OFF_DISTANCE {
+            tab_bar.tear_off_tab(index)?;
+        }
+        Ok(())
+    }
+
+    fn run_mouse_action(
+        &mut self,
+        tab_bar: &TabBar,
+        action: MouseAction,
+        index: Option<TabIndex>,
+    ) -> Result<()> {
+        match (action, index) {
+            (MouseAction::NewTab, _) => tab_bar.open_home_tab(),
+            (MouseAction::Menu, _) => {
+                self.focused_tab = index;
+                self.create_popup_menu(tab_bar)
+            }
+            (MouseAction::Nothing, _) | (_, None) => Ok(()),
+            (MouseAction::Switch, Some(index)) => tab_bar.switch_tab(index),
+            (MouseAction::Close, Some(index)) => tab_bar.remove_tab(index),
+            (MouseAction::Duplicate, Some(index)) => tab_bar.clone_tab(index),
+            (MouseAction::OpenParent, Some(index)) => tab_bar.open_parent(index),
+            (MouseAction::ToggleLock, Some(index)) => tab_bar.toggle_tab_locked(index),
+            (MouseAction::Rename, Some(index)) => self.start_rename(tab_bar, index),
+            (MouseAction::ToggleMark, Some(index)) => tab_bar.toggle_tab_marked(index),
+        }
+    }
+
+    fn handle_mouse_wheel(
+        &mut self,
+        tab_bar: &TabBar,
+        wparam: WPARAM,
+        horizontal: bool,
+    ) -> Result<()> {
+        let delta = ((wparam.0 >> 16) & 0xffff) as i16;
+        let steps = self.wheel.add(delta as i32) as isize;
+        if steps == 0 {
+            return Ok(());
+        }
+
+        let keys = wparam.0 & 0xffff;
+        let modifier_held = match self.wheel_settings.scroll_modifier {
+            KeyModifier::Shift => keys & MK_SHIFT as usize != 0,
+            KeyModifier::Ctrl => keys & MK_CONTROL as usize != 0,
+            KeyModifier::Alt => unsafe { GetKeyState(VK_MENU.0 as i32) < 0 },
+        };
+        // Turning the wheel away from the user goes up, tilting it right goes right
+        let offset = match horizontal {
+            true => steps,
+            false => -steps,
+        };
+        if horizontal || modifier_held {
+            self.scroll_strip(offset)
+        } else if self.wheel_settings.switch_tabs {
+            let offset = match self.wheel_settings.reverse {
+                true => -offset,
+                false => offset,
+            };
+            tab_bar.switch_tab_by(offset, self.wheel_settings.wrap_around)
         } else {
-            match self.focused_tab {
-                Some(index) => tab_bar.switch_tab(index),
-                None => Ok(()),
+            Ok(())
+        }
+    }
+
+    // Scrolls tabs that don't fit in the strip, as the arrows at its end do
+    fn scroll_strip(&self, offset: isize) -> Result<()> {
+        unsafe {
+            let arrows = FindWindowExW(self.handle, HWND(0), "msctls_updown32", PCWSTR::default());
+            if arrows.0 == 0 || !IsWindowVisible(arrows).as_bool() {
+                return Ok(());
             }
+            let (mut min, mut max) = (0i32, 0i32);
+            SendMessageW(
+                arrows,
+                UDM_GETRANGE32,
+                WPARAM(addr_of_mut!(min) as _),
+                LPARAM(addr_of_mut!(max) as _),
+            );
+            let position = SendMessageW(arrows, UDM_GETPOS32, WPARAM(0), LPARAM(0)).0 as i32;
+            let position = (position + offset as i32).clamp(min, max);
+            SendMessageW(
+                self.handle,
+                WM_HSCROLL,
+                WPARAM(SB_THUMBPOSITION.0 as usize | (position as usize) << 16),
+                LPARAM(0),
+            );
+            SendMessageW(arrows, UDM_SETPOS32, WPARAM(0), LPARAM(position as _));
         }
+        Ok(())
     }
 
     fn window_procedure(
@@ -464,7 +1157,7 @@ impl TabControl {
         if let Some(tab_bar) = self.tab_bar.upgrade() {
             let result = match message {
                 WM_COMMAND => match wparam.0 {
-                    1001 => {
+                    Self::MENU_TOGGLE_DARK_MODE => {
                         tab_bar.toggle_dark_mode();
                         unsafe {
                             InvalidateRect(hwnd, std::ptr::null(), BOOL(1));
@@ -472,18 +1165,145 @@ impl TabControl {
                         }
                         Ok(())
                     }
+                    Self::MENU_RENAME_TAB => match self.menu_tab {
+                        Some(index) => self.start_rename(&tab_bar, index),
+                        None => Ok(()),
+                    },
+                    Self::MENU_RESET_TAB_NAME => match self.menu_tab {
+                        Some(index) => tab_bar.set_custom_title(index, String::new()),
+                        None => Ok(()),
+                    },
+                    Self::MENU_KEEP_TAB_NAME => match self.menu_tab {
+                        Some(index) => tab_bar.toggle_custom_title_sticky(index),
+                        None => Ok(()),
+                    },
+                    Self::MENU_MOVE_LEFT | Self::MENU_MOVE_RIGHT => match self.menu_tab {
+                        Some(index) => tab_bar.move_tab(index, wparam.0 == Self::MENU_MOVE_LEFT),
+                        None => Ok(()),
+                    },
+                    Self::MENU_NEW_GROUP => match self.menu_tab {
+                        Some(index) => {
+                            let mut indices = tab_bar.get_marked_tabs();
+                            if !indices.contains(&index) {
+                                indices.push(index);
+                            }
+                            tab_bar.create_group(&indices)
+                        }
+                        None => Ok(()),
+                    },
+                    Self::MENU_REMOVE_FROM_GROUP => match self.menu_tab {
+                        Some(index) => tab_bar.remove_from_group(index),
+                        None => Ok(()),
+                    },
+                    Self::MENU_TOGGLE_GROUP => match self.menu_tab {
+                        Some(index) => tab_bar.toggle_group_collapsed(index),
+                        None => Ok(()),
+                    },
+                    Self::MENU_UNGROUP => match self.menu_tab {
+                        Some(index) => tab_bar.ungroup(index),
+                        None => Ok(()),
+                    },
+                    Self::MENU_SEARCH_HISTORY => self.start_history_search(),
+                    Self::MENU_CLEAR_HISTORY => {
+                        global_history::clear();
+                        Ok(())
+                    }
+                    id if global_history_menu_index(id).is_some() => {
+                        match self
+                            .menu_history
+                            .get(global_history_menu_index(id).unwrap())
+                        {
+                            Some(entry) => tab_bar.open_tab(entry.get_idl()),
+                            None => Ok(()),
+                        }
+                    }
+                    Self::MENU_JUMP_TO_FOLDER => self.start_jump_to_folder(),
+                    Self::MENU_SAVE_WORKSPACE => self.start_save_workspace(),
+                    id if workspace_menu_command(id).is_some() => {
+                        let (index, action) = workspace_menu_command(id).unwrap();
+                        self.run_workspace_action(&tab_bar, index, action)
+                    }
+                    Self::MENU_LOCK_TAB => match self.menu_tab {
+                        Some(index) => tab_bar.toggle_tab_locked(index),
+                        None => Ok(()),
+                    },
+                    Self::MENU_MERGE_WINDOWS => tab_bar.merge_all_windows(),
+                    Self::MENU_MOVE_TO_NEW_WINDOW => match self.menu_tab {
+                        Some(index) => tab_bar.tear_off_tab(index),
+                        None => Ok(()),
+                    },
+                    id if window_menu_index(id).is_some() => {
+                        let window = self.menu_windows.get(window_menu_index(id).unwrap());
+                        match (self.menu_tab, window) {
+                            (Some(index), Some(&window)) => {
+                                tab_bar.move_tab_to_window(index, window)
+                            }
+                            _ => Ok(()),
+                        }
+                    }
+                    Self::MENU_BOOKMARK_TAB => match self.menu_tab {
+                        Some(index) => tab_bar.bookmark_tab(index, &[]),
+                        None => Ok(()),
+                    },
+                    id if frequent_menu_index(id).is_some() => {
+                        match self.menu_frequent.get(frequent_menu_index(id).unwrap()) {
+                            Some(entry) => tab_bar.open_tab(entry.get_idl()),
+                            None => Ok(()),
+                        }
+                    }
+                    id if history_menu_offset(id).is_some() => match self.menu_tab {
+                        Some(index) => {
+                            tab_bar.navigate_history(index, history_menu_offset(id).unwrap())
+                        }
+                        None => Ok(()),
+                    },
+                    id if (Self::MENU_TAB_COLOR_DEFAULT
+                        ..=Self::MENU_TAB_COLOR_DEFAULT + PRESET_COLORS.len())
+                        .contains(&id) =>
+                    {
+                        let color = (id - Self::MENU_TAB_COLOR_DEFAULT)
+                            .checked_sub(1)
+                            .map(|offset| PRESET_COLORS[offset].1);
+                        match (self.menu_tab, color) {
+                            (Some(index), Some(color)) if tab_bar.is_group_header(index) => {
+                                tab_bar.set_group_color(index, color)
+                            }
+                            (Some(index), _) => tab_bar.set_tab_color(index, color),
+                            (None, _) => Ok(()),
+                        }
+                    }
                     _ => Ok(()),
                 },
                 WM_PAINT => match self.dark_mode {
-                    true => return LRESULT(self.paint(hwnd).is_ok() as _),
+                    true => return LRESULT(self.paint(hwnd, &tab_bar).is_ok() as _),
                     false => Ok(()),
                 },
-                WM_MBUTTONDOWN => match self.focused_tab {
-                    Some(index) => tab_bar.remove_tab(index),
-                    None => Ok(()),
-                },
-                WM_LBUTTONDOWN => self.handle_left_click(tab_bar, wparam.0),
-                WM_RBUTTONUP => return LRESULT(self.create_popup_menu().is_ok() as _),
+                WM_LBUTTONDOWN | WM_LBUTTONDBLCLK | WM_MBUTTONDOWN | WM_MBUTTONDBLCLK
+                | WM_RBUTTONUP => {
+                    let (button, kind) = match message {
+                        WM_LBUTTONDOWN => (MouseButton::Left, ClickKind::Click),
+                        WM_LBUTTONDBLCLK => (MouseButton::Left, ClickKind::DoubleClick),
+                        WM_MBUTTONDOWN => (MouseButton::Middle, ClickKind::Click),
+                        WM_MBUTTONDBLCLK => (MouseButton::Middle, ClickKind::DoubleClick),
+                        // The menu shows on release, right double clicks are plain clicks
+                        _ => (MouseButton::Right, ClickKind::Click),
+                    };
+                    match self.handle_click(tab_bar, button, kind, wparam, lparam) {
+                        Ok(true) => return LRESULT(0),
+                        result => result.map(|_| ()),
+                    }
+                }
+                WM_MOUSEWHEEL | WM_MOUSEHWHEEL => {
+                    match self.handle_mouse_wheel(&tab_bar, wparam, message == WM_MOUSEHWHEEL) {
+                        Ok(_) => return LRESULT(0),
+                        result => result,
+                    }
+                }
+                WM_LBUTTONUP => self.end_drag(&tab_bar, lparam),
+                WM_CAPTURECHANGED => {
+                    self.drag_tab = None;
+                    Ok(())
+                }
                 WM_MOUSEMOVE => unsafe {
                     let x = (lparam.0 & 0xffff) as i16;
                     let y = ((lparam.0 >> 16) & 0xffff) as i16;
@@ -496,8 +1316,21 @@ impl TabControl {
                     }
                     Ok(())
                 },
+                // The bookmarks button takes the right end of the band, beside the strip
+                WM_WINDOWPOSCHANGING => {
+                    let position = unsafe { &mut *(lparam.0 as *mut WINDOWPOS) };
+                    if position.flags & SWP_NOSIZE != SWP_NOSIZE {
+                        position.cx = (position.cx - BookmarksButton::WIDTH).max(0);
+                    }
+                    Ok(())
+                }
+                WM_SIZE | WM_MOVE => {
+                    tab_bar.strip_moved();
+                    Ok(())
+                }
                 WM_MOUSELEAVE => {
                     self.focused_tab = None;
+                    self.wheel.reset();
                     Ok(())
                 }
                 _ => Ok(()),
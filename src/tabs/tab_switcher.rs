@@ -0,0 +1,245 @@
+use std::ptr::{addr_of, addr_of_mut};
+
+use windows::core::{Result, PCWSTR};
+use windows::Win32::Foundation::*;
+use windows::Win32::Graphics::Gdi::*;
+use windows::Win32::Storage::FileSystem::FILE_FLAGS_AND_ATTRIBUTES;
+use windows::Win32::UI::Shell::*;
+use windows::Win32::UI::WindowsAndMessaging::*;
+
+use super::tab_bar::{TabBar, TabKey, TabPath, DLL_INSTANCE};
+
+const WIDTH: i32 = 400;
+const ROW_HEIGHT: i32 = 24;
+const PADDING: i32 = 6;
+const ICON_SIZE: i32 = 16;
+const MAX_ENTRIES: usize = 20;
+
+const BACKGROUND: u32 = 0x202020;
+const BG_SELECTED: u32 = 0x4d4d4d;
+const BORDER_COLOR: u32 = 0x2b2b2b;
+
+struct SwitcherEntry {
+    key: TabKey,
+    title: String,
+    icon: HICON,
+}
+
+fn get_small_icon(path: &TabPath) -> HICON {
+    let pidl = match path {
+        Some(pidl) => pidl,
+        None => return HICON(0),
+    };
+    let mut info = SHFILEINFOW::default();
+    unsafe {
+        SHGetFileInfoW(
+            PCWSTR(pidl.get() as _),
+            FILE_FLAGS_AND_ATTRIBUTES(0),
+            addr_of_mut!(info),
+            std::mem::size_of::<SHFILEINFOW>() as _,
+            SHGFI_PIDL | SHGFI_ICON | SHGFI_SMALLICON,
+        )
+    };
+    info.hIcon
+}
+
+/// Tabs in most recently used order, shown while ctrl is held after ctrl+tab.
+///
+/// The popup never takes the focus, keys keep going to the explorer window and the keyboard hook.
+pub struct TabSwitcher {
+    handle: HWND,
+    entries: Vec<SwitcherEntry>,
+    selected: usize,
+    font: HFONT,
+}
+
+impl TabSwitcher {
+    const SUBCLASS_UID: usize = 48;
+
+    pub extern "system" fn subclass_proc(
+        hwnd: HWND,
+        message: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+        _uid_subclass: usize,
+        ref_data: usize,
+    ) -> LRESULT {
+        let obj = ref_data as *const TabSwitcher;
+        match message {
+            WM_PAINT => {
+                unsafe { &*obj }.paint();
+                LRESULT(0)
+            }
+            WM_MOUSEACTIVATE => LRESULT(MA_NOACTIVATE as _),
+            WM_NCDESTROY => unsafe {
+                RemoveWindowSubclass(hwnd, Some(Self::subclass_proc), Self::SUBCLASS_UID);
+                DefSubclassProc(hwnd, message, wparam, lparam)
+            },
+            _ => unsafe { DefSubclassProc(hwnd, message, wparam, lparam) },
+        }
+    }
+
+    // Starts on the previously used tab, or on the least recent one when going backward
+    pub fn open(tab_bar: &TabBar, backward: bool) -> Result<Box<Self>> {
+        let entries: Vec<SwitcherEntry> = tab_bar
+            .get_recent_tabs()
+            .into_iter()
+            .take(MAX_ENTRIES)
+            .map(|(key, title, path)| SwitcherEntry {
+                key,
+                title,
+                icon: get_small_icon(&path),
+            })
+            .collect();
+        if entries.len() < 2 {
+            return Err(E_FAIL.into());
+        }
+
+        let owner = tab_bar.get_explorer_handle();
+        let mut owner_rect = RECT::default();
+        unsafe { GetWindowRect(owner, addr_of_mut!(owner_rect)).ok()? };
+        let height = entries.len() as i32 * ROW_HEIGHT + 2 * PADDING;
+        let x = owner_rect.left + (owner_rect.right - owner_rect.left - WIDTH) / 2;
+        let y = owner_rect.top + (owner_rect.bottom - owner_rect.top - height) / 2;
+
+        let handle = unsafe {
+            CreateWindowExW(
+                WS_EX_TOOLWINDOW | WS_EX_NOACTIVATE,
+                "STATIC",
+                "",
+                WS_POPUP | WS_BORDER,
+                x,
+                y,
+                WIDTH,
+                height,
+                owner,
+                HMENU(0),
+                DLL_INSTANCE.unwrap(),
+                std::ptr::null(),
+            )
+        };
+        if handle.0 == 0 {
+            return Err(E_FAIL.into());
+        }
+
+        let font = unsafe { SendMessageW(tab_bar.get_handle(), WM_GETFONT, WPARAM(0), LPARAM(0)) };
+        let selected = match backward {
+            true => entries.len() - 1,
+            false => 1,
+        };
+        let switcher = Box::new(TabSwitcher {
+            handle,
+            entries,
+            selected,
+            font: HFONT(font.0),
+        });
+        unsafe {
+            SetWindowSubclass(
+                handle,
+                Some(Self::subclass_proc),
+                Self::SUBCLASS_UID,
+                addr_of!(*switcher) as _,
+            );
+            ShowWindow(handle, SW_SHOWNOACTIVATE);
+        }
+        Ok(switcher)
+    }
+
+    pub fn move_selection(&mut self, backward: bool) {
+        let count = self.entries.len();
+        self.selected = match backward {
+            true => (self.selected + count - 1) % count,
+            false => (self.selected + 1) % count,
+        };
+        unsafe { InvalidateRect(self.handle, std::ptr::null(), false) };
+    }
+
+    pub fn selected_key(&self) -> TabKey {
+        self.entries[self.selected].key
+    }
+
+    fn paint(&self) {
+        unsafe {
+            let mut paint_struct: PAINTSTRUCT = Default::default();
+            let hdc = BeginPaint(self.handle, addr_of_mut!(paint_struct));
+            let mut client_rect = RECT::default();
+            GetClientRect(self.handle, addr_of_mut!(client_rect));
+            {
+                let brush = CreateSolidBrush(BACKGROUND);
+                FillRect(hdc, addr_of!(client_rect), brush);
+                DeleteObject(brush);
+            }
+
+            let hold_font = SelectObject(hdc, self.font);
+            SetBkMode(hdc, TRANSPARENT);
+            SetTextColor(hdc, 0xffffff);
+            for (index, entry) in self.entries.iter().enumerate() {
+                let top = PADDING + index as i32 * ROW_HEIGHT;
+                let row_rect = RECT {
+                    left: client_rect.left + PADDING,
+                    top,
+                    right: client_rect.right - PADDING,
+                    bottom: top + ROW_HEIGHT,
+                };
+                if index == self.selected {
+                    let brush = CreateSolidBrush(BG_SELECTED);
+                    FillRect(hdc, addr_of!(row_rect), brush);
+                    DeleteObject(brush);
+                    let pen = CreatePen(PS_SOLID, 1, BORDER_COLOR);
+                    let hold_pen = SelectObject(hdc, pen);
+                    let edges = [
+                        POINT {
+                            x: row_rect.left,
+                            y: row_rect.bottom - 1,
+                        },
+                        POINT {
+                            x: row_rect.right - 1,
+                            y: row_rect.bottom - 1,
+                        },
+                    ];
+                    Polyline(hdc, &edges);
+                    SelectObject(hdc, hold_pen);
+                    DeleteObject(pen);
+                }
+
+                if entry.icon.0 != 0 {
+                    DrawIconEx(
+                        hdc,
+                        row_rect.left + PADDING,
+                        top + (ROW_HEIGHT - ICON_SIZE) / 2,
+                        entry.icon,
+                        ICON_SIZE,
+                        ICON_SIZE,
+                        0,
+                        HBRUSH(0),
+                        DI_NORMAL,
+                    );
+                }
+                let mut text_rect = row_rect;
+                text_rect.left += 2 * PADDING + ICON_SIZE;
+                let title: Vec<u16> = entry.title.encode_utf16().collect();
+                DrawTextW(
+                    hdc,
+                    &title,
+                    addr_of_mut!(text_rect),
+                    DT_SINGLELINE | DT_VCENTER | DT_END_ELLIPSIS,
+                );
+            }
+            SelectObject(hdc, hold_font);
+            EndPaint(self.handle, addr_of_mut!(paint_struct));
+        }
+    }
+}
+
+impl Drop for TabSwitcher {
+    fn drop(&mut self) {
+        unsafe {
+            DestroyWindow(self.handle);
+            for entry in self.entries.iter() {
+                if entry.icon.0 != 0 {
+                    DestroyIcon(entry.icon);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+
+const SEPARATOR: &str = " — ";
+
+// Label made of the first `depth` components, compared case insensitively
+fn label_key(components: &[String], depth: usize) -> Vec<String> {
+    components
+        .iter()
+        .take(depth)
+        .map(|component| component.to_lowercase())
+        .collect()
+}
+
+fn format_title(components: &[String], depth: usize) -> String {
+    let name = match components.first() {
+        Some(name) => name.clone(),
+        None => return String::new(),
+    };
+    if depth <= 1 {
+        return name;
+    }
+
+    let parents: Vec<&str> = components[1..depth]
+        .iter()
+        .rev()
+        .map(String::as_str)
+        .collect();
+    format!("{}{}{}", name, SEPARATOR, parents.join("\\"))
+}
+
+/// Builds tab titles from path components, ordered from the folder itself up to the root.
+///
+/// Tabs sharing a name get parent folder names appended until their titles differ,
+/// e.g. `src — projA` and `src — projB`. Tabs showing the same folder keep the same title.
+pub fn disambiguate_titles(paths: &[Vec<String>]) -> Vec<String> {
+    let mut depths: Vec<usize> = paths.iter().map(|path| path.len().min(1)).collect();
+
+    loop {
+        let mut groups: HashMap<Vec<String>, Vec<usize>> = HashMap::new();
+        for (index, path) in paths.iter().enumerate() {
+            groups
+                .entry(label_key(path, depths[index]))
+                .or_default()
+                .push(index);
+        }
+
+        let mut changed = false;
+        for group in groups.values().filter(|group| group.len() > 1) {
+            for &index in group {
+                let path = &paths[index];
+                let has_other_path = group.iter().any(|&other| {
+                    label_key(&paths[other], usize::MAX) != label_key(path, usize::MAX)
+                });
+                if has_other_path && depths[index] < path.len() {
+                    depths[index] += 1;
+                    changed = true;
+                }
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    paths
+        .iter()
+        .zip(depths)
+        .map(|(path, depth)| format_title(path, depth))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Components the way explorer names them, from a folder up to "This PC"
+    fn path(components: &[&str]) -> Vec<String> {
+        components.iter().map(|c| c.to_string()).collect()
+    }
+
+    fn titles(paths: &[&[&str]]) -> Vec<String> {
+        let paths: Vec<_> = paths.iter().map(|p| path(p)).collect();
+        disambiguate_titles(&paths)
+    }
+
+    #[test]
+    fn single_tab_is_its_name() {
+        assert_eq!(
+            titles(&[&["src", "projA", "Local Disk (C:)", "This PC"]]),
+            vec!["src"]
+        );
+        assert!(disambiguate_titles(&[]).is_empty());
+    }
+
+    #[test]
+    fn identical_names_get_their_parent() {
+        assert_eq!(
+            titles(&[
+                &["src", "projA", "Local Disk (C:)", "This PC"],
+                &["src", "projB", "Local Disk (C:)", "This PC"],
+                &["docs", "projA", "Local Disk (C:)", "This PC"],
+            ]),
+            vec!["src — projA", "src — projB", "docs"]
+        );
+    }
+
+    #[test]
+    fn names_compare_ignoring_case() {
+        assert_eq!(
+            titles(&[
+                &["Src", "projA", "Local Disk (C:)", "This PC"],
+                &["src", "projB", "Local Disk (C:)", "This PC"],
+            ]),
+            vec!["Src — projA", "src — projB"]
+        );
+    }
+
+    #[test]
+    fn nested_common_suffixes_go_up_until_they_differ() {
+        assert_eq!(
+            titles(&[
+                &["src", "app", "projA", "Local Disk (C:)", "This PC"],
+                &["src", "app", "projB", "Local Disk (C:)", "This PC"],
+            ]),
+            vec!["src — projA\\app", "src — projB\\app"]
+        );
+    }
+
+    #[test]
+    fn only_the_clashing_tabs_grow() {
+        assert_eq!(
+            titles(&[
+                &["src", "app", "projA", "Local Disk (C:)", "This PC"],
+                &["src", "app", "projB", "Local Disk (C:)", "This PC"],
+                &["src", "lib", "projA", "Local Disk (C:)", "This PC"],
+            ]),
+            vec!["src — projA\\app", "src — projB\\app", "src — lib"]
+        );
+    }
+
+    #[test]
+    fn same_folder_keeps_the_same_title() {
+        assert_eq!(
+            titles(&[
+                &["src", "projA", "Local Disk (C:)", "This PC"],
+                &["SRC", "PROJA", "Local Disk (C:)", "This PC"],
+            ]),
+            vec!["src", "SRC"]
+        );
+    }
+
+    #[test]
+    fn drive_roots() {
+        assert_eq!(
+            titles(&[
+                &["Data", "Local Disk (C:)", "This PC"],
+                &["Data", "Backup (D:)", "This PC"],
+                &["Local Disk (C:)", "This PC"],
+            ]),
+            vec![
+                "Data — Local Disk (C:)",
+                "Data — Backup (D:)",
+                "Local Disk (C:)"
+            ]
+        );
+    }
+
+    #[test]
+    fn known_folder_beside_its_target() {
+        assert_eq!(
+            titles(&[
+                &["Downloads", "This PC"],
+                &["Downloads", "me", "Users", "Local Disk (C:)", "This PC"],
+            ]),
+            vec!["Downloads — This PC", "Downloads — me"]
+        );
+    }
+
+    #[test]
+    fn virtual_folders() {
+        // Folders under the desktop have no parent to show
+        assert_eq!(
+            titles(&[&["Recycle Bin"], &["Recycle Bin"]]),
+            vec!["Recycle Bin", "Recycle Bin"]
+        );
+        assert_eq!(
+            titles(&[&["Network"], &["Network", "Local Disk (C:)", "This PC"]]),
+            vec!["Network", "Network — Local Disk (C:)"]
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+use windows::core::{Abi, Interface, Result};
+use windows::Win32::Foundation::{E_FAIL, HWND, LPARAM, WPARAM};
+use windows::Win32::System::Com::{CoCreateInstance, IServiceProvider, CLSCTX_ALL, VARIANT};
+use windows::Win32::System::Ole::VT_I4;
+use windows::Win32::UI::Shell::{
+    IShellBrowser, IShellWindows, IWebBrowser2, SID_STopLevelBrowser, ShellBrowserWindow,
+    ShellWindows, SBSP_ABSOLUTE,
+};
+use windows::Win32::UI::WindowsAndMessaging::{RegisterWindowMessageW, SendMessageW};
+
+use crate::idl::Idl;
+use crate::workspaces::TabState;
+use crate::{MERGE_WINDOW_MESSAGE, RECEIVE_TAB_MESSAGE};
+
+use super::tab_bar::get_current_folder_path;
+
+// A torn off tab not picked up by then belongs to a window that failed to open
+const TEAR_OFF_TIMEOUT: Duration = Duration::from_secs(10);
+
+// IWebBrowser2 takes a VARIANT_BOOL
+const VARIANT_TRUE: i16 = -1;
+
+/// Tabs waiting for the window opened by tearing them off, with the handle of that window.
+static TORN_OFF_TABS: Lazy<Mutex<Vec<(Instant, HWND, TabState)>>> = Lazy::new(Default::default);
+
+// Windows being opened for torn off tabs, see `is_tearing_off`
+static TEARING_OFF: AtomicUsize = AtomicUsize::new(0);
+
+/// Hands a tab to the tab bar of another explorer window, true when it took it.
+///
+/// Every explorer window lives in the same process, the state is passed by pointer.
+pub fn send_tab(window: HWND, state: &TabState) -> bool {
+    unsafe {
+        let message_id = RegisterWindowMessageW(RECEIVE_TAB_MESSAGE);
+        SendMessageW(
+            window,
+            message_id,
+            WPARAM(0),
+            LPARAM(state as *const _ as _),
+        )
+        .0 == 1
+    }
+}
+
+/// Asks another window with a tab bar to hand its selected tab to `target` and close.
+pub fn request_merge(window: HWND, target: HWND) -> bool {
+    unsafe {
+        let message_id = RegisterWindowMessageW(MERGE_WINDOW_MESSAGE);
+        SendMessageW(window, message_id, WPARAM(target.0 as _), LPARAM(0)).0 == 1
+    }
+}
+
+/// Explorer windows of the session with the folder they show, whether they have a tab bar or not.
+pub fn get_shell_windows() -> Result<Vec<(HWND, Idl)>> {
+    let shell_windows: IShellWindows =
+        unsafe { CoCreateInstance(&ShellWindows, None, CLSCTX_ALL)? };
+    let count = unsafe { shell_windows.Count()? };
+    // Internet Explorer windows and windows still opening have no folder and are left out
+    Ok((0..count)
+        .filter_map(|index| get_shell_window(&shell_windows, index).ok())
+        .collect())
+}
+
+fn get_shell_window(shell_windows: &IShellWindows, index: i32) -> Result<(HWND, Idl)> {
+    let mut variant = VARIANT::default();
+    unsafe {
+        variant.Anonymous.Anonymous.vt = VT_I4.0 as u16;
+        variant.Anonymous.Anonymous.Anonymous.lVal = index;
+    }
+    let browser: IWebBrowser2 = unsafe { shell_windows.Item(&variant)? }.cast()?;
+    let handle = HWND(unsafe { browser.HWND()? }.0);
+
+    let shell_browser = get_top_level_browser(&browser)?;
+    let pidl = get_current_folder_path(&shell_browser).ok_or(E_FAIL)?;
+    Ok((handle, pidl))
+}
+
+fn get_top_level_browser(browser: &IWebBrowser2) -> Result<IShellBrowser> {
+    let service_provider: IServiceProvider = browser.cast()?;
+    let mut shell_browser: *mut std::ffi::c_void = std::ptr::null_mut();
+    unsafe {
+        service_provider.QueryService(
+            &SID_STopLevelBrowser,
+            &IShellBrowser::IID,
+            &mut shell_browser,
+        )?;
+        IShellBrowser::from_abi(shell_browser)
+    }
+}
+
+/// Opens a new window on a torn off tab, its tab bar picks up `state` with `take_torn_off_tab`.
+///
+/// The window is created hidden so the tab is tied to its handle before it browses anywhere,
+/// which also works for folders without a file system path.
+pub fn open_torn_off_window(state: TabState) -> Result<()> {
+    let pidl = Idl::from_hex(&state.idl).ok_or(E_FAIL)?;
+    TEARING_OFF.fetch_add(1, Ordering::SeqCst);
+    let result = create_torn_off_window(&pidl, state);
+    TEARING_OFF.fetch_sub(1, Ordering::SeqCst);
+    result
+}
+
+fn create_torn_off_window(pidl: &Idl, state: TabState) -> Result<()> {
+    unsafe {
+        let browser: IWebBrowser2 = CoCreateInstance(&ShellBrowserWindow, None, CLSCTX_ALL)?;
+        let window = HWND(browser.HWND()?.0);
+        add_torn_off_tab(window, state);
+        get_top_level_browser(&browser)?.BrowseObject(pidl.get(), SBSP_ABSOLUTE)?;
+        browser.SetVisible(VARIANT_TRUE)
+    }
+}
+
+fn add_torn_off_tab(window: HWND, state: TabState) {
+    let mut tabs = TORN_OFF_TABS.lock().unwrap();
+    tabs.retain(|(time, _, _)| time.elapsed() < TEAR_OFF_TIMEOUT);
+    tabs.push((Instant::now(), window, state));
+}
+
+/// True while a window for a torn off tab is being opened, it must not be routed into a tab.
+pub fn is_tearing_off() -> bool {
+    TEARING_OFF.load(Ordering::SeqCst) > 0
+}
+
+/// Takes the torn off tab `window` was opened for.
+pub fn take_torn_off_tab(window: HWND) -> Option<TabState> {
+    let mut tabs = TORN_OFF_TABS.lock().unwrap();
+    tabs.retain(|(time, _, _)| time.elapsed() < TEAR_OFF_TIMEOUT);
+    let index = tabs.iter().position(|(_, handle, _)| *handle == window)?;
+    Some(tabs.remove(index).2)
+}
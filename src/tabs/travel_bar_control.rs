@@ -1,22 +1,37 @@
-use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+use std::ptr::addr_of_mut;
+use std::rc::Weak;
+
+use windows::core::Result;
+use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, POINT, WPARAM};
 use windows::Win32::UI::{
-    Controls::TB_ENABLEBUTTON,
-    Shell::{DefSubclassProc, SetWindowSubclass},
-    WindowsAndMessaging::SendMessageW,
+    Controls::{TBBUTTON, TB_ENABLEBUTTON, TB_GETBUTTON, TB_HITTEST},
+    Shell::{DefSubclassProc, RemoveWindowSubclass, SetWindowSubclass},
+    WindowsAndMessaging::*,
 };
 
+use super::history_menu::{create_history_menu, history_menu_offset};
+use super::popup_menu::track_menu_at_cursor;
+use super::tab_bar::TabBar;
+
 pub struct TravelBarControl {
     handle: HWND,
+    tab_bar: Weak<TabBar>,
 }
 
 impl TravelBarControl {
+    pub const BACK_BUTTON: i32 = 256;
+    pub const FORWARD_BUTTON: i32 = 257;
+    // Dropdown arrow next to the forward button
+    pub const RECENT_LOCATIONS_BUTTON: i32 = 258;
+
+    const SUBCLASS_UID: usize = 0;
     pub extern "system" fn subclass_proc(
         hwnd: HWND,
         message: u32,
         wparam: WPARAM,
         lparam: LPARAM,
         _uid_subclass: usize,
-        _ref_data: usize,
+        ref_data: usize,
     ) -> LRESULT {
         if message == TB_ENABLEBUTTON {
             if lparam.0 & 0x10000 == 0 {
@@ -28,21 +43,108 @@ impl TravelBarControl {
                 };
             }
         }
+
+        let obj = unsafe { (ref_data as *const TravelBarControl).as_ref() };
+        if let Some(obj) = obj {
+            let show_history = match message {
+                WM_RBUTTONUP => matches!(
+                    obj.get_button_at(lparam),
+                    Some(Self::BACK_BUTTON) | Some(Self::FORWARD_BUTTON)
+                ),
+                WM_LBUTTONDOWN => obj.get_button_at(lparam) == Some(Self::RECENT_LOCATIONS_BUTTON),
+                _ => false,
+            };
+            if show_history {
+                if let Err(e) = obj.show_history_menu() {
+                    log::error!("Error showing history menu:{:?}", e);
+                }
+                return LRESULT(0);
+            }
+        }
         unsafe { DefSubclassProc(hwnd, message, wparam, lparam) }
     }
-    pub fn new(handle: HWND) -> Self {
-        unsafe { SetWindowSubclass(handle, Some(Self::subclass_proc), 0, 0) };
-        TravelBarControl { handle }
+
+    pub fn new(handle: HWND, tab_bar: Weak<TabBar>) -> Box<Self> {
+        let new = Box::new(TravelBarControl { handle, tab_bar });
+        unsafe {
+            SetWindowSubclass(
+                handle,
+                Some(Self::subclass_proc),
+                Self::SUBCLASS_UID,
+                &*new as *const _ as usize,
+            )
+        };
+        new
     }
 
-    pub fn set_button_active(&self, index: usize, active: bool) {
+    pub fn set_button_active(&self, id: i32, active: bool) {
         unsafe {
             SendMessageW(
                 self.handle,
                 TB_ENABLEBUTTON,
-                WPARAM(index as _),
+                WPARAM(id as _),
                 LPARAM(0x10000 + if active { 1 } else { 0 }),
             );
         }
     }
+
+    fn get_button_at(&self, lparam: LPARAM) -> Option<i32> {
+        let mut point = POINT {
+            x: (lparam.0 & 0xffff) as i16 as _,
+            y: ((lparam.0 >> 16) & 0xffff) as i16 as _,
+        };
+        let index = unsafe {
+            SendMessageW(
+                self.handle,
+                TB_HITTEST,
+                WPARAM(0),
+                LPARAM(addr_of_mut!(point) as _),
+            )
+        };
+        if index.0 < 0 {
+            return None;
+        }
+
+        let mut button = TBBUTTON::default();
+        let found = unsafe {
+            SendMessageW(
+                self.handle,
+                TB_GETBUTTON,
+                WPARAM(index.0 as _),
+                LPARAM(addr_of_mut!(button) as _),
+            )
+        };
+        match found.0 {
+            0 => None,
+            _ => Some(button.idCommand),
+        }
+    }
+
+    // Lists the history of the selected tab, so several steps can be taken at once
+    fn show_history_menu(&self) -> Result<()> {
+        let tab_bar = match self.tab_bar.upgrade() {
+            Some(tab_bar) => tab_bar,
+            None => return Ok(()),
+        };
+        let index = match tab_bar.get_selected_tab_index() {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+
+        let menu = create_history_menu(&tab_bar, index)?;
+        let command = track_menu_at_cursor(menu, self.handle)?;
+
+        match history_menu_offset(command) {
+            Some(offset) => tab_bar.navigate_history(index, offset),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for TravelBarControl {
+    fn drop(&mut self) {
+        unsafe {
+            RemoveWindowSubclass(self.handle, Some(Self::subclass_proc), Self::SUBCLASS_UID);
+        }
+    }
 }
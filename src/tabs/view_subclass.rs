@@ -0,0 +1,226 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Weak;
+
+use windows::core::{Interface, Result, PCWSTR};
+use windows::Win32::Foundation::*;
+use windows::Win32::Graphics::Gdi::ClientToScreen;
+use windows::Win32::System::Com::{CoCreateInstance, CLSCTX_INPROC_SERVER};
+use windows::Win32::UI::Accessibility::{
+    CUIAutomation, IUIAutomation, UIA_DataItemControlTypeId, UIA_ListItemControlTypeId,
+};
+use windows::Win32::UI::Input::KeyboardAndMouse::{GetKeyState, VK_SHIFT};
+use windows::Win32::UI::Shell::*;
+use windows::Win32::UI::WindowsAndMessaging::*;
+
+use crate::idl::Idl;
+
+use super::tab_bar::{get_idl_name, TabBar};
+
+/// Opens folders of the shell view in new tabs on middle click or ctrl+double click.
+///
+/// Explorer creates a new view for every folder, `attach` is called after each navigation.
+pub struct ViewSubclass {
+    tab_bar: Weak<TabBar>,
+    view_handle: Cell<HWND>,
+    folder_view: RefCell<Option<IFolderView>>,
+    // Created on the first click, kept for the life of the window
+    automation: RefCell<Option<IUIAutomation>>,
+    open_in_background: bool,
+}
+
+impl ViewSubclass {
+    const SUBCLASS_UID: usize = 49;
+
+    pub extern "system" fn subclass_proc(
+        hwnd: HWND,
+        message: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+        _uid_subclass: usize,
+        ref_data: usize,
+    ) -> LRESULT {
+        let obj = unsafe { &*(ref_data as *const ViewSubclass) };
+        let ctrl_held = wparam.0 & MK_CONTROL as usize != 0;
+        match message {
+            // Clicks elsewhere than on a folder keep their usual meaning
+            WM_MBUTTONDOWN if obj.open_item_at(lparam) => LRESULT(0),
+            WM_LBUTTONDBLCLK if ctrl_held && obj.open_item_at(lparam) => LRESULT(0),
+            WM_NCDESTROY => {
+                obj.detach();
+                unsafe { DefSubclassProc(hwnd, message, wparam, lparam) }
+            }
+            _ => unsafe { DefSubclassProc(hwnd, message, wparam, lparam) },
+        }
+    }
+
+    pub fn new(tab_bar: Weak<TabBar>, open_in_background: bool) -> Box<Self> {
+        Box::new(ViewSubclass {
+            tab_bar,
+            view_handle: Cell::new(HWND(0)),
+            folder_view: RefCell::new(None),
+            automation: RefCell::new(None),
+            open_in_background,
+        })
+    }
+
+    pub fn attach(&self, browser: &IShellBrowser) -> Result<()> {
+        self.detach();
+        let view = unsafe { browser.QueryActiveShellView()? };
+        let view_handle = unsafe { view.GetWindow()? };
+        // Clicks go to the item list inside the view window
+        let items_handle =
+            unsafe { FindWindowExW(view_handle, HWND(0), "DirectUIHWND", PCWSTR::default()) };
+        let handle = match items_handle {
+            HWND(0) => view_handle,
+            handle => handle,
+        };
+
+        *self.folder_view.borrow_mut() = Some(view.cast()?);
+        self.view_handle.set(handle);
+        unsafe {
+            SetWindowSubclass(
+                handle,
+                Some(Self::subclass_proc),
+                Self::SUBCLASS_UID,
+                self as *const _ as usize,
+            )
+            .ok()
+        }
+    }
+
+    fn detach(&self) {
+        let handle = self.view_handle.replace(HWND(0));
+        self.folder_view.borrow_mut().take();
+        if handle.0 != 0 {
+            unsafe { RemoveWindowSubclass(handle, Some(Self::subclass_proc), Self::SUBCLASS_UID) };
+        }
+    }
+
+    // Opens the folder under the mouse in a new tab, the selection is left as it is
+    fn open_item_at(&self, lparam: LPARAM) -> bool {
+        let point = POINT {
+            x: (lparam.0 & 0xffff) as i16 as _,
+            y: ((lparam.0 >> 16) & 0xffff) as i16 as _,
+        };
+        let folder = match self.get_folder_at(point) {
+            Ok(Some(folder)) => folder,
+            // Not on an item, or not on a folder
+            Ok(None) => return false,
+            Err(e) => {
+                log::error!("Could not hit test the view:{:?}", e);
+                return false;
+            }
+        };
+        let tab_bar = match self.tab_bar.upgrade() {
+            Some(tab_bar) => tab_bar,
+            None => return false,
+        };
+        // Shift does the opposite of the setting
+        let shift_held = unsafe { GetKeyState(VK_SHIFT.0 as i32) } < 0;
+        let background = self.open_in_background != shift_held;
+        if let Err(e) = tab_bar.open_tab_beside(Some(folder), background) {
+            log::error!("Could not open folder from view:{:?}", e);
+        }
+        true
+    }
+
+    fn automation(&self) -> Result<IUIAutomation> {
+        if let Some(automation) = self.automation.borrow().as_ref() {
+            return Ok(automation.clone());
+        }
+        let automation: IUIAutomation =
+            unsafe { CoCreateInstance(&CUIAutomation, None, CLSCTX_INPROC_SERVER)? };
+        *self.automation.borrow_mut() = Some(automation.clone());
+        Ok(automation)
+    }
+
+    // Name of the item under `point`, in the coordinates of the item list. Asks the view what is
+    // there, so it holds in every view mode and scroll position.
+    fn get_item_name_at(&self, mut point: POINT) -> Result<Option<String>> {
+        // Hits land on the icon, the label or a column of an item, a few levels below it
+        const MAX_DEPTH: usize = 4;
+        let automation = self.automation()?;
+        let list_handle = self.view_handle.get();
+        unsafe {
+            ClientToScreen(list_handle, &mut point);
+            let walker = automation.ControlViewWalker()?;
+            let mut element = automation.ElementFromPoint(point)?;
+            for _ in 0..MAX_DEPTH {
+                let control_type = element.CurrentControlType()?;
+                // Details view shows items as data items
+                if control_type == UIA_ListItemControlTypeId
+                    || control_type == UIA_DataItemControlTypeId
+                {
+                    return Ok(Some(element.CurrentName()?.to_string()));
+                }
+                // Empty space or a group header, up to the list itself
+                if element.CurrentNativeWindowHandle()? == list_handle {
+                    return Ok(None);
+                }
+                element = walker.GetParentElement(&element)?;
+            }
+            Ok(None)
+        }
+    }
+
+    // The item shown as `name`. Folders are named as they are parsed, other items like drives
+    // are looked for among the items of the view.
+    fn find_item(folder_view: &IFolderView, parent: &Idl, name: &str) -> Result<Option<Idl>> {
+        unsafe {
+            let mut child = std::ptr::null_mut();
+            let parsed = folder_view.GetFolder::<IShellFolder>()?.ParseDisplayName(
+                HWND(0),
+                None,
+                name,
+                std::ptr::null_mut(),
+                &mut child,
+                std::ptr::null_mut(),
+            );
+            if parsed.is_ok() {
+                let child = Idl::from_raw(child);
+                if get_idl_name(&parent.join(&child)) == name {
+                    return Ok(Some(child));
+                }
+            }
+            for index in 0..folder_view.ItemCount(SVGIO_ALLVIEW.0 as u32)? {
+                let child = Idl::from_raw(folder_view.Item(index)?);
+                if get_idl_name(&parent.join(&child)) == name {
+                    return Ok(Some(child));
+                }
+            }
+            Ok(None)
+        }
+    }
+
+    fn get_folder_at(&self, point: POINT) -> Result<Option<Idl>> {
+        let folder_view = self.folder_view.borrow().clone().ok_or(E_FAIL)?;
+        let name = match self.get_item_name_at(point)? {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        let parent = unsafe { folder_view.GetFolder::<IPersistFolder2>()?.GetCurFolder()? };
+        let parent = Idl::from_raw(parent);
+        let child = match Self::find_item(&folder_view, &parent, &name)? {
+            Some(child) => child,
+            None => return Ok(None),
+        };
+        unsafe {
+            // Zip files are folders too, but open in explorer as files
+            let mut attributes = (SFGAO_FOLDER | SFGAO_STREAM) as u32;
+            folder_view
+                .GetFolder::<IShellFolder>()?
+                .GetAttributesOf(&[child.get()], &mut attributes)?;
+            if attributes & SFGAO_FOLDER as u32 == 0 || attributes & SFGAO_STREAM as u32 != 0 {
+                return Ok(None);
+            }
+
+            Ok(Some(parent.join(&child)))
+        }
+    }
+}
+
+impl Drop for ViewSubclass {
+    fn drop(&mut self) {
+        self.detach();
+    }
+}
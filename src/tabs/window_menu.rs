@@ -0,0 +1,18 @@
+use windows::core::Result;
+use windows::Win32::Foundation::HWND;
+use windows::Win32::UI::WindowsAndMessaging::HMENU;
+
+use crate::window_registry::get_window_title;
+
+use super::popup_menu::{create_list_menu, WINDOW_MENU_IDS};
+
+/// Lists explorer windows by their title, the name of their selected folder.
+pub fn create_window_menu(windows: &[HWND]) -> Result<HMENU> {
+    let titles = windows.iter().map(|window| get_window_title(*window));
+    create_list_menu(WINDOW_MENU_IDS, titles, "No Other Windows")
+}
+
+/// Index of the entry selected in a menu made by `create_window_menu`.
+pub fn window_menu_index(id: usize) -> Option<usize> {
+    WINDOW_MENU_IDS.index(id)
+}
@@ -0,0 +1,51 @@
+use windows::core::{Result, PCWSTR};
+use windows::Win32::UI::WindowsAndMessaging::*;
+
+use super::popup_menu::WORKSPACE_MENU_IDS;
+
+// Each workspace gets a block of ids, one for each action
+const MAX_WORKSPACE_MENU_ENTRIES: usize = WORKSPACE_MENU_IDS.count() / WORKSPACE_ACTIONS.len();
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WorkspaceAction {
+    Open,
+    Append,
+    Overwrite,
+    Rename,
+    Delete,
+}
+
+const WORKSPACE_ACTIONS: [(WorkspaceAction, &str); 5] = [
+    (WorkspaceAction::Open, "Open"),
+    (WorkspaceAction::Append, "Add To Current Tabs"),
+    (WorkspaceAction::Overwrite, "Save Current Tabs Here"),
+    (WorkspaceAction::Rename, "Rename..."),
+    (WorkspaceAction::Delete, "Delete"),
+];
+
+/// Lists the workspaces with a submenu of actions for each, followed by `save_id`.
+pub fn create_workspace_menu(names: &[String], save_id: usize) -> Result<HMENU> {
+    let menu = unsafe { CreatePopupMenu()? };
+    for (index, name) in names.iter().take(MAX_WORKSPACE_MENU_ENTRIES).enumerate() {
+        let submenu = unsafe { CreatePopupMenu()? };
+        for (offset, (_, label)) in WORKSPACE_ACTIONS.iter().enumerate() {
+            let id = WORKSPACE_MENU_IDS.id(index * WORKSPACE_ACTIONS.len() + offset);
+            unsafe { AppendMenuW(submenu, MF_STRING, id.unwrap_or_default(), *label) };
+        }
+        unsafe { AppendMenuW(menu, MF_POPUP, submenu.0 as _, name.as_str()) };
+    }
+    unsafe {
+        if !names.is_empty() {
+            AppendMenuW(menu, MF_SEPARATOR, 0, PCWSTR::default());
+        }
+        AppendMenuW(menu, MF_STRING, save_id, "Save Tabs As Workspace...");
+    }
+    Ok(menu)
+}
+
+/// Workspace index and action selected in a menu made by `create_workspace_menu`.
+pub fn workspace_menu_command(id: usize) -> Option<(usize, WorkspaceAction)> {
+    let offset = WORKSPACE_MENU_IDS.index(id)?;
+    let index = offset / WORKSPACE_ACTIONS.len();
+    Some((index, WORKSPACE_ACTIONS[offset % WORKSPACE_ACTIONS.len()].0))
+}
@@ -0,0 +1,372 @@
+use serde::Deserialize;
+
+use crate::path_chars::{is_separator, path_chars_equal};
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DriveKind {
+    Fixed,
+    Removable,
+    Network,
+    Optical,
+    Ram,
+}
+
+/// Replaces the title of folders matching `pattern`.
+///
+/// Patterns are globs matched against the whole folder path, case insensitively.
+/// `*` matches inside a single path component, `**` across components and `?` one character.
+///
+/// The title template can use `{1}`..`{n}` for the text matched by each wildcard,
+/// `{name}` for the default folder name, `{path}` for the full path and `{drive}` for the drive.
+/// `{{` and `}}` produce literal braces.
+#[derive(Deserialize, Debug, Clone)]
+pub struct TitleRule {
+    pub pattern: String,
+    pub title: String,
+    // Only apply the rule on this kind of drive
+    #[serde(default)]
+    pub drive: Option<DriveKind>,
+}
+
+pub struct TitleContext<'a> {
+    pub path: &'a str,
+    pub name: &'a str,
+    pub drive: Option<DriveKind>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Literal(char),
+    AnyCharacter,
+    AnyInComponent,
+    AnyAcrossComponents,
+}
+
+fn tokenize(pattern: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                Token::AnyAcrossComponents
+            }
+            '*' => Token::AnyInComponent,
+            '?' => Token::AnyCharacter,
+            c => Token::Literal(c),
+        });
+    }
+    tokens
+}
+
+// Where to resume when the text stops matching, by making a wildcard match one more character
+#[derive(Clone, Copy)]
+struct Backtrack {
+    token: usize,
+    start: usize,
+    length: usize,
+    // Index of the wildcard's capture
+    capture: usize,
+}
+
+impl Backtrack {
+    fn extend(mut self, tokens: &[Token], text: &[char]) -> Option<Self> {
+        let next = *text.get(self.start + self.length)?;
+        if tokens[self.token] == Token::AnyInComponent && is_separator(next) {
+            return None;
+        }
+        self.length += 1;
+        Some(self)
+    }
+}
+
+// Only the last wildcard needs to be retried, as in the usual iterative glob match. A `*` can't
+// grow past a separator though, then the last `**` before it is retried instead.
+fn match_tokens(tokens: &[Token], text: &[char]) -> Option<Vec<String>> {
+    let mut captures: Vec<(usize, usize)> = Vec::new();
+    let mut last_wildcard: Option<Backtrack> = None;
+    let mut last_across: Option<Backtrack> = None;
+    let (mut token_index, mut text_index) = (0, 0);
+    loop {
+        let matched = match tokens.get(token_index) {
+            None if text_index == text.len() => break,
+            None => false,
+            Some(Token::Literal(c)) => text
+                .get(text_index)
+                .map_or(false, |t| path_chars_equal(*t, *c)),
+            Some(Token::AnyCharacter) => match text.get(text_index) {
+                Some(t) if !is_separator(*t) => {
+                    captures.push((text_index, text_index + 1));
+                    true
+                }
+                _ => false,
+            },
+            Some(token) => {
+                let backtrack = Backtrack {
+                    token: token_index,
+                    start: text_index,
+                    length: 0,
+                    capture: captures.len(),
+                };
+                captures.push((text_index, text_index));
+                last_wildcard = Some(backtrack);
+                if *token == Token::AnyAcrossComponents {
+                    last_across = Some(backtrack);
+                }
+                token_index += 1;
+                continue;
+            }
+        };
+        if matched {
+            token_index += 1;
+            text_index += 1;
+            continue;
+        }
+
+        let retry = last_wildcard
+            .and_then(|backtrack| backtrack.extend(tokens, text))
+            .or_else(|| last_across.and_then(|backtrack| backtrack.extend(tokens, text)))?;
+        if tokens[retry.token] == Token::AnyAcrossComponents {
+            last_across = Some(retry);
+        }
+        last_wildcard = Some(retry);
+        token_index = retry.token + 1;
+        text_index = retry.start + retry.length;
+        captures.truncate(retry.capture);
+        captures.push((retry.start, text_index));
+    }
+    Some(
+        captures
+            .into_iter()
+            .map(|(start, end)| text[start..end].iter().collect())
+            .collect(),
+    )
+}
+
+/// Matches a whole path against a glob pattern, returning the text captured by each wildcard.
+pub fn glob_match(pattern: &str, path: &str) -> Option<Vec<String>> {
+    let text: Vec<char> = path.trim_end_matches(is_separator).chars().collect();
+    let tokens = tokenize(pattern.trim_end_matches(is_separator));
+    match_tokens(&tokens, &text)
+}
+
+fn drive_of(path: &str) -> String {
+    let mut chars = path.chars();
+    match (chars.next(), chars.next()) {
+        (Some(letter), Some(':')) if letter.is_ascii_alphabetic() => format!("{}:", letter),
+        _ => String::new(),
+    }
+}
+
+/// Expands `{...}` placeholders, unknown placeholders are kept as they are.
+pub fn expand_template(template: &str, captures: &[String], context: &TitleContext) -> String {
+    let mut output = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                output.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                output.push('}');
+            }
+            '{' => {
+                let mut placeholder = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+                    placeholder.push(c);
+                }
+
+                let value = match placeholder.as_str() {
+                    _ if !closed => None,
+                    "name" => Some(context.name.to_owned()),
+                    "path" => Some(context.path.to_owned()),
+                    "drive" => Some(drive_of(context.path)),
+                    index => index
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|index| index.checked_sub(1))
+                        .and_then(|index| captures.get(index).cloned()),
+                };
+                match value {
+                    Some(value) => output.push_str(&value),
+                    None => {
+                        output.push('{');
+                        output.push_str(&placeholder);
+                        if closed {
+                            output.push('}');
+                        }
+                    }
+                }
+            }
+            c => output.push(c),
+        }
+    }
+    output
+}
+
+/// Title given by the first rule matching the context, if any.
+pub fn apply_title_rules(rules: &[TitleRule], context: &TitleContext) -> Option<String> {
+    rules
+        .iter()
+        .filter(|rule| rule.drive.is_none() || rule.drive == context.drive)
+        .find_map(|rule| {
+            glob_match(&rule.pattern, context.path)
+                .map(|captures| expand_template(&rule.title, &captures, context))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context<'a>(path: &'a str, name: &'a str) -> TitleContext<'a> {
+        TitleContext {
+            path,
+            name,
+            drive: Some(DriveKind::Fixed),
+        }
+    }
+
+    fn rule(pattern: &str, title: &str, drive: Option<DriveKind>) -> TitleRule {
+        TitleRule {
+            pattern: pattern.to_owned(),
+            title: title.to_owned(),
+            drive,
+        }
+    }
+
+    #[test]
+    fn literal_patterns() {
+        assert_eq!(glob_match("C:\\Users", "c:\\users"), Some(vec![]));
+        assert_eq!(glob_match("C:/Users/", "C:\\Users\\"), Some(vec![]));
+        assert_eq!(glob_match("C:\\Users", "C:\\Users\\me"), None);
+        assert_eq!(glob_match("C:\\Users\\me", "C:\\Users"), None);
+    }
+
+    #[test]
+    fn star_stays_in_a_component() {
+        assert_eq!(
+            glob_match("C:\\Users\\*\\Desktop", "C:\\Users\\me\\Desktop"),
+            Some(vec!["me".to_owned()])
+        );
+        assert_eq!(glob_match("C:\\Users\\*", "C:\\Users\\me\\Desktop"), None);
+        assert_eq!(glob_match("C:\\*", "C:\\"), None);
+        assert_eq!(
+            glob_match("C:\\Work\\*-*", "C:\\Work\\client-project-2"),
+            Some(vec!["client".to_owned(), "project-2".to_owned()])
+        );
+    }
+
+    #[test]
+    fn double_star_crosses_components() {
+        assert_eq!(
+            glob_match("C:\\**\\src", "C:\\Projects\\app\\src"),
+            Some(vec!["Projects\\app".to_owned()])
+        );
+        assert_eq!(
+            glob_match("**\\src", "D:\\src"),
+            Some(vec!["D:".to_owned()])
+        );
+        assert_eq!(glob_match("C:\\**\\src", "C:\\Projects\\app\\lib"), None);
+    }
+
+    #[test]
+    fn star_after_double_star_backtracks_to_it() {
+        // The `*` can't take "b\c", the `**` has to give up characters instead
+        assert_eq!(
+            glob_match("**\\*c", "a\\b\\c"),
+            Some(vec!["a\\b".to_owned(), "".to_owned()])
+        );
+        assert_eq!(
+            glob_match("**\\x*\\*.d", "x1\\x2\\y\\x3\\z.d"),
+            Some(vec!["x1\\x2\\y".to_owned(), "3".to_owned(), "z".to_owned()])
+        );
+    }
+
+    #[test]
+    fn question_mark_is_one_character() {
+        assert_eq!(
+            glob_match("C:\\Disk?", "C:\\Disk2"),
+            Some(vec!["2".to_owned()])
+        );
+        assert_eq!(glob_match("C:\\Disk?", "C:\\Disk"), None);
+        assert_eq!(glob_match("C:?Disk", "C:\\Disk"), None);
+    }
+
+    #[test]
+    fn long_paths_do_not_blow_up() {
+        let path = format!("C:\\{}", "a\\".repeat(200));
+        let pattern = "**\\*a\\**\\*a\\**\\*b";
+        assert_eq!(glob_match(pattern, &path), None);
+    }
+
+    #[test]
+    fn template_placeholders() {
+        let context = context("C:\\Users\\me\\Desktop", "Desktop");
+        let captures = vec!["me".to_owned()];
+        assert_eq!(
+            expand_template("{name} of {1} on {drive}", &captures, &context),
+            "Desktop of me on C:"
+        );
+        assert_eq!(
+            expand_template("{path}", &captures, &context),
+            "C:\\Users\\me\\Desktop"
+        );
+    }
+
+    #[test]
+    fn template_escapes_and_unknown_placeholders() {
+        let context = context("\\\\server\\share", "share");
+        assert_eq!(expand_template("{{name}}", &[], &context), "{name}");
+        assert_eq!(expand_template("{2} {0} {x}", &[], &context), "{2} {0} {x}");
+        assert_eq!(expand_template("{drive}|{name", &[], &context), "|{name");
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let rules = [
+            rule("C:\\Users\\*\\Desktop", "Desktop of {1}", None),
+            rule("C:\\Users\\**", "In users", None),
+        ];
+        assert_eq!(
+            apply_title_rules(&rules, &context("C:\\Users\\me\\Desktop", "Desktop")),
+            Some("Desktop of me".to_owned())
+        );
+        assert_eq!(
+            apply_title_rules(&rules, &context("C:\\Users\\me\\Music", "Music")),
+            Some("In users".to_owned())
+        );
+        assert_eq!(
+            apply_title_rules(&rules, &context("D:\\Music", "Music")),
+            None
+        );
+    }
+
+    #[test]
+    fn drive_kind_filters_rules() {
+        let rules = [
+            rule("**", "USB {name}", Some(DriveKind::Removable)),
+            rule("**", "{name} on {drive}", None),
+        ];
+        assert_eq!(
+            apply_title_rules(&rules, &context("E:\\Photos", "Photos")),
+            Some("Photos on E:".to_owned())
+        );
+        let removable = TitleContext {
+            drive: Some(DriveKind::Removable),
+            ..context("E:\\Photos", "Photos")
+        };
+        assert_eq!(
+            apply_title_rules(&rules, &removable),
+            Some("USB Photos".to_owned())
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use std::ffi::c_void;
+
+// Implements the explorer window, both hooked methods included
+pub const EXPLORER_FRAME_MODULE: &str = "explorerframe.dll";
+
+// Every COM vtable starts with QueryInterface, AddRef and Release
+pub const IUNKNOWN_METHOD_COUNT: usize = 3;
+
+/// A method of a COM interface, found by its index in the vtable of an object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VtableSlot {
+    pub interface: &'static str,
+    pub method: &'static str,
+    pub index: usize,
+    // Dll expected to implement the method, anything else means the layout is not the known one
+    pub module: &'static str,
+}
+
+impl VtableSlot {
+    pub const fn new(
+        interface: &'static str,
+        method: &'static str,
+        index: usize,
+        module: &'static str,
+    ) -> Self {
+        Self {
+            interface,
+            method,
+            index,
+            module,
+        }
+    }
+
+    /// Address of the slot in the vtable of `object`, a COM interface pointer.
+    ///
+    /// # Safety
+    /// `object` has to point to an object whose first field is a vtable pointer.
+    pub unsafe fn slot_address(&self, object: *const c_void) -> *const *const c_void {
+        let vtable = *(object as *const *const *const c_void);
+        vtable.add(self.index)
+    }
+}
+
+pub const SHELL_BROWSER_BROWSE_OBJECT: VtableSlot = VtableSlot::new(
+    "IShellBrowser",
+    "BrowseObject",
+    // After the two IOleWindow methods and six other IShellBrowser ones
+    IUNKNOWN_METHOD_COUNT + 8,
+    EXPLORER_FRAME_MODULE,
+);
+
+// Undocumented, from QTTabBar, opens the folders explorer would show in a new window
+pub const COMMON_EXPLORER_HOST_SHOW_WINDOW: VtableSlot = VtableSlot::new(
+    "ICommonExplorerHost",
+    "ShowWindow",
+    IUNKNOWN_METHOD_COUNT,
+    EXPLORER_FRAME_MODULE,
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe extern "system" fn first() {}
+    unsafe extern "system" fn second() {}
+    unsafe extern "system" fn third() {}
+
+    // Laid out like a COM object, a vtable pointer followed by the object data
+    #[repr(C)]
+    struct FakeObject {
+        vtable: *const *const c_void,
+        data: usize,
+    }
+
+    fn fake_vtable() -> Vec<*const c_void> {
+        let mut vtable = vec![std::ptr::null(); IUNKNOWN_METHOD_COUNT];
+        vtable.extend([first as *const c_void, second as _, third as _]);
+        vtable
+    }
+
+    #[test]
+    fn slot_address_indexes_the_vtable() {
+        let vtable = fake_vtable();
+        let object = FakeObject {
+            vtable: vtable.as_ptr(),
+            data: 0,
+        };
+        let object_pointer = &object as *const FakeObject as *const c_void;
+        let slot = VtableSlot::new("IFake", "Second", IUNKNOWN_METHOD_COUNT + 1, "fake.dll");
+        unsafe {
+            assert_eq!(slot.slot_address(object_pointer), vtable.as_ptr().add(4));
+            assert_eq!(*slot.slot_address(object_pointer), second as *const c_void);
+        }
+    }
+
+    #[test]
+    fn slot_address_ignores_object_data() {
+        let vtable = fake_vtable();
+        let slot = VtableSlot::new("IFake", "First", IUNKNOWN_METHOD_COUNT, "fake.dll");
+        for data in [0, 1, usize::MAX] {
+            let object = FakeObject {
+                vtable: vtable.as_ptr(),
+                data,
+            };
+            let function = unsafe { *slot.slot_address(&object as *const _ as *const c_void) };
+            assert_eq!(function, first as *const c_void);
+        }
+    }
+
+    #[test]
+    fn objects_sharing_a_vtable_share_the_slot() {
+        let vtable = fake_vtable();
+        let objects = [
+            FakeObject {
+                vtable: vtable.as_ptr(),
+                data: 1,
+            },
+            FakeObject {
+                vtable: vtable.as_ptr(),
+                data: 2,
+            },
+        ];
+        let slot = VtableSlot::new("IFake", "Third", IUNKNOWN_METHOD_COUNT + 2, "fake.dll");
+        let slots: Vec<_> = objects
+            .iter()
+            .map(|object| unsafe { slot.slot_address(object as *const _ as *const c_void) })
+            .collect();
+        assert_eq!(slots[0], slots[1]);
+        assert_eq!(unsafe { *slots[0] }, third as *const c_void);
+    }
+
+    #[test]
+    fn known_slots_skip_iunknown() {
+        assert_eq!(SHELL_BROWSER_BROWSE_OBJECT.index, 11);
+        assert_eq!(COMMON_EXPLORER_HOST_SHOW_WINDOW.index, 3);
+        assert_eq!(SHELL_BROWSER_BROWSE_OBJECT.module, EXPLORER_FRAME_MODULE);
+        assert_eq!(
+            COMMON_EXPLORER_HOST_SHOW_WINDOW.module,
+            EXPLORER_FRAME_MODULE
+        );
+    }
+}
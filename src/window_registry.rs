@@ -0,0 +1,72 @@
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use windows::Win32::Foundation::HWND;
+use windows::Win32::UI::WindowsAndMessaging::GetWindowTextW;
+
+/// Explorer windows with a tab bar, most recently active first.
+///
+/// The first window is the main one, folders explorer would open in new windows go there.
+/// When it closes, the window active before it takes over.
+#[derive(Default, Debug)]
+pub struct WindowRegistry {
+    windows: Vec<HWND>,
+}
+
+impl WindowRegistry {
+    // New windows are active when they open
+    pub fn register(&mut self, handle: HWND) {
+        self.activated(handle);
+    }
+
+    pub fn unregister(&mut self, handle: HWND) {
+        self.windows.retain(|window| *window != handle);
+    }
+
+    pub fn activated(&mut self, handle: HWND) {
+        self.unregister(handle);
+        self.windows.insert(0, handle);
+    }
+
+    pub fn main(&self) -> Option<HWND> {
+        self.windows.first().copied()
+    }
+
+    pub fn windows(&self) -> &[HWND] {
+        &self.windows
+    }
+}
+
+static WINDOW_REGISTRY: Lazy<Mutex<WindowRegistry>> = Lazy::new(Default::default);
+
+pub fn register_window(handle: HWND) {
+    WINDOW_REGISTRY.lock().unwrap().register(handle);
+}
+
+pub fn unregister_window(handle: HWND) {
+    WINDOW_REGISTRY.lock().unwrap().unregister(handle);
+}
+
+pub fn window_activated(handle: HWND) {
+    let mut registry = WINDOW_REGISTRY.lock().unwrap();
+    // Only windows with a tab bar are tracked
+    if registry.windows().contains(&handle) {
+        registry.activated(handle);
+    }
+}
+
+pub fn main_window() -> Option<HWND> {
+    WINDOW_REGISTRY.lock().unwrap().main()
+}
+
+// Explorer titles its windows after the selected folder
+pub fn get_window_title(window: HWND) -> String {
+    let mut title = [0u16; 260];
+    let length = unsafe { GetWindowTextW(window, &mut title) } as usize;
+    String::from_utf16_lossy(&title[..length])
+}
+
+/// Every window with a tab bar, most recently active first.
+pub fn all_windows() -> Vec<HWND> {
+    WINDOW_REGISTRY.lock().unwrap().windows().to_vec()
+}
@@ -0,0 +1,99 @@
+use std::error::Error;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+pub const WORKSPACES_FILE_NAME: &str = "workspaces.json";
+
+/// What is needed to bring a tab back.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TabState {
+    // Folder name when the tab was saved
+    pub name: String,
+    pub path: String,
+    // Serialized id list of the folder
+    pub idl: String,
+    #[serde(default)]
+    pub custom_title: Option<String>,
+    // The custom title stays when the tab navigates away
+    #[serde(default)]
+    pub custom_title_sticky: bool,
+    // Serialized id lists of the tab history, nearest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub backward: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub forward: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Workspace {
+    pub name: String,
+    pub tabs: Vec<TabState>,
+    #[serde(default)]
+    pub selected: Option<usize>,
+}
+
+/// Named sets of tabs, shared by the tab bar and `extabctl`.
+///
+/// Read from disk before every use, so changes made by another process are picked up.
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct Workspaces {
+    workspaces: Vec<Workspace>,
+}
+
+impl Workspaces {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let file = match std::fs::File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Default::default()),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let file = std::fs::File::create(path)?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    pub fn list(&self) -> &[Workspace] {
+        &self.workspaces
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.workspaces
+            .iter()
+            .position(|workspace| workspace.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Workspace> {
+        self.workspaces.get(self.position(name)?)
+    }
+
+    // Replaces any workspace with the same name
+    pub fn set(&mut self, workspace: Workspace) {
+        match self.position(&workspace.name) {
+            Some(index) => self.workspaces[index] = workspace,
+            None => self.workspaces.push(workspace),
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Workspace> {
+        let index = self.position(name)?;
+        Some(self.workspaces.remove(index))
+    }
+
+    pub fn rename(&mut self, name: &str, new_name: &str) -> Result<(), String> {
+        let index = self
+            .position(name)
+            .ok_or_else(|| format!("no workspace named {:?}", name))?;
+        if let Some(other) = self.position(new_name) {
+            if other != index {
+                return Err(format!("a workspace named {:?} already exists", new_name));
+            }
+        }
+        self.workspaces[index].name = new_name.to_owned();
+        Ok(())
+    }
+}